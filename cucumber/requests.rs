@@ -85,7 +85,7 @@ async fn handled(world: &mut ReqWorld, step: &Step) {
     let config = Config::test(config_text, ()).await.unwrap();
     let mut host = String::from("example.com:3000");
 
-    let _ = config
+    config
         .modify_request(&mut host, &mut world.subject)
         .await
         .unwrap();
@@ -96,10 +96,10 @@ async fn filter(world: &mut ReqWorld, config: String) {
     let mut pre = r#"target("example.com:3000"):"#.to_string();
     pre.push_str(&config);
     let config_text: &'static str = String::leak(pre); // we don't care about leaks in tests
-    let config = Config::test(&config_text, ()).await.unwrap();
+    let config = Config::test(config_text, ()).await.unwrap();
     let mut host = String::from("example.com:3000");
 
-    let _ = config
+    config
         .modify_request(&mut host, &mut world.subject)
         .await
         .unwrap();
@@ -33,7 +33,7 @@ async fn handled(world: &mut ResWorld, step: &Step) {
     let config = Config::test(config_text, ()).await.unwrap();
     let mut host = String::from("example.com:3000");
 
-    let _ = config
+    config
         .modify_response(&mut host, &mut world.subject)
         .await
         .unwrap();
@@ -44,10 +44,10 @@ async fn filter(world: &mut ResWorld, config: String) {
     let mut pre = r#"target("example.com:3000"):"#.to_string();
     pre.push_str(&config);
     let config_text: &'static str = String::leak(pre); // we don't care about leaks in tests
-    let config = Config::test(&config_text, ()).await.unwrap();
+    let config = Config::test(config_text, ()).await.unwrap();
     let mut host = String::from("example.com:3000");
 
-    let _ = config
+    config
         .modify_response(&mut host, &mut world.subject)
         .await
         .unwrap();
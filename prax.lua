@@ -1,7 +1,12 @@
 --- @meta _
 
+-- Modules `require`d while the config loads are watched along with it (--watch),
+-- a module first required from inside a function (a rule or hook) is not,
+-- require modules at the top level for changes to them to reload the config
+
 --- @return nil
 --- only focus the proxy on request that match a target ref
+--- tls connections to other hosts are tunneled through without interception
 function focus() end
 
 --- @param name string
@@ -14,14 +19,61 @@ TargetRef = {}
 
 --- @param ... Rule | RedirectRule Rules to add to target reference
 --- @return TargetRef
---- add request rules to the current target
+--- add request rules to the current target,
+--- rules that can never apply to requests (like setting the status) raise an error
 function TargetRef:req(...) end
 
 --- @param ... Rule
 --- @return TargetRef
---- add response rules to the current target
+--- add response rules to the current target,
+--- rules that can never apply to responses (like redirects or setting the method) raise an error
 function TargetRef:resp(...) end
 
+--- @param cert string path to a pem encoded client certificate
+--- @param key string path to the pem encoded private key
+--- @return TargetRef
+--- authenticate to the target with a client certificate (mTLS)
+function TargetRef:identity(cert, key) end
+
+--- @class Message
+--- A request or response handed to hooks, changes made to it are applied
+--- @field method? string
+--- @field path? string
+--- @field query? QueryParam[] in order, repeated names included
+--- @field status? integer
+--- @field headers table<string, string>
+--- @field body string
+
+--- @class QueryParam
+--- A query parameter, percent decoded (`+` is kept as is) and encoded again if changed
+--- @field name string
+--- @field value? string nil for a bare name without `=`
+
+--- @alias Outcome nil | "continue" | "drop" | Message
+--- returning nothing continues, "drop" aborts the exchange and a table answers with it as the response
+
+--- @param hook fun(req: Message): Outcome
+--- @return TargetRef
+--- run a function over requests to the target
+function TargetRef:on_request(hook) end
+
+--- @param hook fun(req: Message, res: Message): Outcome
+--- @return TargetRef
+--- run a function over responses from the target
+function TargetRef:on_response(hook) end
+
+--- @class Timeouts
+--- @field connect? integer milliseconds allowed for each attempt to connect
+--- @field read? integer milliseconds allowed waiting on the target to respond
+--- @field total? integer milliseconds allowed for the whole exchange
+--- @field retries? integer times to retry connecting
+--- @field backoff? integer milliseconds before the first retry, doubling each attempt
+
+--- @param opts Timeouts
+--- @return TargetRef
+--- override how long to wait on the target, unset fields use the proxy's defaults
+function TargetRef:timeouts(opts) end
+
 --- @class Attr
 --- An attribute of a response or request
 
@@ -43,17 +95,40 @@ function header(name) end
 function query(name) end
 
 --- @param attr Attr
---- @param value string
+--- @param value string | Var
 --- @return Rule
 --- set a value for a given Attr
 function set(attr, value) end
 
 --- @param attr Attr
---- @param transform string | fun(string): string
+--- @param name string
+--- @return Rule
+--- store the value of an Attr in `state` under name
+function capture(attr, name) end
+
+--- @class Var
+--- A value looked up in `state` when a rule is applied
+
+--- @param name string
+--- @return Var
+--- refer to a captured value, rules using it are skipped until it is captured
+function var(name) end
+
+--- @class CommandOpts
+--- @field timeout? integer milliseconds the command may run before it is killed (defaults to 10000)
+--- @field env? table<string, string> replaces the command's environment, PATH is kept
+
+--- @param attr Attr
+--- @param transform string | string[] | fun(string): string
+--- @param opts? CommandOpts only for commands
 --- @return Rule
 ---
---- substitute a value for a given Attr
-function sub(attr, transform) end
+--- substitute a value for a given Attr,
+--- a string transform is a shell command given the value on stdin and replacing it with stdout,
+--- a list like { "jq", "." } runs the command directly without a shell,
+--- commands failing or timing out leave the value as it was and log their stderr,
+--- status is handed to transforms as a number and read back as a whole number
+function sub(attr, transform, opts) end
 
 --- @param host string
 --- @return Rule
@@ -61,6 +136,27 @@ function sub(attr, transform) end
 --- redirect requests to a new host
 function redirect(host) end
 
+--- @param min integer milliseconds
+--- @param max? integer milliseconds, waits a random time between min and max when given
+--- @return Rule
+--- hold the message back before passing it on
+function delay(min, max) end
+
+--- @param rate integer bytes per second
+--- @return Rule
+--- pass the body on slowly
+function throttle(rate) end
+
+--- @return Rule
+--- close the connection instead of passing the message on
+function drop() end
+
+--- @param status integer
+--- @param probability? number between 0 and 1, defaults to always
+--- @return Rule
+--- answer with a made up response instead of passing the message on
+function fail(status, probability) end
+
 --- @type Rule
 dump = nil
 
@@ -78,3 +174,37 @@ path = nil
 
 --- @type Attr
 body = nil
+
+--- @type table<string, any>
+--- values shared by every lua state running the config,
+--- the config runs once in each of the --lua-states states, so top level code runs that many times
+--- and locals captured by functions are kept per state, use `state` for what functions should share,
+--- values are copied in and out so assign tables back after changing them,
+--- kept across reloads with --keep-state
+state = nil
+
+--- @class Codec
+--- @field encode fun(data: string): string
+--- @field decode fun(text: string): string
+
+--- @class Jwt
+--- @field decode fun(token: string, secret?: string): table, table claims and header, the signature is checked when given a secret
+--- @field encode fun(claims: table, secret: string, alg?: "HS256" | "HS384" | "HS512"): string
+
+--- @class Prax
+--- Helpers for encoding and signing, binary data is passed as lua strings
+--- @field base64 Codec
+--- @field base64url Codec unpadded, decoding tolerates padding
+--- @field url Codec percent encoding
+--- @field hex Codec
+--- @field sha1 fun(data: string): string raw digest
+--- @field sha256 fun(data: string): string raw digest
+--- @field hmac fun(alg: "sha1" | "sha256" | "sha384" | "sha512", key: string, data: string): string raw mac
+--- @field random fun(len: integer): string random bytes
+--- @field uuid fun(): string a random (v4) uuid
+--- @field now fun(): integer seconds since the unix epoch
+--- @field millis fun(): integer milliseconds since the unix epoch
+--- @field jwt Jwt
+
+--- @type Prax
+prax = nil
@@ -1,6 +1,21 @@
-use futures::Future;
+use std::sync::Arc;
 
-use super::{Req, Res};
+use futures::{future::BoxFuture, Future};
+
+use super::{Req, Res, Upstream};
+
+/// What the proxy does with a message once a filter is done with it
+#[derive(Debug)]
+pub enum Outcome {
+    /// pass the (possibly modified) message on
+    Continue,
+
+    /// answer with this response instead, for a response it replaces the upstream's
+    Respond(Res<Vec<u8>>),
+
+    /// close the connection without passing the message on
+    Drop,
+}
 
 /// A trait for modifying in flight requests
 pub trait Filter {
@@ -8,13 +23,13 @@ pub trait Filter {
         &self,
         hostname: &mut String,
         req: &mut Req<Vec<u8>>,
-    ) -> impl Future<Output = crate::Result<()>> + Send;
+    ) -> impl Future<Output = crate::Result<Outcome>> + Send;
 
     fn modify_response(
         &self,
         hostname: &mut String,
         req: &mut Res<Vec<u8>>,
-    ) -> impl Future<Output = crate::Result<()>> + Send;
+    ) -> impl Future<Output = crate::Result<Outcome>> + Send;
 }
 
 impl Filter for () {
@@ -22,16 +37,184 @@ impl Filter for () {
         &self,
         _: &mut String,
         _: &mut super::Req<Vec<u8>>,
-    ) -> crate::Result<()> {
-        Ok(())
+    ) -> crate::Result<Outcome> {
+        Ok(Outcome::Continue)
     }
 
     async fn modify_response(
         &self,
         _: &mut String,
         _: &mut super::Res<Vec<u8>>,
-    ) -> crate::Result<()> {
-        Ok(())
+    ) -> crate::Result<Outcome> {
+        Ok(Outcome::Continue)
+    }
+}
+
+/// Applies `A` then `B`, stopping at the first filter that does not continue
+#[derive(Clone, Debug, Default)]
+pub struct Chain<A, B>(pub A, pub B);
+
+/// Applies a filter only to hostnames ("host:port") the predicate accepts
+#[derive(Clone)]
+pub struct When<F, P> {
+    pub filter: F,
+    pub predicate: P,
+}
+
+/// An object safe [`Filter`], for pipelines picked at runtime
+///
+/// Every `Filter` that is also an [`Upstream`] is a `DynFilter`,
+/// and `Box<dyn DynFilter>` is both again, ready for the server.
+pub trait DynFilter: Upstream + Send + Sync {
+    fn modify_request_boxed<'a>(
+        &'a self,
+        hostname: &'a mut String,
+        req: &'a mut Req<Vec<u8>>,
+    ) -> BoxFuture<'a, crate::Result<Outcome>>;
+
+    fn modify_response_boxed<'a>(
+        &'a self,
+        hostname: &'a mut String,
+        res: &'a mut Res<Vec<u8>>,
+    ) -> BoxFuture<'a, crate::Result<Outcome>>;
+}
+
+impl<F, P> When<F, P>
+where
+    P: Fn(&str) -> bool,
+{
+    pub fn new(predicate: P, filter: F) -> Self {
+        When { filter, predicate }
+    }
+}
+
+impl<A, B> Filter for Chain<A, B>
+where
+    A: Filter + Sync,
+    B: Filter + Sync,
+{
+    async fn modify_request(
+        &self,
+        hostname: &mut String,
+        req: &mut Req<Vec<u8>>,
+    ) -> crate::Result<Outcome> {
+        match self.0.modify_request(hostname, req).await? {
+            Outcome::Continue => self.1.modify_request(hostname, req).await,
+            outcome => Ok(outcome),
+        }
+    }
+
+    async fn modify_response(
+        &self,
+        hostname: &mut String,
+        res: &mut Res<Vec<u8>>,
+    ) -> crate::Result<Outcome> {
+        match self.0.modify_response(hostname, res).await? {
+            Outcome::Continue => self.1.modify_response(hostname, res).await,
+            outcome => Ok(outcome),
+        }
+    }
+}
+
+impl<F, P> Filter for When<F, P>
+where
+    F: Filter + Sync,
+    P: Fn(&str) -> bool + Sync,
+{
+    async fn modify_request(
+        &self,
+        hostname: &mut String,
+        req: &mut Req<Vec<u8>>,
+    ) -> crate::Result<Outcome> {
+        if !(self.predicate)(hostname) {
+            return Ok(Outcome::Continue);
+        }
+
+        self.filter.modify_request(hostname, req).await
+    }
+
+    async fn modify_response(
+        &self,
+        hostname: &mut String,
+        res: &mut Res<Vec<u8>>,
+    ) -> crate::Result<Outcome> {
+        if !(self.predicate)(hostname) {
+            return Ok(Outcome::Continue);
+        }
+
+        self.filter.modify_response(hostname, res).await
+    }
+}
+
+impl<F: Filter + ?Sized> Filter for Arc<F> {
+    fn modify_request(
+        &self,
+        hostname: &mut String,
+        req: &mut Req<Vec<u8>>,
+    ) -> impl Future<Output = crate::Result<Outcome>> + Send {
+        (**self).modify_request(hostname, req)
+    }
+
+    fn modify_response(
+        &self,
+        hostname: &mut String,
+        res: &mut Res<Vec<u8>>,
+    ) -> impl Future<Output = crate::Result<Outcome>> + Send {
+        (**self).modify_response(hostname, res)
+    }
+}
+
+impl<F: Filter + ?Sized> Filter for Box<F> {
+    fn modify_request(
+        &self,
+        hostname: &mut String,
+        req: &mut Req<Vec<u8>>,
+    ) -> impl Future<Output = crate::Result<Outcome>> + Send {
+        (**self).modify_request(hostname, req)
+    }
+
+    fn modify_response(
+        &self,
+        hostname: &mut String,
+        res: &mut Res<Vec<u8>>,
+    ) -> impl Future<Output = crate::Result<Outcome>> + Send {
+        (**self).modify_response(hostname, res)
+    }
+}
+
+impl<F: Filter + Upstream + Send + Sync> DynFilter for F {
+    fn modify_request_boxed<'a>(
+        &'a self,
+        hostname: &'a mut String,
+        req: &'a mut Req<Vec<u8>>,
+    ) -> BoxFuture<'a, crate::Result<Outcome>> {
+        Box::pin(self.modify_request(hostname, req))
+    }
+
+    fn modify_response_boxed<'a>(
+        &'a self,
+        hostname: &'a mut String,
+        res: &'a mut Res<Vec<u8>>,
+    ) -> BoxFuture<'a, crate::Result<Outcome>> {
+        Box::pin(self.modify_response(hostname, res))
+    }
+}
+
+impl Filter for dyn DynFilter {
+    async fn modify_request(
+        &self,
+        hostname: &mut String,
+        req: &mut Req<Vec<u8>>,
+    ) -> crate::Result<Outcome> {
+        self.modify_request_boxed(hostname, req).await
+    }
+
+    async fn modify_response(
+        &self,
+        hostname: &mut String,
+        res: &mut Res<Vec<u8>>,
+    ) -> crate::Result<Outcome> {
+        self.modify_response_boxed(hostname, res).await
     }
 }
 
@@ -49,8 +232,11 @@ async fn test_null_filter() {
 
     let mut host = String::from("example.com");
 
-    ().modify_request(&mut host, &mut req).await.unwrap();
-    ().modify_response(&mut host, &mut res).await.unwrap();
+    let outcome = ().modify_request(&mut host, &mut req).await.unwrap();
+    assert!(matches!(outcome, Outcome::Continue));
+
+    let outcome = ().modify_response(&mut host, &mut res).await.unwrap();
+    assert!(matches!(outcome, Outcome::Continue));
 
     assert_eq!(req.uri(), init_req.uri());
     assert_eq!(req.headers(), init_req.headers());
@@ -60,3 +246,81 @@ async fn test_null_filter() {
     assert_eq!(res.headers(), init_res.headers());
     assert_eq!(res.body(), init_res.body());
 }
+
+/// adds a header named after itself to each message
+#[cfg(test)]
+struct Stamp(&'static str);
+
+#[cfg(test)]
+impl Upstream for Stamp {}
+
+#[cfg(test)]
+impl Filter for Stamp {
+    async fn modify_request(
+        &self,
+        _: &mut String,
+        req: &mut Req<Vec<u8>>,
+    ) -> crate::Result<Outcome> {
+        req.headers_mut().insert(self.0, "1".parse().unwrap());
+        Ok(Outcome::Continue)
+    }
+
+    async fn modify_response(
+        &self,
+        _: &mut String,
+        res: &mut Res<Vec<u8>>,
+    ) -> crate::Result<Outcome> {
+        res.headers_mut().insert(self.0, "1".parse().unwrap());
+        Ok(Outcome::Drop)
+    }
+}
+
+#[tokio::test]
+async fn test_chain() {
+    let chain = Chain(Stamp("x-first"), Arc::new(Stamp("x-second")));
+    let mut host = String::from("example.com:443");
+
+    let mut req = Req::new(Vec::new());
+    let outcome = chain.modify_request(&mut host, &mut req).await.unwrap();
+
+    assert!(matches!(outcome, Outcome::Continue));
+    assert!(req.headers().contains_key("x-first"));
+    assert!(req.headers().contains_key("x-second"));
+
+    // the first drops responses, the second never sees them
+    let mut res = Res::new(Vec::new());
+    let outcome = chain.modify_response(&mut host, &mut res).await.unwrap();
+
+    assert!(matches!(outcome, Outcome::Drop));
+    assert!(res.headers().contains_key("x-first"));
+    assert!(!res.headers().contains_key("x-second"));
+}
+
+#[tokio::test]
+async fn test_when() {
+    let when = When::new(|host: &str| host.ends_with(":443"), Stamp("x-tls"));
+
+    let mut req = Req::new(Vec::new());
+    when.modify_request(&mut String::from("example.com:80"), &mut req)
+        .await
+        .unwrap();
+    assert!(!req.headers().contains_key("x-tls"));
+
+    when.modify_request(&mut String::from("example.com:443"), &mut req)
+        .await
+        .unwrap();
+    assert!(req.headers().contains_key("x-tls"));
+}
+
+#[tokio::test]
+async fn test_dyn_filter() {
+    let filters: Vec<Box<dyn DynFilter>> = vec![Box::new(()), Box::new(Stamp("x-dyn"))];
+    let mut host = String::from("example.com:443");
+    let mut req = Req::new(Vec::new());
+
+    for filter in &filters {
+        filter.modify_request(&mut host, &mut req).await.unwrap();
+    }
+
+    assert!(req.headers().contains_key("x-dyn"));
+}
@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::Req;
+
+/// Marks a message whose body was streamed through and only partially recorded
+///
+/// The proxy inserts markers into a message's extensions before handing it to
+/// a [`Scribe`](super::Scribe) so that the record can reflect what happened on the wire.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Truncated;
+
+/// Marks a message handed to filters without its body, because the body is streamed through
+///
+/// Inserted before filtering, changes filters make to the body of such a message are not applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Withheld;
+
+/// Marks a response whose body is passed through as it arrives
+///
+/// The rest of the body is reported with [`Scribe::report_chunk`](super::Scribe::report_chunk).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Live;
+
+/// The certificate chain (DER encoded) an upstream server presented
+/// for a message sent through a tls tunnel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeerCertificates(pub Arc<Vec<Vec<u8>>>);
+
+/// Marks the response of a CONNECT tunnel that was passed through without
+/// interception, inserted once the tunnel closes
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tunneled {
+    /// bytes sent from the client to the upstream, only counted if the tunnel closed cleanly
+    pub sent: u64,
+
+    /// bytes received from the upstream, only counted if the tunnel closed cleanly
+    pub received: u64,
+
+    /// how long the tunnel was open
+    pub duration: Duration,
+
+    /// why the tunnel broke off, if it did
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// The request a response answers, as it was sent upstream
+///
+/// Inserted into a response's extensions before it is filtered,
+/// the request keeps the extensions filters gave it.
+#[derive(Clone, Debug)]
+pub struct Origin(pub Arc<Req<Vec<u8>>>);
+
+/// Something a rule did to simulate a faulty backend
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Fault {
+    /// held back before being passed on
+    Delayed(Duration),
+
+    /// body passed on at most this many bytes a second
+    Throttled(u64),
+
+    /// answered by the proxy with this status instead of being passed on
+    Failed(u16),
+
+    /// connection closed without passing the message on
+    Dropped,
+}
+
+/// Marks a message that rules tampered with, so its record shows it was synthetic
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Faults(pub Vec<Fault>);
+
+impl Faults {
+    /// adds `fault` to the faults already marked in `extensions`
+    pub fn record(extensions: &mut http::Extensions, fault: Fault) {
+        match extensions.get_mut::<Faults>() {
+            Some(Faults(faults)) => faults.push(fault),
+            None => {
+                extensions.insert(Faults(vec![fault]));
+            }
+        }
+    }
+
+    /// the rate the body should be throttled to, the slowest if throttled more than once
+    pub fn throttle(extensions: &http::Extensions) -> Option<u64> {
+        let Faults(faults) = extensions.get::<Faults>()?;
+
+        faults
+            .iter()
+            .filter_map(|fault| match fault {
+                Fault::Throttled(rate) => Some(*rate),
+                _ => None,
+            })
+            .min()
+    }
+}
+
+impl std::fmt::Display for Fault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fault::Delayed(delay) => write!(f, "delayed {}ms", delay.as_millis()),
+            Fault::Throttled(rate) => write!(f, "throttled to {rate} bytes/s"),
+            Fault::Failed(status) => write!(f, "failed with {status}"),
+            Fault::Dropped => write!(f, "dropped"),
+        }
+    }
+}
@@ -1,10 +1,14 @@
 mod filter;
+mod marker;
 mod report;
 mod scribe;
+mod upstream;
 
 pub type Req<T> = hyper::Request<T>;
 pub type Res<T> = hyper::Response<T>;
 
 pub use filter::*;
+pub use marker::*;
 pub use report::*;
 pub use scribe::*;
+pub use upstream::*;
@@ -1,10 +1,12 @@
-use futures::Future;
+use std::{any::Any, sync::Arc};
+
+use futures::{future::BoxFuture, Future};
 
 use super::{Req, Res};
 
 /// A trait to add to a history store
 pub trait Scribe {
-    type Ticket: Send;
+    type Ticket: Clone + Send;
 
     fn report_request(&self, req: &Req<Vec<u8>>) -> impl Future<Output = Self::Ticket> + Send;
     fn report_response(
@@ -12,6 +14,18 @@ pub trait Scribe {
         ticket: Self::Ticket,
         res: &Res<Vec<u8>>,
     ) -> impl Future<Output = ()> + Send;
+
+    /// report more of a live response body as it streams through
+    fn report_chunk(&self, ticket: Self::Ticket, chunk: &[u8]) -> impl Future<Output = ()> + Send {
+        let _ = (ticket, chunk);
+        async {}
+    }
+
+    /// the live response body has ended, whether in full or cut off
+    fn report_end(&self, ticket: Self::Ticket) -> impl Future<Output = ()> + Send {
+        let _ = ticket;
+        async {}
+    }
 }
 
 impl Scribe for () {
@@ -21,6 +35,193 @@ impl Scribe for () {
     async fn report_response(&self, _: Self::Ticket, _: &super::Res<Vec<u8>>) {}
 }
 
+/// Reports to both `A` and `B`, like a history plus a log file
+#[derive(Clone, Debug, Default)]
+pub struct Tee<A, B>(pub A, pub B);
+
+/// The ticket of a [`DynScribe`], whatever the scribe behind it uses
+pub type DynTicket = Arc<dyn Any + Send + Sync>;
+
+/// An object safe [`Scribe`], for scribes picked at runtime
+///
+/// Every `Scribe` with a `'static` ticket is a `DynScribe`, and `Box<dyn DynScribe>` is a `Scribe` again.
+pub trait DynScribe: Send + Sync {
+    fn report_request_boxed<'a>(&'a self, req: &'a Req<Vec<u8>>) -> BoxFuture<'a, DynTicket>;
+
+    fn report_response_boxed<'a>(
+        &'a self,
+        ticket: DynTicket,
+        res: &'a Res<Vec<u8>>,
+    ) -> BoxFuture<'a, ()>;
+
+    fn report_chunk_boxed<'a>(&'a self, ticket: DynTicket, chunk: &'a [u8]) -> BoxFuture<'a, ()>;
+
+    fn report_end_boxed(&self, ticket: DynTicket) -> BoxFuture<'_, ()>;
+}
+
+impl<A, B> Scribe for Tee<A, B>
+where
+    A: Scribe + Sync,
+    B: Scribe + Sync,
+{
+    type Ticket = (A::Ticket, B::Ticket);
+
+    async fn report_request(&self, req: &Req<Vec<u8>>) -> Self::Ticket {
+        futures::join!(self.0.report_request(req), self.1.report_request(req))
+    }
+
+    async fn report_response(&self, (a, b): Self::Ticket, res: &Res<Vec<u8>>) {
+        futures::join!(
+            self.0.report_response(a, res),
+            self.1.report_response(b, res)
+        );
+    }
+
+    async fn report_chunk(&self, (a, b): Self::Ticket, chunk: &[u8]) {
+        futures::join!(self.0.report_chunk(a, chunk), self.1.report_chunk(b, chunk));
+    }
+
+    async fn report_end(&self, (a, b): Self::Ticket) {
+        futures::join!(self.0.report_end(a), self.1.report_end(b));
+    }
+}
+
+impl<S: Scribe + Sync + ?Sized> Scribe for &S {
+    type Ticket = S::Ticket;
+
+    fn report_request(&self, req: &Req<Vec<u8>>) -> impl Future<Output = Self::Ticket> + Send {
+        (**self).report_request(req)
+    }
+
+    fn report_response(
+        &self,
+        ticket: Self::Ticket,
+        res: &Res<Vec<u8>>,
+    ) -> impl Future<Output = ()> + Send {
+        (**self).report_response(ticket, res)
+    }
+
+    fn report_chunk(&self, ticket: Self::Ticket, chunk: &[u8]) -> impl Future<Output = ()> + Send {
+        (**self).report_chunk(ticket, chunk)
+    }
+
+    fn report_end(&self, ticket: Self::Ticket) -> impl Future<Output = ()> + Send {
+        (**self).report_end(ticket)
+    }
+}
+
+impl<S: Scribe + ?Sized> Scribe for Arc<S> {
+    type Ticket = S::Ticket;
+
+    fn report_request(&self, req: &Req<Vec<u8>>) -> impl Future<Output = Self::Ticket> + Send {
+        (**self).report_request(req)
+    }
+
+    fn report_response(
+        &self,
+        ticket: Self::Ticket,
+        res: &Res<Vec<u8>>,
+    ) -> impl Future<Output = ()> + Send {
+        (**self).report_response(ticket, res)
+    }
+
+    fn report_chunk(&self, ticket: Self::Ticket, chunk: &[u8]) -> impl Future<Output = ()> + Send {
+        (**self).report_chunk(ticket, chunk)
+    }
+
+    fn report_end(&self, ticket: Self::Ticket) -> impl Future<Output = ()> + Send {
+        (**self).report_end(ticket)
+    }
+}
+
+impl<S: Scribe + ?Sized> Scribe for Box<S> {
+    type Ticket = S::Ticket;
+
+    fn report_request(&self, req: &Req<Vec<u8>>) -> impl Future<Output = Self::Ticket> + Send {
+        (**self).report_request(req)
+    }
+
+    fn report_response(
+        &self,
+        ticket: Self::Ticket,
+        res: &Res<Vec<u8>>,
+    ) -> impl Future<Output = ()> + Send {
+        (**self).report_response(ticket, res)
+    }
+
+    fn report_chunk(&self, ticket: Self::Ticket, chunk: &[u8]) -> impl Future<Output = ()> + Send {
+        (**self).report_chunk(ticket, chunk)
+    }
+
+    fn report_end(&self, ticket: Self::Ticket) -> impl Future<Output = ()> + Send {
+        (**self).report_end(ticket)
+    }
+}
+
+impl<S> DynScribe for S
+where
+    S: Scribe + Send + Sync,
+    S::Ticket: Sync + 'static,
+{
+    fn report_request_boxed<'a>(&'a self, req: &'a Req<Vec<u8>>) -> BoxFuture<'a, DynTicket> {
+        Box::pin(async move {
+            let ticket: DynTicket = Arc::new(self.report_request(req).await);
+            ticket
+        })
+    }
+
+    fn report_response_boxed<'a>(
+        &'a self,
+        ticket: DynTicket,
+        res: &'a Res<Vec<u8>>,
+    ) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            match ticket.downcast_ref::<S::Ticket>() {
+                Some(ticket) => self.report_response(ticket.clone(), res).await,
+                None => tracing::error!("response reported with another scribe's ticket"),
+            }
+        })
+    }
+
+    fn report_chunk_boxed<'a>(&'a self, ticket: DynTicket, chunk: &'a [u8]) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            match ticket.downcast_ref::<S::Ticket>() {
+                Some(ticket) => self.report_chunk(ticket.clone(), chunk).await,
+                None => tracing::error!("chunk reported with another scribe's ticket"),
+            }
+        })
+    }
+
+    fn report_end_boxed(&self, ticket: DynTicket) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            match ticket.downcast_ref::<S::Ticket>() {
+                Some(ticket) => self.report_end(ticket.clone()).await,
+                None => tracing::error!("end reported with another scribe's ticket"),
+            }
+        })
+    }
+}
+
+impl Scribe for dyn DynScribe {
+    type Ticket = DynTicket;
+
+    async fn report_request(&self, req: &Req<Vec<u8>>) -> Self::Ticket {
+        self.report_request_boxed(req).await
+    }
+
+    async fn report_response(&self, ticket: Self::Ticket, res: &Res<Vec<u8>>) {
+        self.report_response_boxed(ticket, res).await
+    }
+
+    async fn report_chunk(&self, ticket: Self::Ticket, chunk: &[u8]) {
+        self.report_chunk_boxed(ticket, chunk).await
+    }
+
+    async fn report_end(&self, ticket: Self::Ticket) {
+        self.report_end_boxed(ticket).await
+    }
+}
+
 #[tokio::test]
 async fn test_null() {
     let req = Req::builder()
@@ -34,3 +235,27 @@ async fn test_null() {
     let ticket = ().report_request(&req).await;
     ().report_response(ticket, &res).await;
 }
+
+#[tokio::test]
+async fn test_tee() {
+    let hist = crate::hist::Hist::default();
+    let shared = Arc::new(crate::hist::Hist::default());
+    let boxed: Box<dyn DynScribe> = Box::new(shared.clone());
+    let tee = Tee(&hist, boxed);
+
+    let req = Req::builder()
+        .method("GET")
+        .header("Host", "example.com")
+        .body(Vec::new())
+        .unwrap();
+
+    let res = Res::builder().status(204).body(Vec::new()).unwrap();
+
+    let ticket = tee.report_request(&req).await;
+    tee.report_response(ticket, &res).await;
+
+    for hist in [&hist, &*shared] {
+        let entry = hist.entry(0).unwrap();
+        assert_eq!(entry.response.map(|res| res.status), Some(204));
+    }
+}
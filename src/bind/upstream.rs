@@ -0,0 +1,240 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A client certificate and private key (pem) to authenticate with upstream
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Identity {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Overrides for how long to wait on an upstream, unset fields fall back to the proxy's defaults
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    /// time allowed for each attempt to connect
+    pub connect: Option<Duration>,
+
+    /// time allowed waiting on the upstream to respond
+    pub read: Option<Duration>,
+
+    /// time allowed for the whole exchange
+    pub total: Option<Duration>,
+
+    /// how many times to retry connecting
+    pub retries: Option<usize>,
+
+    /// wait before the first retry, doubling with each attempt
+    pub backoff: Option<Duration>,
+}
+
+/// A trait for per host settings on how the proxy connects upstream
+pub trait Upstream {
+    /// the client certificate to present to `hostname` ("host:port")
+    fn identity(&self, hostname: &str) -> Option<Identity> {
+        let _ = hostname;
+        None
+    }
+
+    /// overrides for how long to wait on `hostname` ("host:port")
+    fn timeouts(&self, hostname: &str) -> Timeouts {
+        let _ = hostname;
+        Timeouts::default()
+    }
+
+    /// whether traffic to `hostname` ("host:port") should be intercepted,
+    /// out of scope tls connections are tunneled through untouched
+    fn in_scope(&self, hostname: &str) -> bool {
+        let _ = hostname;
+        true
+    }
+}
+
+impl Upstream for () {}
+
+impl<U: Upstream + ?Sized> Upstream for std::sync::Arc<U> {
+    fn identity(&self, hostname: &str) -> Option<Identity> {
+        (**self).identity(hostname)
+    }
+
+    fn timeouts(&self, hostname: &str) -> Timeouts {
+        (**self).timeouts(hostname)
+    }
+
+    fn in_scope(&self, hostname: &str) -> bool {
+        (**self).in_scope(hostname)
+    }
+}
+
+impl<U: Upstream + ?Sized> Upstream for Box<U> {
+    fn identity(&self, hostname: &str) -> Option<Identity> {
+        (**self).identity(hostname)
+    }
+
+    fn timeouts(&self, hostname: &str) -> Timeouts {
+        (**self).timeouts(hostname)
+    }
+
+    fn in_scope(&self, hostname: &str) -> bool {
+        (**self).in_scope(hostname)
+    }
+}
+
+/// settings of the first filter win field by field, a host is in scope if both want it
+impl<A: Upstream, B: Upstream> Upstream for super::Chain<A, B> {
+    fn identity(&self, hostname: &str) -> Option<Identity> {
+        self.0
+            .identity(hostname)
+            .or_else(|| self.1.identity(hostname))
+    }
+
+    fn timeouts(&self, hostname: &str) -> Timeouts {
+        let a = self.0.timeouts(hostname);
+        let b = self.1.timeouts(hostname);
+
+        Timeouts {
+            connect: a.connect.or(b.connect),
+            read: a.read.or(b.read),
+            total: a.total.or(b.total),
+            retries: a.retries.or(b.retries),
+            backoff: a.backoff.or(b.backoff),
+        }
+    }
+
+    fn in_scope(&self, hostname: &str) -> bool {
+        self.0.in_scope(hostname) && self.1.in_scope(hostname)
+    }
+}
+
+/// hosts the predicate rejects get the defaults
+impl<F, P> Upstream for super::When<F, P>
+where
+    F: Upstream,
+    P: Fn(&str) -> bool,
+{
+    fn identity(&self, hostname: &str) -> Option<Identity> {
+        if !(self.predicate)(hostname) {
+            return None;
+        }
+
+        self.filter.identity(hostname)
+    }
+
+    fn timeouts(&self, hostname: &str) -> Timeouts {
+        if !(self.predicate)(hostname) {
+            return Timeouts::default();
+        }
+
+        self.filter.timeouts(hostname)
+    }
+
+    fn in_scope(&self, hostname: &str) -> bool {
+        !(self.predicate)(hostname) || self.filter.in_scope(hostname)
+    }
+}
+
+/// a single host with settings of its own, kept out of scope if `scoped` is false
+#[cfg(test)]
+struct Host {
+    name: &'static str,
+    timeouts: Timeouts,
+    scoped: bool,
+}
+
+#[cfg(test)]
+impl Upstream for Host {
+    fn identity(&self, hostname: &str) -> Option<Identity> {
+        (hostname == self.name).then(|| Identity {
+            cert: PathBuf::from(format!("{}.crt", self.name)),
+            key: PathBuf::from(format!("{}.key", self.name)),
+        })
+    }
+
+    fn timeouts(&self, hostname: &str) -> Timeouts {
+        if hostname == self.name {
+            self.timeouts.clone()
+        } else {
+            Timeouts::default()
+        }
+    }
+
+    fn in_scope(&self, hostname: &str) -> bool {
+        hostname != self.name || self.scoped
+    }
+}
+
+#[test]
+fn test_chain_upstream() {
+    use super::Chain;
+
+    let first = Host {
+        name: "a.test:443",
+        timeouts: Timeouts {
+            connect: Some(Duration::from_secs(1)),
+            ..Timeouts::default()
+        },
+        scoped: true,
+    };
+
+    let second = Host {
+        name: "a.test:443",
+        timeouts: Timeouts {
+            connect: Some(Duration::from_secs(2)),
+            retries: Some(3),
+            ..Timeouts::default()
+        },
+        scoped: false,
+    };
+
+    let chain = Chain(first, second);
+
+    assert_eq!(
+        chain.identity("a.test:443").map(|id| id.cert),
+        Some(PathBuf::from("a.test:443.crt"))
+    );
+    assert_eq!(chain.identity("b.test:443"), None);
+
+    assert_eq!(
+        chain.timeouts("a.test:443"),
+        Timeouts {
+            connect: Some(Duration::from_secs(1)),
+            retries: Some(3),
+            ..Timeouts::default()
+        }
+    );
+    assert_eq!(chain.timeouts("b.test:443"), Timeouts::default());
+
+    // the second keeps its host out even though the first would not
+    assert!(!chain.in_scope("a.test:443"));
+    assert!(chain.in_scope("b.test:443"));
+    assert!(Chain((), ()).in_scope("b.test:443"));
+}
+
+#[test]
+fn test_when_upstream() {
+    use super::When;
+
+    let host = Host {
+        name: "a.test:443",
+        timeouts: Timeouts {
+            read: Some(Duration::from_secs(5)),
+            ..Timeouts::default()
+        },
+        scoped: false,
+    };
+
+    let when = When::new(|host: &str| host.ends_with(":443"), host);
+
+    assert!(when.identity("a.test:443").is_some());
+    assert_eq!(
+        when.timeouts("a.test:443").read,
+        Some(Duration::from_secs(5))
+    );
+    assert!(!when.in_scope("a.test:443"));
+
+    // rejected hosts get the defaults, in scope included
+    let when = When::new(|_: &str| false, when);
+
+    assert_eq!(when.identity("a.test:443"), None);
+    assert_eq!(when.timeouts("a.test:443"), Timeouts::default());
+    assert!(when.in_scope("a.test:443"));
+}
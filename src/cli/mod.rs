@@ -1,5 +1,7 @@
 use clap::Parser;
-use std::{net::SocketAddr, path::PathBuf, str::FromStr};
+use prax::server::{Policy, Streaming, TlsOptions};
+use prax::Identity;
+use std::{net::SocketAddr, path::PathBuf, str::FromStr, time::Duration};
 
 /// an attack proxy designed with neovim in mind
 #[derive(Parser)]
@@ -13,11 +15,20 @@ pub struct Cli {
     #[clap(short = 'f', long = "file")]
     pub configure: Option<PathBuf>,
 
-    /// watch configure script
+    /// lua states to run config functions on concurrently, each runs the whole config
+    #[clap(long, default_value_t = 4)]
+    pub lua_states: usize,
+
+    /// watch configure script and any lua files it requires while loading
     #[clap(short, long, requires = "configure")]
     #[cfg(target_os = "linux")]
     pub watch: bool,
 
+    /// keep captured values and lua state when the configure script is reloaded
+    #[clap(long, requires = "watch")]
+    #[cfg(target_os = "linux")]
+    pub keep_state: bool,
+
     /// log file
     #[clap(short = 'L', long)]
     pub log: Option<PathBuf>,
@@ -32,6 +43,25 @@ pub struct Cli {
 
     #[clap(flatten)]
     pub tls: CertOpts,
+
+    #[clap(flatten)]
+    pub streaming: StreamOpts,
+
+    #[clap(flatten)]
+    pub timeouts: TimeoutOpts,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// load a configure script and report problems with it
+    Check {
+        /// configure script
+        #[clap(short = 'f', long = "file")]
+        configure: PathBuf,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -49,6 +79,78 @@ pub struct CertOpts {
     /// certificate for a tls session
     #[clap(short, long, requires = "key")]
     pub cert: Option<PathBuf>,
+
+    /// extra certificate authority (pem) to trust for upstream servers
+    #[clap(long = "ca", requires = "key")]
+    pub cas: Vec<PathBuf>,
+
+    /// upstream host to skip certificate verification for
+    #[clap(long = "insecure", requires = "key")]
+    pub insecure: Vec<String>,
+
+    /// pin an upstream host to a certificate's sha256 fingerprint (host=fingerprint),
+    /// a pinned certificate is accepted without validating its chain, expiry or name
+    #[clap(long = "pin", requires = "key")]
+    pub pins: Vec<Pin>,
+
+    /// client certificate and key (pem) to present to an upstream host (host=cert,key)
+    #[clap(long = "identity", requires = "key")]
+    pub identities: Vec<HostIdentity>,
+}
+
+#[derive(Clone, Debug)]
+pub struct HostIdentity {
+    pub host: String,
+    pub identity: Identity,
+}
+
+#[derive(Clone, Debug)]
+pub struct Pin {
+    pub host: String,
+    pub fingerprint: [u8; 32],
+}
+
+#[derive(Parser)]
+pub struct StreamOpts {
+    /// body size in bytes after which bodies are streamed rather than buffered
+    #[clap(long, default_value_t = 0x800000)]
+    pub stream_threshold: usize,
+
+    /// content type (or prefix like "video/") of bodies to always stream
+    #[clap(long = "stream-type")]
+    pub stream_types: Vec<String>,
+
+    /// content type of bodies to pass through as they arrive and record live
+    #[clap(long = "live-type", default_value = "text/event-stream")]
+    pub live_types: Vec<String>,
+
+    /// milliseconds a body of unknown length may stall before it is passed through live
+    /// (0 to only pass through live types)
+    #[clap(long, default_value_t = 0)]
+    pub stream_idle: u64,
+}
+
+#[derive(Parser)]
+pub struct TimeoutOpts {
+    /// milliseconds allowed for each attempt to connect upstream
+    #[clap(long, default_value_t = 10000)]
+    pub connect_timeout: u64,
+
+    /// milliseconds allowed waiting on the upstream to respond (0 to wait forever)
+    #[clap(long, default_value_t = 30000)]
+    pub read_timeout: u64,
+
+    /// milliseconds allowed for a whole exchange with the upstream (0 to wait forever)
+    #[clap(long, default_value_t = 0)]
+    pub total_timeout: u64,
+
+    /// times to retry connecting upstream
+    #[clap(long, default_value_t = 5)]
+    pub retries: usize,
+
+    /// milliseconds to wait before the first retry, doubling with each attempt
+    #[clap(long, default_value_t = 250)]
+    pub backoff: u64,
 }
 
 impl FromStr for NvimConnInfo {
@@ -63,9 +165,156 @@ impl FromStr for NvimConnInfo {
     }
 }
 
+impl FromStr for Pin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((host, hex)) = s.split_once('=') else {
+            return Err("expected host=fingerprint".to_string());
+        };
+
+        let hex: Vec<u8> = hex.bytes().filter(|b| *b != b':').collect();
+        if hex.len() != 64 {
+            return Err("fingerprint should be a hex encoded sha256".to_string());
+        }
+
+        let mut fingerprint = [0u8; 32];
+        for (byte, pair) in fingerprint.iter_mut().zip(hex.chunks(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|e| e.to_string())?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|e| e.to_string())?;
+        }
+
+        Ok(Pin {
+            host: host.to_string(),
+            fingerprint,
+        })
+    }
+}
+
+impl FromStr for HostIdentity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((host, pair)) = s.split_once('=') else {
+            return Err("expected host=cert,key".to_string());
+        };
+
+        let Some((cert, key)) = pair.split_once(',') else {
+            return Err("expected a cert and key separated by a comma".to_string());
+        };
+
+        Ok(HostIdentity {
+            host: host.to_string(),
+            identity: Identity {
+                cert: PathBuf::from(cert),
+                key: PathBuf::from(key),
+            },
+        })
+    }
+}
+
+impl CertOpts {
+    /// what the proxy needs to intercept tls, if a key and cert were given
+    pub fn options(self) -> Option<TlsOptions> {
+        let (Some(key), Some(cert)) = (self.key, self.cert) else {
+            return None;
+        };
+
+        Some(TlsOptions {
+            key,
+            cert,
+            cas: self.cas,
+            insecure: self.insecure,
+            pins: self
+                .pins
+                .into_iter()
+                .map(|pin| (pin.host, pin.fingerprint))
+                .collect(),
+            identities: self
+                .identities
+                .into_iter()
+                .map(|id| (id.host, id.identity))
+                .collect(),
+        })
+    }
+}
+
+impl From<StreamOpts> for Streaming {
+    fn from(opts: StreamOpts) -> Self {
+        Streaming {
+            threshold: opts.stream_threshold,
+            types: opts.stream_types,
+            live: opts.live_types,
+            idle: (opts.stream_idle != 0).then(|| Duration::from_millis(opts.stream_idle)),
+        }
+    }
+}
+
+impl From<TimeoutOpts> for Policy {
+    fn from(opts: TimeoutOpts) -> Self {
+        let optional = |ms| (ms != 0).then(|| Duration::from_millis(ms));
+
+        Policy {
+            connect: Duration::from_millis(opts.connect_timeout),
+            read: optional(opts.read_timeout),
+            total: optional(opts.total_timeout),
+            retries: opts.retries,
+            backoff: Duration::from_millis(opts.backoff),
+        }
+    }
+}
+
 impl NvimConnInfo {
     /// whether this connection method should kill the proxy
     pub fn singleton(&self) -> bool {
         matches!(self, NvimConnInfo::Stdin)
     }
 }
+
+#[test]
+fn test_pin() {
+    let hex = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+    let pin: Pin = format!("example.com={hex}").parse().unwrap();
+
+    assert_eq!(pin.host, "example.com");
+    assert_eq!(pin.fingerprint[..4], [0x00, 0x11, 0x22, 0x33]);
+    assert_eq!(pin.fingerprint[31], 0xff);
+
+    // colon separated as openssl prints them
+    let colons = hex
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| std::str::from_utf8(pair).unwrap())
+        .collect::<Vec<_>>()
+        .join(":");
+    let same: Pin = format!("example.com={colons}").parse().unwrap();
+    assert_eq!(same.fingerprint, pin.fingerprint);
+
+    assert!("example.com".parse::<Pin>().is_err());
+    assert!("example.com=0011".parse::<Pin>().is_err());
+    assert!(format!("example.com={}", hex.replace('a', "g"))
+        .parse::<Pin>()
+        .is_err());
+}
+
+#[test]
+fn test_tls_flags_need_key() {
+    let pin = format!("example.com={}", "ab".repeat(32));
+
+    for flag in [
+        ["--ca", "ca.pem"],
+        ["--insecure", "example.com"],
+        ["--pin", &pin],
+        ["--identity", "example.com=client.pem,client.key"],
+    ] {
+        let err = Cli::try_parse_from(["prax"].into_iter().chain(flag)).err();
+        assert_eq!(
+            err.map(|e| e.kind()),
+            Some(clap::error::ErrorKind::MissingRequiredArgument),
+            "{flag:?}"
+        );
+
+        let tls = ["prax", "--key", "key.pem", "--cert", "cert.pem"];
+        assert!(Cli::try_parse_from(tls.into_iter().chain(flag)).is_ok());
+    }
+}
@@ -44,6 +44,6 @@ pub enum Error {
     #[error("Failed to marshal header")]
     HeaderMarshal(#[from] hyper::header::ToStrError),
 
-    #[error("No tls configuration when upgrading")]
-    NoTlsConfig,
+    #[error("Exchange dropped by a filter")]
+    Dropped,
 }
@@ -20,7 +20,7 @@ impl AsRef<[u8]> for Body {
 }
 
 impl Body {
-    pub fn lines(&self) -> Option<Split<char>> {
+    pub fn lines(&self) -> Option<Split<'_, char>> {
         let s = std::str::from_utf8(&self.0);
 
         let s = s.ok()?;
@@ -54,6 +54,18 @@ impl Body {
         }
     }
 
+    /// colon separated sha256 digest, as certificate fingerprints are shown
+    pub fn fingerprint(&self) -> String {
+        let digest = ring::digest::digest(&ring::digest::SHA256, &self.0);
+
+        digest
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
     #[allow(dead_code)]
     pub fn encode(&self, encoding: Encoding) -> std::io::Result<Body> {
         Ok(Body(encoding.encode(&self.0)?))
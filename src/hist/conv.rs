@@ -1,11 +1,16 @@
 use std::collections::HashMap;
 
-use super::{Request, Response};
+use crate::{Faults, Live, PeerCertificates, Truncated, Tunneled};
+
+use super::{Body, Request, Response};
 
 impl From<&hyper::Request<Vec<u8>>> for Request {
     fn from(value: &hyper::Request<Vec<u8>>) -> Self {
         let method = value.method().to_string();
-        let path = value.uri().path().to_string();
+        let path = match value.uri().authority() {
+            Some(authority) if value.method() == hyper::Method::CONNECT => authority.to_string(),
+            _ => value.uri().path().to_string(),
+        };
         let version = format!("{:?}", value.version());
 
         let mut headers = HashMap::new();
@@ -28,6 +33,14 @@ impl From<&hyper::Request<Vec<u8>>> for Request {
         }
 
         let body = value.body().clone().into();
+        let extensions = value.extensions();
+        let truncated =
+            extensions.get::<Truncated>().is_some() || extensions.get::<Live>().is_some();
+        let certificates = extensions
+            .get::<PeerCertificates>()
+            .map(|PeerCertificates(chain)| chain.iter().cloned().map(Body::from).collect())
+            .unwrap_or_default();
+        let faults = faults(extensions);
 
         Request {
             method,
@@ -36,6 +49,9 @@ impl From<&hyper::Request<Vec<u8>>> for Request {
             version,
             headers,
             body,
+            truncated,
+            certificates,
+            faults,
         }
     }
 }
@@ -52,11 +68,26 @@ impl From<&hyper::Response<Vec<u8>>> for Response {
         }
 
         let body = value.body().clone().into();
+        let truncated = value.extensions().get::<Truncated>().is_some();
+        let live = value.extensions().get::<Live>().is_some();
+        let tunnel = value.extensions().get::<Tunneled>().cloned();
+        let faults = faults(value.extensions());
 
         Response {
             status,
             headers,
             body,
+            truncated,
+            live: live.into(),
+            tunnel,
+            faults,
         }
     }
 }
+
+fn faults(extensions: &hyper::http::Extensions) -> Vec<crate::Fault> {
+    extensions
+        .get::<Faults>()
+        .map(|Faults(faults)| faults.clone())
+        .unwrap_or_default()
+}
@@ -0,0 +1,229 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+};
+
+use serde_json::Value;
+
+use super::{decoded, Body, Ent};
+
+/// How a value differs from the left to the right
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change<T> {
+    Added(T),
+    Removed(T),
+    Changed(T, T),
+}
+
+/// What differs between the same side of two exchanges
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Changes {
+    pub headers: BTreeMap<String, Change<String>>,
+
+    /// only requests have a query
+    pub query: BTreeMap<String, Change<String>>,
+
+    /// fields by json pointer ("/user/id"), only when both bodies are json
+    pub fields: BTreeMap<String, Change<Value>>,
+
+    /// the bodies differ at all, after decoding
+    pub body: bool,
+}
+
+/// Structured differences between two history entries, from left to right
+///
+/// Headers and query parameters are compared by name and json bodies by field,
+/// for a text diff compare their [`ToLines`](crate::lines::ToLines) renderings.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diff {
+    pub method: Option<Change<String>>,
+    pub path: Option<Change<String>>,
+    pub status: Option<Change<u16>>,
+
+    pub request: Changes,
+    pub response: Changes,
+}
+
+impl Diff {
+    pub fn new(left: &Ent, right: &Ent) -> Diff {
+        let (l, r) = (left.request, right.request);
+
+        let request = Changes {
+            query: entries(&l.query, &r.query),
+            ..Changes::new((&l.headers, &l.body), (&r.headers, &r.body))
+        };
+
+        // a missing response compares like an empty one
+        let (none, nobody) = (HashMap::new(), Body::from(Vec::new()));
+        let response = Changes::new(
+            left.response
+                .map_or((&none, &nobody), |res| (&res.headers, &res.body)),
+            right
+                .response
+                .map_or((&none, &nobody), |res| (&res.headers, &res.body)),
+        );
+
+        Diff {
+            method: compare(Some(&l.method), Some(&r.method)),
+            path: compare(Some(&l.path), Some(&r.path)),
+            status: compare(
+                left.response.map(|res| &res.status),
+                right.response.map(|res| &res.status),
+            ),
+            request,
+            response,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Diff::default()
+    }
+
+    /// one line per difference, like "request header accept: + */*"
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        if let Some(change) = &self.method {
+            lines.push(format!("method: {change}"));
+        }
+
+        if let Some(change) = &self.path {
+            lines.push(format!("path: {change}"));
+        }
+
+        if let Some(change) = &self.status {
+            lines.push(format!("status: {change}"));
+        }
+
+        self.request.lines("request", &mut lines);
+        self.response.lines("response", &mut lines);
+
+        lines
+    }
+}
+
+impl Changes {
+    fn new(
+        left: (&HashMap<String, String>, &Body),
+        right: (&HashMap<String, String>, &Body),
+    ) -> Self {
+        let l = decoded(left.0, left.1);
+        let r = decoded(right.0, right.1);
+
+        let mut fields = BTreeMap::new();
+
+        let json = (
+            serde_json::from_slice::<Value>(l.as_ref()),
+            serde_json::from_slice::<Value>(r.as_ref()),
+        );
+
+        if let (Ok(l), Ok(r)) = json {
+            compare_json(&l, &r, String::new(), &mut fields);
+        }
+
+        Changes {
+            headers: entries(left.0, right.0),
+            query: BTreeMap::new(),
+            fields,
+            body: l != r,
+        }
+    }
+
+    fn lines(&self, side: &str, lines: &mut Vec<String>) {
+        for (name, change) in &self.headers {
+            lines.push(format!("{side} header {name}: {change}"));
+        }
+
+        for (name, change) in &self.query {
+            lines.push(format!("{side} query {name}: {change}"));
+        }
+
+        for (pointer, change) in &self.fields {
+            lines.push(format!("{side} json {pointer}: {change}"));
+        }
+
+        if self.body && self.fields.is_empty() {
+            lines.push(format!("{side} body differs"));
+        }
+    }
+}
+
+/// "+ added", "- removed" or "before → after"
+impl<T: Display> Display for Change<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added(value) => write!(f, "+ {value}"),
+            Change::Removed(value) => write!(f, "- {value}"),
+            Change::Changed(before, after) => write!(f, "{before} → {after}"),
+        }
+    }
+}
+
+fn compare<T: PartialEq + ToOwned + ?Sized>(
+    left: Option<&T>,
+    right: Option<&T>,
+) -> Option<Change<T::Owned>> {
+    match (left, right) {
+        (Some(l), Some(r)) if l != r => Some(Change::Changed(l.to_owned(), r.to_owned())),
+        (Some(l), None) => Some(Change::Removed(l.to_owned())),
+        (None, Some(r)) => Some(Change::Added(r.to_owned())),
+        _ => None,
+    }
+}
+
+fn entries(
+    left: &HashMap<String, String>,
+    right: &HashMap<String, String>,
+) -> BTreeMap<String, Change<String>> {
+    let names: BTreeSet<_> = left.keys().chain(right.keys()).collect();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let change = compare(left.get(name), right.get(name))?;
+            Some((name.clone(), change))
+        })
+        .collect()
+}
+
+fn compare_json(
+    left: &Value,
+    right: &Value,
+    pointer: String,
+    fields: &mut BTreeMap<String, Change<Value>>,
+) {
+    let mut nested = |key: &str, l: Option<&Value>, r: Option<&Value>| {
+        let pointer = format!("{pointer}/{}", key.replace('~', "~0").replace('/', "~1"));
+
+        match (l, r) {
+            (Some(l), Some(r)) => compare_json(l, r, pointer, fields),
+            (l, r) => {
+                if let Some(change) = compare(l, r) {
+                    fields.insert(pointer, change);
+                }
+            }
+        }
+    };
+
+    match (left, right) {
+        (Value::Object(l), Value::Object(r)) => {
+            let keys: BTreeSet<_> = l.keys().chain(r.keys()).collect();
+
+            for key in keys {
+                nested(key, l.get(key), r.get(key));
+            }
+        }
+
+        (Value::Array(l), Value::Array(r)) => {
+            for i in 0..l.len().max(r.len()) {
+                nested(&i.to_string(), l.get(i), r.get(i));
+            }
+        }
+
+        (l, r) => {
+            if let Some(change) = compare(Some(l), Some(r)) {
+                fields.insert(pointer, change);
+            }
+        }
+    }
+}
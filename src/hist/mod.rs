@@ -1,19 +1,31 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
 mod body;
 mod conv;
 mod deser;
+mod diff;
 mod encoding;
+mod flag;
+mod query;
+mod sitemap;
 
 #[cfg(test)]
 mod test;
 
 pub use body::Body;
+pub use diff::{Change, Changes, Diff};
 pub use encoding::Encoding;
+pub use flag::Flag;
+pub use query::{Iter, Query, QueryError};
+pub use sitemap::{Node, Row, SiteMap, Summary};
 use tokio::sync::broadcast;
 
-use crate::bind::{Req, Res, Scribe};
+use crate::bind::{Fault, Req, Res, Scribe, Tunneled};
 
 use crate::store::{Append, Random, Store};
 
@@ -25,6 +37,18 @@ pub struct Request {
     pub version: String,
     pub headers: HashMap<String, String>,
     pub body: Body,
+
+    /// only a prefix of the body was recorded
+    #[serde(default)]
+    pub truncated: bool,
+
+    /// der encoded certificate chain presented by the upstream server
+    #[serde(default)]
+    pub certificates: Vec<Body>,
+
+    /// what rules did to simulate a faulty backend
+    #[serde(default)]
+    pub faults: Vec<Fault>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
@@ -32,6 +56,34 @@ pub struct Response {
     pub status: u16,
     pub headers: HashMap<String, String>,
     pub body: Body,
+
+    /// only a prefix of the body was recorded
+    #[serde(default)]
+    pub truncated: bool,
+
+    /// the body is still being recorded as it streams, see [`Hist::streamed`],
+    /// cleared once it ends
+    #[serde(default)]
+    pub live: Flag,
+
+    /// the connection was tunneled through without interception
+    #[serde(default)]
+    pub tunnel: Option<Tunneled>,
+
+    /// what rules did to simulate a faulty backend, a failed response was made up by the proxy
+    #[serde(default)]
+    pub faults: Vec<Fault>,
+}
+
+impl Request {
+    /// the host the request was sent to, with its port if given
+    pub fn host(&self) -> Option<&str> {
+        if self.method == "CONNECT" {
+            return Some(&self.path);
+        }
+
+        self.headers.get("host").map(String::as_str)
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -48,24 +100,64 @@ pub struct Entry {
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum HistoryEvent {
-    Request { index: usize },
-    Response { index: usize },
+    Request {
+        index: usize,
+    },
+    Response {
+        index: usize,
+    },
+
+    /// `len` more bytes were appended to a live response body at `offset`,
+    /// sent at most every [`CHUNK_INTERVAL`] per response
+    ResponseChunk {
+        index: usize,
+        offset: usize,
+        len: usize,
+    },
+
+    /// a live response body ended
+    ResponseEnd {
+        index: usize,
+    },
 }
 
+/// most bytes of a live body recorded, the rest is only passed through
+pub const STREAM_LEN: usize = 0x1000000;
+
+/// least time between two [`HistoryEvent::ResponseChunk`] of a response
+pub const CHUNK_INTERVAL: Duration = Duration::from_millis(100);
+
 #[derive(Debug)]
 pub struct Hist {
     requests: Store<Request, Append>,
     responses: Store<Response, Random>,
+    streams: Store<Arc<Mutex<Stream>>, Random>,
+    times: Store<SystemTime, Random>,
 
     events: broadcast::Sender<HistoryEvent>,
 }
 
+/// a live body as recorded so far
+#[derive(Debug, Default)]
+struct Stream {
+    buf: Vec<u8>,
+
+    /// bytes announced in events so far
+    announced: usize,
+    last: Option<Instant>,
+
+    /// an event is scheduled for the bytes not announced yet
+    pending: bool,
+    ended: bool,
+}
+
 impl Scribe for Hist {
     type Ticket = usize;
 
     async fn report_request(&self, req: &Req<Vec<u8>>) -> usize {
         let req = Request::from(req);
         let index = self.requests.push(req);
+        self.times.insert(index, SystemTime::now());
 
         let _ = self.events.send(HistoryEvent::Request { index });
 
@@ -75,14 +167,118 @@ impl Scribe for Hist {
     async fn report_response(&self, index: Self::Ticket, res: &Res<Vec<u8>>) {
         let res = Response::from(res);
 
+        if res.live.get() {
+            let buf = res.body.as_ref().to_vec();
+            let stream = Stream {
+                announced: buf.len(),
+                buf,
+                ..Stream::default()
+            };
+
+            self.streams.insert(index, Arc::new(Mutex::new(stream)));
+        }
+
         if self.responses.insert(index, res) {
             let _ = self.events.send(HistoryEvent::Response { index });
         }
     }
+
+    async fn report_chunk(&self, index: Self::Ticket, chunk: &[u8]) {
+        let Some(stream) = self.streams.get(index) else {
+            return;
+        };
+
+        let Ok(mut locked) = stream.lock() else {
+            return;
+        };
+
+        let room = STREAM_LEN.saturating_sub(locked.buf.len());
+        if room < chunk.len() {
+            tracing::debug!(
+                "live body of #{index} past {STREAM_LEN} bytes, not recording the rest"
+            );
+        }
+
+        locked
+            .buf
+            .extend_from_slice(&chunk[..room.min(chunk.len())]);
+
+        if locked.pending || locked.ended {
+            return;
+        }
+
+        let wait = locked.last.map_or(Duration::ZERO, |last| {
+            CHUNK_INTERVAL.saturating_sub(last.elapsed())
+        });
+
+        if wait.is_zero() {
+            announce(&mut locked, index, &self.events);
+            return;
+        }
+
+        // announce what arrives in the meantime in one event
+        locked.pending = true;
+        drop(locked);
+
+        let stream = stream.clone();
+        let events = self.events.clone();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(wait).await;
+
+            let Ok(mut locked) = stream.lock() else {
+                return;
+            };
+
+            locked.pending = false;
+
+            if !locked.ended {
+                announce(&mut locked, index, &events);
+            }
+        });
+    }
+
+    async fn report_end(&self, index: Self::Ticket) {
+        let Some(stream) = self.streams.get(index) else {
+            return;
+        };
+
+        let Ok(mut locked) = stream.lock() else {
+            return;
+        };
+
+        if locked.ended {
+            return;
+        }
+
+        announce(&mut locked, index, &self.events);
+        locked.ended = true;
+
+        if let Some(res) = self.responses.get(index) {
+            res.live.clear();
+        }
+
+        let _ = self.events.send(HistoryEvent::ResponseEnd { index });
+    }
+}
+
+/// sends an event for the bytes of `stream` not announced yet, if any
+fn announce(stream: &mut Stream, index: usize, events: &broadcast::Sender<HistoryEvent>) {
+    let offset = stream.announced;
+    let len = stream.buf.len() - offset;
+
+    if len == 0 {
+        return;
+    }
+
+    stream.announced = stream.buf.len();
+    stream.last = Some(Instant::now());
+
+    let _ = events.send(HistoryEvent::ResponseChunk { index, offset, len });
 }
 
 impl Hist {
-    pub fn entry(&self, index: usize) -> Option<Ent> {
+    pub fn entry(&self, index: usize) -> Option<Ent<'_>> {
         let request = self.requests.get(index)?;
 
         let response = self.responses.get(index);
@@ -98,6 +294,27 @@ impl Hist {
         self.responses.get(index)
     }
 
+    /// when the request was recorded
+    pub fn time(&self, index: usize) -> Option<SystemTime> {
+        self.times.get(index).copied()
+    }
+
+    /// the body of a live response including everything streamed so far
+    pub fn streamed(&self, index: usize) -> Option<Body> {
+        let stream = self.streams.get(index)?;
+        let stream = stream.lock().ok()?;
+
+        Some(Body::from(stream.buf.clone()))
+    }
+
+    /// part of a live response body, to follow it without copying all of it
+    pub fn streamed_range(&self, index: usize, range: Range<usize>) -> Option<Vec<u8>> {
+        let stream = self.streams.get(index)?;
+        let stream = stream.lock().ok()?;
+
+        stream.buf.get(range).map(<[u8]>::to_vec)
+    }
+
     pub fn listen(&self) -> broadcast::Receiver<HistoryEvent> {
         self.events.subscribe()
     }
@@ -107,13 +324,27 @@ impl Default for Hist {
     fn default() -> Self {
         let requests = Store::<Request, Append>::default();
         let responses = Store::default();
+        let streams = Store::default();
+        let times = Store::default();
 
         let events = broadcast::Sender::new(16);
 
         Hist {
             requests,
             responses,
+            streams,
+            times,
             events,
         }
     }
 }
+
+/// the body with its content encoding undone, as recorded if that fails
+fn decoded(headers: &HashMap<String, String>, body: &Body) -> Body {
+    let encoding = headers
+        .get("content-encoding")
+        .and_then(|e| Encoding::from_str(e).ok())
+        .unwrap_or(Encoding::Bare);
+
+    body.decode(encoding).unwrap_or_else(|_| body.clone())
+}
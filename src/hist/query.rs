@@ -0,0 +1,326 @@
+use std::{
+    collections::HashMap,
+    ops::RangeInclusive,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+use regex::Regex;
+
+use super::{decoded, Body, Ent, Hist};
+
+/// Which history entries to look at, every condition set has to hold
+///
+/// ```
+/// # use prax::hist::Query;
+/// let query = Query::default()
+///     .method("POST")
+///     .status(400..=499)
+///     .header("authorization");
+///
+/// let parsed: Query = "method=POST status=4xx header=authorization".parse().unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Query {
+    host: Option<String>,
+    method: Option<String>,
+    path: Option<Regex>,
+    status: Option<RangeInclusive<u16>>,
+    headers: Vec<String>,
+    body: Option<Vec<u8>>,
+    mime: Option<String>,
+    since: Option<SystemTime>,
+    until: Option<SystemTime>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum QueryError {
+    #[error("unknown filter \"{0}\"")]
+    Unknown(String),
+
+    #[error("invalid path pattern: {0}")]
+    Path(#[from] regex::Error),
+
+    #[error("invalid status range \"{0}\"")]
+    Status(String),
+
+    #[error("invalid duration \"{0}\"")]
+    Duration(String),
+}
+
+/// Entries of a [`Hist`] in the order they were recorded
+pub struct Iter<'a> {
+    hist: &'a Hist,
+    index: usize,
+}
+
+impl Query {
+    /// the target requested, with or without its port
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    pub fn method(mut self, method: impl Into<String>) -> Self {
+        self.method = Some(method.into());
+        self
+    }
+
+    pub fn path(mut self, path: Regex) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    /// only answered requests with a status in range
+    pub fn status(mut self, status: RangeInclusive<u16>) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// the request or the response has the header, may be given multiple times
+    pub fn header(mut self, name: impl Into<String>) -> Self {
+        self.headers.push(name.into().to_lowercase());
+        self
+    }
+
+    /// the request or the response body has these bytes, after decoding
+    pub fn body(mut self, needle: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(needle.into());
+        self
+    }
+
+    /// part of the content type of the request or the response ("json", "text/html")
+    pub fn mime(mut self, mime: impl Into<String>) -> Self {
+        self.mime = Some(mime.into().to_lowercase());
+        self
+    }
+
+    /// recorded at or after `time`
+    pub fn since(mut self, time: SystemTime) -> Self {
+        self.since = Some(time);
+        self
+    }
+
+    /// recorded at or before `time`
+    pub fn until(mut self, time: SystemTime) -> Self {
+        self.until = Some(time);
+        self
+    }
+
+    /// whether `ent` recorded at `time` is wanted
+    pub fn matches(&self, ent: &Ent, time: Option<SystemTime>) -> bool {
+        let Ent { request, response } = ent;
+
+        if let Some(host) = &self.host {
+            let Some(target) = request.host() else {
+                return false;
+            };
+
+            let bare = target.split_once(':').map_or(target, |(host, _)| host);
+
+            if !target.eq_ignore_ascii_case(host) && !bare.eq_ignore_ascii_case(host) {
+                return false;
+            }
+        }
+
+        if let Some(method) = &self.method {
+            if !request.method.eq_ignore_ascii_case(method) {
+                return false;
+            }
+        }
+
+        if let Some(path) = &self.path {
+            if !path.is_match(&request.path) {
+                return false;
+            }
+        }
+
+        if let Some(status) = &self.status {
+            if !response.is_some_and(|res| status.contains(&res.status)) {
+                return false;
+            }
+        }
+
+        for name in &self.headers {
+            let has = request.headers.contains_key(name)
+                || response.is_some_and(|res| res.headers.contains_key(name));
+
+            if !has {
+                return false;
+            }
+        }
+
+        if let Some(needle) = &self.body {
+            let found = contains(&request.headers, &request.body, needle)
+                || response.is_some_and(|res| contains(&res.headers, &res.body, needle));
+
+            if !found {
+                return false;
+            }
+        }
+
+        if let Some(mime) = &self.mime {
+            let is = |headers: &HashMap<String, String>| {
+                headers
+                    .get("content-type")
+                    .is_some_and(|ty| ty.to_lowercase().contains(mime.as_str()))
+            };
+
+            if !is(&request.headers) && !response.is_some_and(|res| is(&res.headers)) {
+                return false;
+            }
+        }
+
+        if self.since.is_some() || self.until.is_some() {
+            let Some(time) = time else {
+                return false;
+            };
+
+            if self.since.is_some_and(|since| time < since) {
+                return false;
+            }
+
+            if self.until.is_some_and(|until| time > until) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// parses `:PraxFilter` style queries: `key=value` terms separated by spaces
+///
+/// - `host=example.com`, `method=POST`, `path=^/api` (a regex, also taken bare)
+/// - `status=404`, `status=4xx` or `status=200-299`
+/// - `header=authorization`, `body=token`, `mime=json`
+/// - `since=10m` and `until=1h` relative to now, in `s`, `m`, `h` or `d`
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut query = Query::default();
+
+        for term in s.split_whitespace() {
+            let Some((key, value)) = term.split_once('=') else {
+                query = query.path(Regex::new(term)?);
+                continue;
+            };
+
+            query = match key {
+                "host" => query.host(value),
+                "method" => query.method(value),
+                "path" => query.path(Regex::new(value)?),
+                "status" => query.status(status_range(value)?),
+                "header" => query.header(value),
+                "body" => query.body(value),
+                "mime" => query.mime(value),
+                "since" => query.since(ago(value)?),
+                "until" => query.until(ago(value)?),
+
+                _ => return Err(QueryError::Unknown(term.to_string())),
+            };
+        }
+
+        Ok(query)
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (usize, Ent<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.index;
+        let ent = self.hist.entry(index)?;
+        self.index += 1;
+
+        Some((index, ent))
+    }
+}
+
+impl Hist {
+    /// requests recorded so far
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            hist: self,
+            index: 0,
+        }
+    }
+
+    /// entries matching `query`, alongside their index
+    pub fn search<'a>(&'a self, query: &'a Query) -> impl Iterator<Item = (usize, Ent<'a>)> {
+        self.iter()
+            .filter(|(index, ent)| query.matches(ent, self.time(*index)))
+    }
+}
+
+impl<'a> IntoIterator for &'a Hist {
+    type Item = (usize, Ent<'a>);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+fn contains(headers: &HashMap<String, String>, body: &Body, needle: &[u8]) -> bool {
+    let body = decoded(headers, body);
+    let bytes = body.as_ref();
+
+    needle.is_empty() || bytes.windows(needle.len()).any(|w| w == needle)
+}
+
+fn status_range(s: &str) -> Result<RangeInclusive<u16>, QueryError> {
+    let invalid = || QueryError::Status(s.to_string());
+
+    if let Some((low, high)) = s.split_once('-') {
+        let low: u16 = low.parse().map_err(|_| invalid())?;
+        let high: u16 = high.parse().map_err(|_| invalid())?;
+
+        if low > high {
+            return Err(invalid());
+        }
+
+        return Ok(low..=high);
+    }
+
+    if let Some(class) = s.strip_suffix("xx") {
+        let class: u16 = class.parse().map_err(|_| invalid())?;
+        let low = class.checked_mul(100).ok_or_else(invalid)?;
+        let high = low.checked_add(99).ok_or_else(invalid)?;
+
+        return Ok(low..=high);
+    }
+
+    let status = s.parse().map_err(|_| invalid())?;
+    Ok(status..=status)
+}
+
+/// the time `s` ("30s", "10m", "2h", "1d") ago
+fn ago(s: &str) -> Result<SystemTime, QueryError> {
+    let invalid = || QueryError::Duration(s.to_string());
+
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (count, unit) = s.split_at(split);
+    let count: u64 = count.parse().map_err(|_| invalid())?;
+
+    let secs = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+
+    let duration = Duration::from_secs(count.checked_mul(secs).ok_or_else(invalid)?);
+
+    SystemTime::now().checked_sub(duration).ok_or_else(invalid)
+}
@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+
+use super::{Hist, Request};
+
+/// Recorded traffic as a tree of hosts, their path segments and the requests made
+///
+/// Branches start collapsed, [`SiteMap::toggle`] expands and collapses them.
+#[derive(Debug, Default)]
+pub struct SiteMap {
+    hosts: BTreeMap<String, Branch>,
+}
+
+/// How the requests below a branch were answered
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub requests: usize,
+    pub pending: usize,
+
+    /// answers by status class, 1xx to 5xx
+    pub classes: [usize; 5],
+}
+
+/// What a row of the tree stands for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    /// the host followed by path segments
+    Branch(Vec<String>),
+
+    /// a request by its history index
+    Entry(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Row {
+    pub text: String,
+    pub node: Node,
+}
+
+#[derive(Debug, Default)]
+struct Branch {
+    open: bool,
+    summary: Summary,
+    children: BTreeMap<String, Branch>,
+    leaves: Vec<Leaf>,
+}
+
+#[derive(Debug)]
+struct Leaf {
+    entry: usize,
+    method: String,
+    path: String,
+    status: Option<u16>,
+}
+
+impl SiteMap {
+    /// files the request recorded as `entry` under its host and path, once
+    pub fn insert(&mut self, entry: usize, request: &Request) {
+        let (host, segments) = key(request);
+
+        let filed = self
+            .branch(&host, &segments)
+            .is_some_and(|branch| branch.leaves.iter().any(|leaf| leaf.entry == entry));
+
+        if filed {
+            return;
+        }
+
+        let mut branch = self.hosts.entry(host).or_default();
+        branch.summary.requests += 1;
+        branch.summary.pending += 1;
+
+        for segment in segments {
+            branch = branch.children.entry(segment).or_default();
+            branch.summary.requests += 1;
+            branch.summary.pending += 1;
+        }
+
+        branch.leaves.push(Leaf {
+            entry,
+            method: request.method.clone(),
+            path: request.path.clone(),
+            status: None,
+        });
+    }
+
+    /// counts the response to `entry`, once and only if it was inserted
+    pub fn answer(&mut self, entry: usize, request: &Request, status: u16) {
+        let (host, segments) = key(request);
+
+        let pending = self
+            .branch(&host, &segments)
+            .and_then(|branch| branch.leaves.iter().find(|leaf| leaf.entry == entry))
+            .is_some_and(|leaf| leaf.status.is_none());
+
+        let Some(mut branch) = self.hosts.get_mut(&host).filter(|_| pending) else {
+            return;
+        };
+
+        branch.summary.answer(status);
+
+        for segment in &segments {
+            let Some(child) = branch.children.get_mut(segment) else {
+                return;
+            };
+
+            branch = child;
+            branch.summary.answer(status);
+        }
+
+        if let Some(leaf) = branch.leaves.iter_mut().find(|leaf| leaf.entry == entry) {
+            leaf.status = Some(status);
+        }
+    }
+
+    /// expands or collapses the branch at `path`, false if there is none
+    pub fn toggle(&mut self, path: &[String]) -> bool {
+        let Some((host, segments)) = path.split_first() else {
+            return false;
+        };
+
+        let mut branch = self.hosts.get_mut(host);
+
+        for segment in segments {
+            branch = branch.and_then(|branch| branch.children.get_mut(segment));
+        }
+
+        match branch {
+            Some(branch) => {
+                branch.open = !branch.open;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn summary(&self, path: &[String]) -> Option<Summary> {
+        let (host, segments) = path.split_first()?;
+
+        self.branch(host, segments).map(|branch| branch.summary)
+    }
+
+    /// the tree as shown, children of collapsed branches left out
+    pub fn rows(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+
+        for (host, branch) in &self.hosts {
+            branch.rows(0, host, vec![host.clone()], &mut rows);
+        }
+
+        rows
+    }
+
+    fn branch(&self, host: &str, segments: &[String]) -> Option<&Branch> {
+        let mut branch = self.hosts.get(host)?;
+
+        for segment in segments {
+            branch = branch.children.get(segment)?;
+        }
+
+        Some(branch)
+    }
+}
+
+impl From<&Hist> for SiteMap {
+    fn from(hist: &Hist) -> Self {
+        let mut map = SiteMap::default();
+
+        for (entry, ent) in hist {
+            map.insert(entry, ent.request);
+
+            if let Some(response) = ent.response {
+                map.answer(entry, ent.request, response.status);
+            }
+        }
+
+        map
+    }
+}
+
+impl Branch {
+    fn rows(&self, depth: usize, name: &str, path: Vec<String>, rows: &mut Vec<Row>) {
+        let indent = "  ".repeat(depth);
+        let marker = if self.open { "▾" } else { "▸" };
+
+        rows.push(Row {
+            text: format!("{indent}{marker} {name} {}", self.summary),
+            node: Node::Branch(path.clone()),
+        });
+
+        if !self.open {
+            return;
+        }
+
+        for leaf in &self.leaves {
+            let status = leaf
+                .status
+                .map_or("…".to_string(), |status| status.to_string());
+
+            rows.push(Row {
+                text: format!(
+                    "{indent}  #{} {} {} {status}",
+                    leaf.entry, leaf.method, leaf.path
+                ),
+                node: Node::Entry(leaf.entry),
+            });
+        }
+
+        for (segment, child) in &self.children {
+            let mut path = path.clone();
+            path.push(segment.clone());
+
+            child.rows(depth + 1, &format!("/{segment}"), path, rows);
+        }
+    }
+}
+
+impl Summary {
+    fn answer(&mut self, status: u16) {
+        self.pending = self.pending.saturating_sub(1);
+
+        if let Some(count) = self
+            .classes
+            .get_mut((status / 100).wrapping_sub(1) as usize)
+        {
+            *count += 1;
+        }
+    }
+}
+
+/// "(4) 2xx:3 4xx:1 pending:1"
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({})", self.requests)?;
+
+        for (class, count) in self.classes.iter().enumerate() {
+            if *count > 0 {
+                write!(f, " {}xx:{count}", class + 1)?;
+            }
+        }
+
+        if self.pending > 0 {
+            write!(f, " pending:{}", self.pending)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn key(request: &Request) -> (String, Vec<String>) {
+    let host = request.host().unwrap_or("(unknown)").to_string();
+
+    if request.method == "CONNECT" {
+        return (host, Vec::new());
+    }
+
+    let segments = request
+        .path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(ToString::to_string)
+        .collect();
+
+    (host, segments)
+}
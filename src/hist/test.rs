@@ -1,8 +1,12 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 use crate::{
-    hist::{Body, Ent, HistoryEvent},
-    Scribe,
+    hist::{Body, Change, Diff, Ent, HistoryEvent, Node, Query, QueryError, SiteMap, Summary},
+    Fault, Faults, Live, PeerCertificates, Scribe, Truncated, Tunneled,
 };
 
 use super::Hist;
@@ -24,12 +28,19 @@ async fn test_record() {
         version: "HTTP/1.1".to_string(),
         headers: HashMap::default(),
         body: Body::from(b"ping".to_vec()),
+        truncated: false,
+        certificates: vec![],
+        faults: vec![],
     };
 
     let hres = super::Response {
         status: 200,
         headers: HashMap::default(),
         body: Body::from(b"pong".to_vec()),
+        truncated: false,
+        live: false.into(),
+        tunnel: None,
+        faults: vec![],
     };
 
     let id = hist.report_request(&req).await;
@@ -125,3 +136,581 @@ async fn test_arbitrary_access() {
     assert!(hist.request(1).is_none());
     assert!(hist.response(1).is_none());
 }
+
+#[tokio::test]
+async fn test_truncated() {
+    let hist = Hist::default();
+
+    let req = hyper::Request::new(b"ping".to_vec());
+    let mut res = hyper::Response::new(b"po".to_vec());
+    res.extensions_mut().insert(Truncated);
+
+    let id = hist.report_request(&req).await;
+    hist.report_response(id, &res).await;
+
+    assert!(!hist.request(0).unwrap().truncated);
+    assert!(hist.response(0).unwrap().truncated);
+}
+
+#[tokio::test]
+async fn test_live() {
+    let hist = Hist::default();
+
+    let req = hyper::Request::new(b"ping".to_vec());
+    let mut res = hyper::Response::new(b"data: 1\n".to_vec());
+    res.extensions_mut().insert(Live);
+
+    let mut listener = hist.listen();
+
+    let id = hist.report_request(&req).await;
+    hist.report_response(id, &res).await;
+    hist.report_chunk(id, b"data: 2\n").await;
+
+    assert_eq!(listener.try_recv(), Ok(HistoryEvent::Request { index: 0 }));
+    assert_eq!(listener.try_recv(), Ok(HistoryEvent::Response { index: 0 }));
+    assert_eq!(
+        listener.try_recv(),
+        Ok(HistoryEvent::ResponseChunk {
+            index: 0,
+            offset: 8,
+            len: 8
+        })
+    );
+
+    assert!(hist.response(0).unwrap().live.get());
+    assert_eq!(
+        hist.streamed(0),
+        Some(Body::from(b"data: 1\ndata: 2\n".to_vec()))
+    );
+    assert_eq!(hist.streamed_range(0, 8..15), Some(b"data: 2".to_vec()));
+
+    hist.report_end(id).await;
+    hist.report_end(id).await;
+
+    assert_eq!(
+        listener.try_recv(),
+        Ok(HistoryEvent::ResponseEnd { index: 0 })
+    );
+    assert!(listener.try_recv().is_err());
+    assert!(!hist.response(0).unwrap().live.get());
+}
+
+#[tokio::test]
+async fn test_live_throttled() {
+    let hist = Hist::default();
+
+    let req = hyper::Request::new(Vec::new());
+    let mut res = hyper::Response::new(Vec::new());
+    res.extensions_mut().insert(Live);
+
+    let id = hist.report_request(&req).await;
+    hist.report_response(id, &res).await;
+
+    let mut listener = hist.listen();
+
+    for _ in 0..10 {
+        hist.report_chunk(id, b"data\n").await;
+    }
+
+    assert_eq!(
+        listener.try_recv(),
+        Ok(HistoryEvent::ResponseChunk {
+            index: 0,
+            offset: 0,
+            len: 5
+        })
+    );
+    assert!(listener.try_recv().is_err());
+
+    tokio::time::sleep(super::CHUNK_INTERVAL * 2).await;
+
+    assert_eq!(
+        listener.try_recv(),
+        Ok(HistoryEvent::ResponseChunk {
+            index: 0,
+            offset: 5,
+            len: 45
+        })
+    );
+
+    hist.report_chunk(id, &vec![0; super::STREAM_LEN]).await;
+    hist.report_end(id).await;
+
+    assert_eq!(
+        hist.streamed(0).map(|body| body.as_ref().len()),
+        Some(super::STREAM_LEN)
+    );
+}
+
+#[tokio::test]
+async fn test_certificates() {
+    let hist = Hist::default();
+
+    let mut req = hyper::Request::new(b"ping".to_vec());
+    req.extensions_mut()
+        .insert(PeerCertificates(Arc::new(vec![b"cert".to_vec()])));
+
+    hist.report_request(&req).await;
+
+    let request = hist.request(0).unwrap();
+    assert_eq!(request.certificates, vec![Body::from(b"cert".to_vec())]);
+    assert_eq!(
+        request.certificates[0].fingerprint(),
+        "06:29:84:32:E8:06:6B:29:E2:22:3B:CC:23:AA:95:04:B5:6A:E5:08:FA:BF:34:35:50:88:69:B9:C3:19:0E:22"
+    );
+}
+
+#[tokio::test]
+async fn test_tunneled() {
+    let hist = Hist::default();
+
+    let req = hyper::Request::builder()
+        .method(hyper::Method::CONNECT)
+        .uri("example.com:443")
+        .body(Vec::new())
+        .unwrap();
+
+    let tunnel = Tunneled {
+        sent: 517,
+        received: 4096,
+        duration: Duration::from_millis(250),
+        error: None,
+    };
+
+    let mut res = hyper::Response::new(Vec::new());
+    res.extensions_mut().insert(tunnel.clone());
+
+    let id = hist.report_request(&req).await;
+    hist.report_response(id, &res).await;
+
+    assert_eq!(hist.request(0).unwrap().method, "CONNECT");
+    assert_eq!(hist.request(0).unwrap().path, "example.com:443");
+    assert_eq!(hist.response(0).unwrap().tunnel, Some(tunnel));
+}
+
+#[tokio::test]
+async fn test_faults() {
+    let hist = Hist::default();
+
+    let mut req = hyper::Request::new(Vec::new());
+    Faults::record(req.extensions_mut(), Fault::Throttled(1024));
+
+    let mut res = hyper::Response::new(Vec::new());
+    Faults::record(
+        res.extensions_mut(),
+        Fault::Delayed(Duration::from_millis(50)),
+    );
+    Faults::record(res.extensions_mut(), Fault::Dropped);
+
+    let id = hist.report_request(&req).await;
+    hist.report_response(id, &res).await;
+
+    assert_eq!(
+        hist.request(0).unwrap().faults,
+        vec![Fault::Throttled(1024)]
+    );
+    assert_eq!(
+        hist.response(0).unwrap().faults,
+        vec![Fault::Delayed(Duration::from_millis(50)), Fault::Dropped]
+    );
+}
+
+/// records a few exchanges to query: (method, uri, content type, status)
+async fn recorded() -> Hist {
+    let hist = Hist::default();
+
+    let exchanges = [
+        ("GET", "http://example.com/", "text/html", 200),
+        (
+            "POST",
+            "http://example.com:8080/api/login",
+            "application/json",
+            401,
+        ),
+        (
+            "GET",
+            "http://other.net/api/users?id=1",
+            "application/json",
+            200,
+        ),
+        ("DELETE", "http://other.net/api/users", "text/plain", 500),
+    ];
+
+    for (method, uri, mime, status) in exchanges {
+        let uri: hyper::Uri = uri.parse().unwrap();
+        let host = uri.authority().unwrap().to_string();
+
+        let mut req = hyper::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("host", host)
+            .body(format!("{method} body").into_bytes())
+            .unwrap();
+
+        if method == "POST" {
+            req.headers_mut()
+                .insert("authorization", "Bearer token".parse().unwrap());
+        }
+
+        let res = hyper::Response::builder()
+            .status(status)
+            .header("content-type", mime)
+            .body(b"{\"ok\": true}".to_vec())
+            .unwrap();
+
+        let id = hist.report_request(&req).await;
+        hist.report_response(id, &res).await;
+    }
+
+    hist
+}
+
+fn found(hist: &Hist, query: &Query) -> Vec<usize> {
+    hist.search(query).map(|(index, _)| index).collect()
+}
+
+#[tokio::test]
+async fn test_iter() {
+    let hist = Hist::default();
+    assert!(hist.is_empty());
+    assert_eq!(hist.iter().count(), 0);
+
+    let hist = recorded().await;
+
+    assert_eq!(hist.len(), 4);
+
+    let methods: Vec<_> = hist
+        .iter()
+        .map(|(index, ent)| (index, ent.request.method.as_str()))
+        .collect();
+
+    assert_eq!(
+        methods,
+        vec![(0, "GET"), (1, "POST"), (2, "GET"), (3, "DELETE")]
+    );
+
+    assert!(hist.time(0).is_some());
+    assert_eq!(hist.time(4), None);
+}
+
+#[tokio::test]
+async fn test_search() {
+    let hist = recorded().await;
+
+    assert_eq!(found(&hist, &Query::default()), vec![0, 1, 2, 3]);
+    assert_eq!(
+        found(&hist, &Query::default().host("example.com")),
+        vec![0, 1]
+    );
+    assert_eq!(
+        found(&hist, &Query::default().host("example.com:8080")),
+        vec![1]
+    );
+    assert_eq!(found(&hist, &Query::default().method("get")), vec![0, 2]);
+    assert_eq!(
+        found(
+            &hist,
+            &Query::default().path(regex::Regex::new("^/api/users$").unwrap())
+        ),
+        vec![2, 3]
+    );
+    assert_eq!(
+        found(&hist, &Query::default().status(400..=599)),
+        vec![1, 3]
+    );
+    assert_eq!(
+        found(&hist, &Query::default().header("Authorization")),
+        vec![1]
+    );
+    assert_eq!(found(&hist, &Query::default().body("DELETE")), vec![3]);
+    assert_eq!(
+        found(&hist, &Query::default().body("\"ok\"")),
+        vec![0, 1, 2, 3]
+    );
+    assert_eq!(found(&hist, &Query::default().mime("json")), vec![1, 2]);
+
+    assert_eq!(
+        found(
+            &hist,
+            &Query::default()
+                .host("other.net")
+                .method("GET")
+                .mime("json")
+        ),
+        vec![2]
+    );
+}
+
+#[tokio::test]
+async fn test_search_time() {
+    let hist = recorded().await;
+
+    let before = SystemTime::now() - Duration::from_secs(60);
+    let after = SystemTime::now() + Duration::from_secs(60);
+
+    assert_eq!(
+        found(&hist, &Query::default().since(before)),
+        vec![0, 1, 2, 3]
+    );
+    assert_eq!(
+        found(&hist, &Query::default().since(after)),
+        Vec::<usize>::new()
+    );
+    assert_eq!(
+        found(&hist, &Query::default().until(before)),
+        Vec::<usize>::new()
+    );
+    assert_eq!(
+        found(&hist, &Query::default().since(before).until(after)),
+        vec![0, 1, 2, 3]
+    );
+}
+
+#[tokio::test]
+async fn test_search_unanswered() {
+    let hist = Hist::default();
+
+    let req = hyper::Request::new(Vec::new());
+    hist.report_request(&req).await;
+
+    assert_eq!(found(&hist, &Query::default().method("GET")), vec![0]);
+    assert_eq!(
+        found(&hist, &Query::default().status(200..=299)),
+        Vec::<usize>::new()
+    );
+}
+
+#[tokio::test]
+async fn test_query_parse() {
+    let hist = recorded().await;
+
+    let parsed = |s: &str| found(&hist, &s.parse::<Query>().unwrap());
+
+    assert_eq!(parsed(""), vec![0, 1, 2, 3]);
+    assert_eq!(parsed("/api"), vec![1, 2, 3]);
+    assert_eq!(parsed("host=other.net status=5xx"), vec![3]);
+    assert_eq!(parsed("status=401"), vec![1]);
+    assert_eq!(parsed("status=200-299 mime=html"), vec![0]);
+    assert_eq!(parsed("method=POST header=authorization"), vec![1]);
+    assert_eq!(parsed("body=GET since=1h"), vec![0, 2]);
+    assert_eq!(parsed("until=1h"), Vec::<usize>::new());
+
+    assert!("color=red".parse::<Query>().is_err());
+    assert!("path=(".parse::<Query>().is_err());
+    assert!("status=ok".parse::<Query>().is_err());
+    assert!("since=5w".parse::<Query>().is_err());
+
+    assert!(matches!(
+        "status=700xx".parse::<Query>(),
+        Err(QueryError::Status(_))
+    ));
+    assert!(matches!(
+        "status=500-400".parse::<Query>(),
+        Err(QueryError::Status(_))
+    ));
+    assert!(matches!(
+        "since=999999999999999999d".parse::<Query>(),
+        Err(QueryError::Duration(_))
+    ));
+}
+
+fn texts(map: &SiteMap) -> Vec<String> {
+    map.rows().into_iter().map(|row| row.text).collect()
+}
+
+#[tokio::test]
+async fn test_sitemap() {
+    let hist = recorded().await;
+    let mut map = SiteMap::from(&hist);
+
+    assert_eq!(
+        texts(&map),
+        vec![
+            "▸ example.com (1) 2xx:1",
+            "▸ example.com:8080 (1) 4xx:1",
+            "▸ other.net (2) 2xx:1 5xx:1",
+        ]
+    );
+
+    let other = vec!["other.net".to_string()];
+    let api = vec!["other.net".to_string(), "api".to_string()];
+    let users = vec![
+        "other.net".to_string(),
+        "api".to_string(),
+        "users".to_string(),
+    ];
+
+    assert!(map.toggle(&other));
+    assert!(map.toggle(&api));
+    assert!(map.toggle(&users));
+    assert!(!map.toggle(&["nowhere".to_string()]));
+
+    let rows = map.rows();
+    assert_eq!(
+        texts(&map)[2..],
+        [
+            "▾ other.net (2) 2xx:1 5xx:1",
+            "  ▾ /api (2) 2xx:1 5xx:1",
+            "    ▾ /users (2) 2xx:1 5xx:1",
+            "      #2 GET /api/users 200",
+            "      #3 DELETE /api/users 500",
+        ]
+    );
+    assert_eq!(rows[3].node, Node::Branch(api.clone()));
+    assert_eq!(rows[5].node, Node::Entry(2));
+
+    assert!(map.toggle(&api));
+    assert_eq!(texts(&map)[3..], ["  ▸ /api (2) 2xx:1 5xx:1"]);
+}
+
+#[tokio::test]
+async fn test_sitemap_live() {
+    let hist = Hist::default();
+    let mut map = SiteMap::default();
+
+    let req = hyper::Request::builder()
+        .uri("http://example.com/a/b")
+        .header("host", "example.com")
+        .body(Vec::new())
+        .unwrap();
+
+    let id = hist.report_request(&req).await;
+    let request = hist.request(id).unwrap();
+    map.insert(id, request);
+    map.insert(id, request);
+
+    let host = vec!["example.com".to_string()];
+    assert_eq!(
+        map.summary(&host),
+        Some(Summary {
+            requests: 1,
+            pending: 1,
+            classes: [0; 5],
+        })
+    );
+
+    map.answer(id, request, 404);
+    map.answer(id, request, 404);
+    map.answer(7, request, 200);
+
+    let answered = Summary {
+        requests: 1,
+        pending: 0,
+        classes: [0, 0, 0, 1, 0],
+    };
+
+    assert_eq!(map.summary(&host), Some(answered));
+    assert_eq!(
+        map.summary(&["example.com".to_string(), "a".to_string(), "b".to_string()]),
+        Some(answered)
+    );
+    assert_eq!(
+        map.summary(&["example.com".to_string(), "c".to_string()]),
+        None
+    );
+}
+
+async fn exchange(hist: &Hist, uri: &str, token: &str, status: u16, body: &str) -> usize {
+    let req = hyper::Request::builder()
+        .uri(uri)
+        .header("authorization", token)
+        .body(Vec::new())
+        .unwrap();
+
+    let res = hyper::Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(body.as_bytes().to_vec())
+        .unwrap();
+
+    let id = hist.report_request(&req).await;
+    hist.report_response(id, &res).await;
+
+    id
+}
+
+#[tokio::test]
+async fn test_diff() {
+    let hist = Hist::default();
+
+    let admin = exchange(
+        &hist,
+        "/users/1?id=1",
+        "admin",
+        200,
+        r#"{"id": 1, "roles": ["admin", "user"], "name": "root"}"#,
+    )
+    .await;
+    let user = exchange(
+        &hist,
+        "/users/1?id=2",
+        "user",
+        403,
+        r#"{"id": 1, "roles": ["user"], "error": "denied"}"#,
+    )
+    .await;
+
+    let diff = Diff::new(&hist.entry(admin).unwrap(), &hist.entry(user).unwrap());
+
+    assert_eq!(diff.method, None);
+    assert_eq!(diff.path, None);
+    assert_eq!(diff.status, Some(Change::Changed(200, 403)));
+    assert_eq!(
+        diff.request.headers["authorization"],
+        Change::Changed("admin".to_string(), "user".to_string())
+    );
+    assert_eq!(
+        diff.request.query["id"],
+        Change::Changed("1".to_string(), "2".to_string())
+    );
+    assert!(!diff.request.body);
+    assert!(diff.response.headers.is_empty());
+    assert!(diff.response.body);
+
+    assert_eq!(
+        diff.lines(),
+        vec![
+            "status: 200 → 403",
+            "request header authorization: admin → user",
+            "request query id: 1 → 2",
+            "response json /error: + \"denied\"",
+            "response json /name: - \"root\"",
+            "response json /roles/0: \"admin\" → \"user\"",
+            "response json /roles/1: - \"user\"",
+        ]
+    );
+
+    let same = Diff::new(&hist.entry(admin).unwrap(), &hist.entry(admin).unwrap());
+    assert!(same.is_empty());
+    assert!(same.lines().is_empty());
+}
+
+#[tokio::test]
+async fn test_diff_unanswered() {
+    let hist = Hist::default();
+
+    let answered = exchange(&hist, "/", "a", 200, "plain text").await;
+
+    let req = hyper::Request::builder()
+        .method("POST")
+        .uri("/")
+        .header("authorization", "a")
+        .body(Vec::new())
+        .unwrap();
+    let pending = hist.report_request(&req).await;
+
+    let diff = Diff::new(
+        &hist.entry(answered).unwrap(),
+        &hist.entry(pending).unwrap(),
+    );
+
+    assert_eq!(
+        diff.lines(),
+        vec![
+            "method: GET → POST",
+            "status: - 200",
+            "response header content-type: - application/json",
+            "response body differs",
+        ]
+    );
+}
@@ -1,6 +1,7 @@
 pub mod hist;
 pub mod lines;
 pub mod proxy;
+pub mod server;
 pub mod store;
 
 mod bind;
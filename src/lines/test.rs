@@ -96,6 +96,9 @@ mod hist_req {
             version: "HTTP/1.1".to_string(),
             headers: HashMap::new(),
             body: b"hello\nworld".to_vec().into(),
+            truncated: false,
+            certificates: vec![],
+            faults: vec![],
         };
 
         assert_eq!(
@@ -124,6 +127,9 @@ mod hist_req {
             version: "HTTP/1.1".to_string(),
             headers,
             body: b"hello\nworld".to_vec().into(),
+            truncated: false,
+            certificates: vec![],
+            faults: vec![],
         };
 
         assert_eq!(
@@ -140,10 +146,10 @@ mod hist_req {
 }
 
 mod hist_res {
-    use std::collections::HashMap;
+    use std::{collections::HashMap, time::Duration};
 
-    use crate::hist::Response;
     use crate::lines::ToLines;
+    use crate::{hist::Response, Fault};
 
     #[test]
     fn get() {
@@ -151,6 +157,10 @@ mod hist_res {
             status: 200,
             headers: HashMap::new(),
             body: b"hello\nworld".to_vec().into(),
+            truncated: false,
+            live: false.into(),
+            tunnel: None,
+            faults: vec![],
         };
 
         assert_eq!(
@@ -173,6 +183,10 @@ mod hist_res {
             status: 200,
             headers,
             body: b"hello\nworld".to_vec().into(),
+            truncated: false,
+            live: false.into(),
+            tunnel: None,
+            faults: vec![],
         };
 
         assert_eq!(
@@ -186,6 +200,57 @@ mod hist_res {
             ]
         );
     }
+
+    #[test]
+    fn truncated() {
+        let req = Response {
+            status: 200,
+            headers: HashMap::new(),
+            body: b"hello\nwor".to_vec().into(),
+            truncated: true,
+            live: false.into(),
+            tunnel: None,
+            faults: vec![],
+        };
+
+        assert_eq!(
+            req.to_lines().unwrap(),
+            vec![
+                "200".to_string(),
+                "".to_string(),
+                "hello".to_string(),
+                "wor".to_string(),
+                "[truncated]".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn faults() {
+        let res = Response {
+            status: 503,
+            headers: HashMap::new(),
+            body: b"down".to_vec().into(),
+            truncated: false,
+            live: false.into(),
+            tunnel: None,
+            faults: vec![
+                Fault::Delayed(Duration::from_millis(250)),
+                Fault::Failed(503),
+            ],
+        };
+
+        assert_eq!(
+            res.to_lines().unwrap(),
+            vec![
+                "503".to_string(),
+                "".to_string(),
+                "down".to_string(),
+                "[fault] delayed 250ms".to_string(),
+                "[fault] failed with 503".to_string()
+            ]
+        );
+    }
 }
 
 mod hyper_req_imprint {
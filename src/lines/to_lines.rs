@@ -106,6 +106,18 @@ impl ToLines for hist::Request {
             body.hex(&mut res);
         }
 
+        if self.truncated {
+            res.push("[truncated]".to_string());
+        }
+
+        for cert in &self.certificates {
+            res.push(format!("[certificate] {}", cert.fingerprint()));
+        }
+
+        for fault in &self.faults {
+            res.push(format!("[fault] {fault}"));
+        }
+
         Ok(res)
     }
 }
@@ -171,6 +183,27 @@ impl ToLines for crate::hist::Response {
             body.hex(&mut res);
         }
 
+        if self.truncated {
+            res.push("[truncated]".to_string());
+        }
+
+        if let Some(tunnel) = &self.tunnel {
+            res.push(format!(
+                "[tunnel] sent {} bytes, received {} bytes in {}ms",
+                tunnel.sent,
+                tunnel.received,
+                tunnel.duration.as_millis()
+            ));
+
+            if let Some(error) = &tunnel.error {
+                res.push(format!("[tunnel] broke off: {error}"));
+            }
+        }
+
+        for fault in &self.faults {
+            res.push(format!("[fault] {fault}"));
+        }
+
         Ok(res)
     }
 }
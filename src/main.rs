@@ -1,14 +1,13 @@
 use prax::hist::Hist;
-use srv::Tls;
+use prax::server::{Policy, Server, Streaming, Tls};
 use std::{fs::File, sync::Arc};
 use tokio_util::sync::CancellationToken;
 use tracing::Level;
 
 use clap::Parser;
-use prax::proxy::Config;
+use prax::proxy::{Config, Diagnostic};
 
 mod cli;
-mod srv;
 
 mod nvim;
 
@@ -16,6 +15,21 @@ mod nvim;
 async fn main() -> eyre::Result<()> {
     let cli = cli::Cli::parse();
 
+    if let Some(cli::Command::Check { configure }) = cli.command {
+        let diagnostics = Config::check(&configure, ()).await;
+
+        for diagnostic in &diagnostics {
+            eprintln!("{diagnostic}");
+        }
+
+        if !diagnostics.is_empty() {
+            std::process::exit(1);
+        }
+
+        println!("{}: ok", configure.display());
+        return Ok(());
+    }
+
     if let Some(path) = cli.log {
         let subscriber = tracing_subscriber::fmt()
             .json()
@@ -30,8 +44,14 @@ async fn main() -> eyre::Result<()> {
         tracing::subscriber::set_global_default(subscriber)?;
     }
 
-    let tls = Tls::load(cli.tls)?;
+    let tls = cli.tls.options().map(Tls::load).transpose()?;
     let token = CancellationToken::new();
+    let server = Server::builder()
+        .listen(cli.listen)
+        .token(token.clone())
+        .tls(tls)
+        .streaming(Streaming::from(cli.streaming))
+        .policy(Policy::from(cli.timeouts));
 
     if let Some(nvim) = cli.nvim {
         let span = tracing::trace_span!("loading nvim connection", info = ?nvim);
@@ -39,25 +59,27 @@ async fn main() -> eyre::Result<()> {
 
         let history: &'static Hist = Box::leak(Box::default());
         let nvim = nvim::NVim::connect(nvim, token.clone(), history).await?;
+        let reporter = nvim.reporter();
         let intercept = nvim.intercept();
 
         if let Some(path) = cli.configure {
             tracing::debug!(?path, "configuring proxy");
 
-            let config = Config::load(&path, intercept).await?;
+            let config = Config::load(&path, intercept.clone(), cli.lua_states).await?;
+            report(&reporter, "prax: config problems", config.diagnostics()).await;
 
             #[cfg(not(target_os = "linux"))]
-            let reload = None::<tokio::sync::mpsc::Receiver<Config<nvim::Intercept>>>;
+            let reload = None::<tokio::sync::mpsc::Receiver<prax::proxy::Reload<nvim::Intercept>>>;
 
             #[cfg(target_os = "linux")]
             let reload = if cli.watch {
-                Some(config.watch(path.clone()))
+                Some(config.watch(path.clone(), cli.keep_state))
             } else {
                 None
             };
 
-            let server = srv::Server::new(cli.listen, token, config, history, tls);
-            let server = Arc::new(server);
+            let config = intercept.toggled(config);
+            let server = Arc::new(server.filter(config).scribe(history).build());
 
             let s = server.clone();
             if let Some(mut reload) = reload {
@@ -65,29 +87,50 @@ async fn main() -> eyre::Result<()> {
                     tracing::debug_span!("watching for reloads", path = %path.display());
 
                 tokio::spawn(async move {
-                    while let Some(filter) = reload.recv().await {
+                    while let Some(reloaded) = reload.recv().await {
                         tracing::debug!(parent: &watch_span, "found reload");
-                        s.replace(filter).await;
+
+                        match reloaded {
+                            Ok(filter) => {
+                                let title = "prax: reloaded config with problems";
+                                report(&reporter, title, filter.diagnostics()).await;
+                                s.replace(intercept.toggled(filter)).await;
+                            }
+
+                            Err(diagnostic) => {
+                                let title = "prax: failed to reload config";
+                                report(&reporter, title, &[diagnostic]).await;
+                            }
+                        }
                     }
                 });
             }
 
             server.listen().await?;
         } else {
-            let config = Config::<()>::default();
-            let server = srv::Server::new(cli.listen, token, config, history, tls);
+            let config = intercept.toggled(Config::<()>::default());
+            let server = server.filter(config).scribe(history).build();
             server.listen().await?;
         };
     } else {
         let config = if let Some(path) = cli.configure {
-            Config::load(&path, ()).await?
+            Config::load(&path, (), cli.lua_states).await?
         } else {
             Config::default()
         };
 
-        let server = srv::Server::new(cli.listen, token, config, &(), tls);
+        let server = server.filter(config).scribe(()).build();
         server.listen().await?;
     };
 
     Ok(())
 }
+
+async fn report(reporter: &nvim::Reporter, title: &str, diagnostics: &[Diagnostic]) {
+    if diagnostics.is_empty() {
+        return;
+    }
+
+    let lines = diagnostics.iter().map(ToString::to_string).collect();
+    reporter.report(title, lines).await;
+}
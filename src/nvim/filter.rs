@@ -1,20 +1,58 @@
 use std::sync::Arc;
 
-use prax::Filter;
+use hyper::{header, StatusCode};
+use prax::{Filter, Identity, Origin, Outcome, Timeouts, Upstream};
 
-use super::{view::ViewOp, NVim};
+use super::{
+    queue::{self, Pending, Side, Verdict},
+    switch::Mode,
+    NVim,
+};
 
 use prax::{Req, Res};
-use tokio::sync::{Mutex, Notify};
 
 use prax::lines::{LinesImprint, ToLines};
 
 #[derive(Clone)]
-pub struct Intercept(Arc<Mutex<NVim>>);
+pub struct Intercept(Arc<NVim>);
+
+/// Marks a message the user has already seen, so it is not held twice
+#[derive(Clone, Copy, Debug)]
+struct Intercepted;
+
+/// Marks a request whose response the user asked to intercept as well
+#[derive(Clone, Copy, Debug)]
+struct Follow;
+
+/// A filter that also intercepts what was switched on from nvim
+pub struct Toggled<F> {
+    filter: F,
+    intercept: Intercept,
+}
 
 impl From<NVim> for Intercept {
     fn from(value: NVim) -> Self {
-        Intercept(Arc::new(Mutex::new(value)))
+        Intercept(Arc::new(value))
+    }
+}
+
+impl Intercept {
+    pub fn toggled<F>(&self, filter: F) -> Toggled<F> {
+        Toggled {
+            filter,
+            intercept: self.clone(),
+        }
+    }
+
+    fn mode(&self, hostname: &str) -> Mode {
+        self.0.switch.mode(hostname)
+    }
+
+    /// whether the request `res` answers was followed
+    fn followed(res: &Res<Vec<u8>>) -> bool {
+        res.extensions()
+            .get::<Origin>()
+            .is_some_and(|Origin(req)| req.extensions().get::<Follow>().is_some())
     }
 }
 
@@ -23,85 +61,151 @@ impl Filter for Intercept {
         &self,
         hostname: &mut String,
         req: &mut Req<Vec<u8>>,
-    ) -> prax::Result<()> {
-        let nvim = self.0.lock().await;
-        nvim.modify_request(hostname, req).await
+    ) -> prax::Result<Outcome> {
+        if self.mode(hostname) == Mode::Off || req.extensions().get::<Intercepted>().is_some() {
+            return Ok(Outcome::Continue);
+        }
+
+        let verdict = self.0.hold(hostname, Side::Request, req.to_lines()?).await;
+        req.extensions_mut().insert(Intercepted);
+
+        if matches!(verdict, Verdict::Follow(_)) {
+            req.extensions_mut().insert(Follow);
+        }
+
+        outcome(verdict, req)
     }
 
     async fn modify_response(
         &self,
         hostname: &mut String,
-        req: &mut Res<Vec<u8>>,
-    ) -> prax::Result<()> {
-        let nvim = self.0.lock().await;
-        nvim.modify_response(hostname, req).await
-    }
-}
+        res: &mut Res<Vec<u8>>,
+    ) -> prax::Result<Outcome> {
+        if res.extensions().get::<Intercepted>().is_some() {
+            return Ok(Outcome::Continue);
+        }
 
-impl Filter for NVim {
-    async fn modify_request(&self, _: &mut String, req: &mut Req<Vec<u8>>) -> prax::Result<()> {
-        let content = req.to_lines()?;
+        if self.mode(hostname) == Mode::Off && !Intercept::followed(res) {
+            return Ok(Outcome::Continue);
+        }
 
-        let notify = {
-            let mut backlog = self.backlog.lock().await;
+        let verdict = self.0.hold(hostname, Side::Response, res.to_lines()?).await;
+        res.extensions_mut().insert(Intercepted);
 
-            if self
-                .action
-                .send(ViewOp::Intercept {
-                    title: "Intercept Request".into(),
-                    content,
-                })
-                .await
-                .is_err()
-            {
-                return Ok(());
-            };
+        outcome(verdict, res)
+    }
+}
 
-            let notify = Arc::new(Notify::new());
-            backlog.push_back(notify.clone());
-            notify
-        };
+impl<F> Filter for Toggled<F>
+where
+    F: Filter + Sync,
+{
+    async fn modify_request(
+        &self,
+        hostname: &mut String,
+        req: &mut Req<Vec<u8>>,
+    ) -> prax::Result<Outcome> {
+        match self.filter.modify_request(hostname, req).await? {
+            Outcome::Continue if self.intercept.mode(hostname) == Mode::On => {
+                self.intercept.modify_request(hostname, req).await
+            }
+            outcome => Ok(outcome),
+        }
+    }
 
-        notify.notified().await;
+    async fn modify_response(
+        &self,
+        hostname: &mut String,
+        res: &mut Res<Vec<u8>>,
+    ) -> prax::Result<Outcome> {
+        match self.filter.modify_response(hostname, res).await? {
+            Outcome::Continue
+                if self.intercept.mode(hostname) == Mode::On || Intercept::followed(res) =>
+            {
+                self.intercept.modify_response(hostname, res).await
+            }
+            outcome => Ok(outcome),
+        }
+    }
+}
 
-        let view = self.view.lock().await;
-        let content = view.intercept_buffer().await?;
+impl<F: Upstream> Upstream for Toggled<F> {
+    fn identity(&self, hostname: &str) -> Option<Identity> {
+        self.filter.identity(hostname)
+    }
 
-        req.imprint(content)?;
+    fn timeouts(&self, hostname: &str) -> Timeouts {
+        self.filter.timeouts(hostname)
+    }
 
-        Ok(())
+    fn in_scope(&self, hostname: &str) -> bool {
+        self.filter.in_scope(hostname) || self.intercept.mode(hostname) == Mode::On
     }
+}
 
-    async fn modify_response(&self, _: &mut String, res: &mut Res<Vec<u8>>) -> prax::Result<()> {
-        let content = res.to_lines()?;
+impl NVim {
+    /// queues a message for the user and waits on what they decide
+    async fn hold(&self, hostname: &str, side: Side, content: Vec<String>) -> Verdict {
+        let (pending, verdict) = Pending::new(hostname, side, content);
 
-        let notify = {
+        {
             let mut backlog = self.backlog.lock().await;
+            backlog.push_back(pending);
 
-            if self
-                .action
-                .send(ViewOp::Intercept {
-                    title: "Intercept Response".into(),
-                    content,
-                })
-                .await
-                .is_err()
-            {
-                return Ok(());
+            let shown = if backlog.len() == 1 {
+                queue::present(&backlog, &self.action).await
+            } else {
+                queue::list(&backlog, &self.action).await
+            };
+
+            if shown.is_err() {
+                backlog.pop_back();
+                return Verdict::Forward(None);
             }
+        }
 
-            let notify = Arc::new(Notify::new());
-            backlog.push_back(notify.clone());
-            notify
-        };
+        // the ui went away, let it through
+        verdict.await.unwrap_or(Verdict::Forward(None))
+    }
+}
 
-        notify.notified().await;
+/// applies what the user decided to `msg`
+fn outcome<M>(verdict: Verdict, msg: &mut M) -> prax::Result<Outcome>
+where
+    M: LinesImprint<Error = prax::Error>,
+{
+    match verdict {
+        Verdict::Forward(Some(content)) | Verdict::Follow(content) => {
+            msg.imprint(content)?;
+            Ok(Outcome::Continue)
+        }
 
-        let view = self.view.lock().await;
-        let content = view.intercept_buffer().await?;
+        Verdict::Forward(None) => Ok(Outcome::Continue),
 
-        res.imprint(content)?;
+        Verdict::Answer(content) => {
+            let mut res = Res::new(Vec::new());
+            res.imprint(content)?;
 
-        Ok(())
+            Ok(Outcome::Respond(res))
+        }
+
+        Verdict::BadGateway => Ok(Outcome::Respond(bad_gateway())),
+        Verdict::Close => Ok(Outcome::Drop),
     }
 }
+
+fn bad_gateway() -> Res<Vec<u8>> {
+    let body = b"prax: dropped while intercepted".to_vec();
+    let length = body.len();
+
+    let mut res = Res::new(body);
+    *res.status_mut() = StatusCode::BAD_GATEWAY;
+    res.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/plain"),
+    );
+    res.headers_mut()
+        .insert(header::CONTENT_LENGTH, length.into());
+
+    res
+}
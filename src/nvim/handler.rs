@@ -6,6 +6,33 @@ use super::Neovim;
 pub enum Event {
     Detail,
     SubmitIntercept,
+
+    /// type a response to send back instead of the intercepted request
+    AnswerIntercept,
+
+    /// submit the intercepted request and intercept its response too
+    FollowIntercept,
+
+    /// `:PraxIntercept` arguments
+    ToggleIntercept(String),
+    ForwardAll,
+
+    /// drop the shown intercept, closing the connection instead of answering 502
+    DropIntercept {
+        close: bool,
+    },
+    ShowQueue,
+
+    /// `:PraxFilter` query, empty to list everything
+    FilterHistory(String),
+    ShowSiteMap,
+
+    /// enter on the site map row under the cursor
+    SiteMapSelect,
+
+    /// mark the entry under the cursor in the history list
+    MarkEntry,
+    DiffMarked,
     DismissDetail,
     Shutdown,
     Chan(u64),
@@ -54,6 +81,62 @@ impl nvim_rs::Handler for Handler {
                 let _ = self.chan.send(Event::SubmitIntercept).await;
             }
 
+            "toggle_intercept" => {
+                let args = match args.first() {
+                    Some(Value::String(args)) => args.as_str().unwrap_or_default().to_string(),
+                    _ => String::new(),
+                };
+
+                let _ = self.chan.send(Event::ToggleIntercept(args)).await;
+            }
+
+            "answer_intercept" => {
+                let _ = self.chan.send(Event::AnswerIntercept).await;
+            }
+
+            "follow_intercept" => {
+                let _ = self.chan.send(Event::FollowIntercept).await;
+            }
+
+            "forward_all" => {
+                let _ = self.chan.send(Event::ForwardAll).await;
+            }
+
+            "drop_intercept" => {
+                let close = matches!(args.first(), Some(Value::Boolean(true)));
+
+                let _ = self.chan.send(Event::DropIntercept { close }).await;
+            }
+
+            "filter_history" => {
+                let query = match args.first() {
+                    Some(Value::String(query)) => query.as_str().unwrap_or_default().to_string(),
+                    _ => String::new(),
+                };
+
+                let _ = self.chan.send(Event::FilterHistory(query)).await;
+            }
+
+            "show_sitemap" => {
+                let _ = self.chan.send(Event::ShowSiteMap).await;
+            }
+
+            "sitemap_select" => {
+                let _ = self.chan.send(Event::SiteMapSelect).await;
+            }
+
+            "mark_entry" => {
+                let _ = self.chan.send(Event::MarkEntry).await;
+            }
+
+            "diff_marked" => {
+                let _ = self.chan.send(Event::DiffMarked).await;
+            }
+
+            "show_queue" => {
+                let _ = self.chan.send(Event::ShowQueue).await;
+            }
+
             _ => (),
         }
     }
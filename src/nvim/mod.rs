@@ -1,18 +1,15 @@
-use std::{collections::VecDeque, sync::Arc};
-
 use crate::cli::NvimConnInfo;
 use prax::hist::Hist;
-use tokio::sync::{mpsc, Mutex, Notify};
+use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 
-use self::{
-    handler::Handler,
-    view::{View, ViewOp},
-};
+use self::{handler::Handler, queue::Backlog, switch::Switch, view::ViewOp};
 
 mod filter;
 mod handler;
 mod io;
+mod queue;
+mod switch;
 mod tasks;
 mod view;
 
@@ -23,9 +20,9 @@ pub(crate) type Buffer = nvim_rs::Buffer<io::IoConn>;
 pub(crate) type Window = nvim_rs::Window<io::IoConn>;
 
 pub struct NVim {
-    view: Arc<Mutex<View>>,
     action: mpsc::Sender<ViewOp>,
-    backlog: Arc<Mutex<VecDeque<Arc<Notify>>>>,
+    backlog: Backlog,
+    switch: Switch,
 }
 
 impl NVim {
@@ -40,21 +37,50 @@ impl NVim {
         let single = conn_info.singleton();
 
         let (nvim, join) = io::IoConn::connect(&conn_info, handler).await?;
-        let (view, action) = View::new(nvim, token.clone()).await?;
-        let backlog = Arc::new(Mutex::new(VecDeque::<Arc<Notify>>::new()));
+        let (view, action) = view::View::new(nvim, token.clone(), history).await?;
+        let backlog = Backlog::default();
+        let switch = Switch::default();
 
         tasks::runloop(join, if single { Some(token) } else { None });
-        tasks::ui_binding(recv, view.clone(), action.clone(), backlog.clone(), history);
+        tasks::ui_binding(
+            recv,
+            view,
+            action.clone(),
+            backlog.clone(),
+            switch.clone(),
+            history,
+        );
         tasks::history_report(action.clone(), history);
 
         Ok(NVim {
-            view,
             action,
             backlog,
+            switch,
         })
     }
 
     pub fn intercept(self) -> Intercept {
         self.into()
     }
+
+    pub fn reporter(&self) -> Reporter {
+        Reporter(self.action.clone())
+    }
+}
+
+/// Shows problems outside of any exchange, like a config failing to reload
+#[derive(Clone)]
+pub struct Reporter(mpsc::Sender<ViewOp>);
+
+impl Reporter {
+    pub async fn report(&self, title: &str, lines: Vec<String>) {
+        let op = ViewOp::Report {
+            title: title.to_string(),
+            lines,
+        };
+
+        if self.0.send(op).await.is_err() {
+            tracing::error!("failed to report {title}");
+        }
+    }
 }
@@ -0,0 +1,156 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use tokio::sync::{
+    mpsc::{error::SendError, Sender},
+    oneshot, Mutex, MutexGuard,
+};
+
+use super::view::ViewOp;
+
+/// Intercepted messages waiting on the user, the front one is shown for editing
+#[derive(Clone, Default)]
+pub struct Backlog(Arc<Mutex<VecDeque<Pending>>>);
+
+pub struct Pending {
+    pub hostname: String,
+    pub side: Side,
+    pub content: Vec<String>,
+
+    /// the user is typing a response to send back instead
+    pub answering: bool,
+    reply: oneshot::Sender<Verdict>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Request,
+    Response,
+}
+
+/// What the user decided to do with an intercepted message
+#[derive(Debug)]
+pub enum Verdict {
+    /// pass it on, with the edited lines if there are any
+    Forward(Option<Vec<String>>),
+
+    /// pass it on and intercept the response to it as well
+    Follow(Vec<String>),
+
+    /// answer with the response typed in instead
+    Answer(Vec<String>),
+
+    /// answer with a 502 instead
+    BadGateway,
+
+    /// close the connection instead
+    Close,
+}
+
+impl Backlog {
+    pub async fn lock(&self) -> MutexGuard<'_, VecDeque<Pending>> {
+        self.0.lock().await
+    }
+}
+
+impl Pending {
+    pub fn new(
+        hostname: &str,
+        side: Side,
+        content: Vec<String>,
+    ) -> (Pending, oneshot::Receiver<Verdict>) {
+        let (reply, verdict) = oneshot::channel();
+
+        let pending = Pending {
+            hostname: hostname.to_string(),
+            side,
+            content,
+            answering: false,
+            reply,
+        };
+
+        (pending, verdict)
+    }
+
+    pub fn title(&self) -> String {
+        let action = if self.answering {
+            "Answer"
+        } else {
+            "Intercept"
+        };
+
+        format!("{action} {} ({})", self.side, self.hostname)
+    }
+
+    /// what is shown while answering, a response to fill in
+    pub fn answer(&mut self) -> Vec<String> {
+        self.answering = true;
+
+        vec![
+            "200".to_string(),
+            "content-type: text/plain".to_string(),
+            String::new(),
+        ]
+    }
+
+    /// passes on what was typed into the intercept window
+    pub fn submit(self, content: Option<Vec<String>>, follow: bool) {
+        let verdict = match content {
+            Some(content) if self.answering => Verdict::Answer(content),
+            Some(content) if follow && self.side == Side::Request => Verdict::Follow(content),
+            content => Verdict::Forward(content),
+        };
+
+        self.decide(verdict);
+    }
+
+    pub fn decide(self, verdict: Verdict) {
+        if self.reply.send(verdict).is_err() {
+            tracing::debug!("intercepted {} was abandoned", self.side);
+        }
+    }
+}
+
+impl std::fmt::Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Side::Request => write!(f, "Request"),
+            Side::Response => write!(f, "Response"),
+        }
+    }
+}
+
+/// shows the front of the backlog (or closes the intercept window) and lists what is left
+pub async fn present(
+    backlog: &VecDeque<Pending>,
+    actions: &Sender<ViewOp>,
+) -> Result<(), SendError<ViewOp>> {
+    let op = match backlog.front() {
+        Some(front) => ViewOp::Intercept {
+            title: front.title(),
+            content: front.content.clone(),
+        },
+        None => ViewOp::DismissIntercept,
+    };
+
+    actions.send(op).await?;
+    list(backlog, actions).await
+}
+
+/// updates the queue buffer with what is waiting
+pub async fn list(
+    backlog: &VecDeque<Pending>,
+    actions: &Sender<ViewOp>,
+) -> Result<(), SendError<ViewOp>> {
+    let lines = backlog
+        .iter()
+        .enumerate()
+        .map(|(i, pending)| {
+            let first = pending.content.first().map(String::as_str).unwrap_or("");
+            let marker = if i == 0 { '>' } else { ' ' };
+
+            format!("{marker} {} {} {first}", pending.side, pending.hostname)
+        })
+        .collect();
+
+    actions.send(ViewOp::Queue { lines }).await
+}
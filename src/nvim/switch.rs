@@ -0,0 +1,209 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+/// Whether messages get intercepted, set at runtime from nvim
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// only where the config has an `intercept` rule
+    #[default]
+    Config,
+
+    /// every message, rules or not
+    On,
+
+    /// nothing, even where the config has an `intercept` rule
+    Off,
+}
+
+/// Intercept modes, globally and overridden per target ("host" or "host:port")
+#[derive(Clone, Default)]
+pub struct Switch(Arc<RwLock<Modes>>);
+
+#[derive(Default)]
+struct Modes {
+    global: Mode,
+    targets: BTreeMap<String, Mode>,
+}
+
+impl Switch {
+    pub fn mode(&self, hostname: &str) -> Mode {
+        let modes = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        let host = hostname.split_once(':').map_or(hostname, |(host, _)| host);
+
+        modes
+            .targets
+            .get(hostname)
+            .or_else(|| modes.targets.get(host))
+            .copied()
+            .unwrap_or(modes.global)
+    }
+
+    /// applies `:PraxIntercept [on|off|config|clear] [target]`, describing the result
+    ///
+    /// without a mode, intercept is flipped between on and off,
+    /// `clear` drops the override of a target or of all targets
+    pub fn apply(&self, args: &str) -> Result<String, String> {
+        let mut words = args.split_whitespace();
+        let first = words.next();
+
+        let clear = first == Some("clear");
+        let (mode, target) = match first.map(Mode::parse) {
+            _ if clear => (None, words.next()),
+            Some(Some(mode)) => (Some(mode), words.next()),
+            Some(None) => (None, first),
+            None => (None, None),
+        };
+
+        if let Some(extra) = words.next() {
+            return Err(format!("unexpected argument \"{extra}\""));
+        }
+
+        let mut modes = self.0.write().unwrap_or_else(PoisonError::into_inner);
+
+        match target {
+            Some(target) if clear => {
+                modes.targets.remove(target);
+
+                Ok(format!("intercept {} for {target}", modes.global))
+            }
+
+            None if clear => {
+                modes.targets.clear();
+
+                Ok(format!("intercept {} for every target", modes.global))
+            }
+
+            Some(target) => {
+                let current = modes.targets.get(target).copied().unwrap_or(modes.global);
+                let mode = mode.unwrap_or(current.flipped());
+
+                modes.targets.insert(target.to_string(), mode);
+
+                Ok(format!("intercept {mode} for {target}"))
+            }
+
+            None => {
+                modes.global = mode.unwrap_or(modes.global.flipped());
+
+                Ok(format!("intercept {}", modes.global))
+            }
+        }
+    }
+}
+
+impl Mode {
+    fn parse(word: &str) -> Option<Mode> {
+        match word {
+            "config" => Some(Mode::Config),
+            "on" => Some(Mode::On),
+            "off" => Some(Mode::Off),
+            _ => None,
+        }
+    }
+
+    fn flipped(self) -> Mode {
+        match self {
+            Mode::On => Mode::Off,
+            Mode::Config | Mode::Off => Mode::On,
+        }
+    }
+}
+
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mode::Config => write!(f, "as configured"),
+            Mode::On => write!(f, "on"),
+            Mode::Off => write!(f, "off"),
+        }
+    }
+}
+
+#[test]
+fn test_flip() {
+    let switch = Switch::default();
+    assert_eq!(switch.mode("example.com:443"), Mode::Config);
+
+    assert_eq!(switch.apply(""), Ok("intercept on".to_string()));
+    assert_eq!(switch.mode("example.com:443"), Mode::On);
+
+    assert_eq!(switch.apply("  "), Ok("intercept off".to_string()));
+    assert_eq!(switch.mode("example.com:443"), Mode::Off);
+
+    assert_eq!(
+        switch.apply("config"),
+        Ok("intercept as configured".to_string())
+    );
+    assert_eq!(switch.apply(""), Ok("intercept on".to_string()));
+
+    // a target flips from the global mode
+    assert_eq!(
+        switch.apply("example.com"),
+        Ok("intercept off for example.com".to_string())
+    );
+    assert_eq!(switch.mode("example.com:443"), Mode::Off);
+    assert_eq!(switch.mode("other.com:443"), Mode::On);
+}
+
+#[test]
+fn test_target() {
+    let switch = Switch::default();
+
+    switch.apply("off").unwrap();
+    switch.apply("on example.com").unwrap();
+    switch.apply("off example.com:8080").unwrap();
+
+    // host:port wins over host, host over the global mode
+    assert_eq!(switch.mode("example.com:8080"), Mode::Off);
+    assert_eq!(switch.mode("example.com:443"), Mode::On);
+    assert_eq!(switch.mode("example.com"), Mode::On);
+    assert_eq!(switch.mode("other.com:443"), Mode::Off);
+
+    // config is an override too, the global mode no longer applies
+    assert_eq!(
+        switch.apply("config example.com"),
+        Ok("intercept as configured for example.com".to_string())
+    );
+    assert_eq!(switch.mode("example.com:443"), Mode::Config);
+    assert_eq!(switch.mode("example.com:8080"), Mode::Off);
+
+    switch.apply("on").unwrap();
+    assert_eq!(switch.mode("example.com:443"), Mode::Config);
+
+    // clear drops the override, the global mode applies again
+    assert_eq!(
+        switch.apply("clear example.com"),
+        Ok("intercept on for example.com".to_string())
+    );
+    assert_eq!(switch.mode("example.com:443"), Mode::On);
+    assert_eq!(switch.mode("example.com:8080"), Mode::Off);
+
+    assert_eq!(
+        switch.apply("clear"),
+        Ok("intercept on for every target".to_string())
+    );
+    assert_eq!(switch.mode("example.com:8080"), Mode::On);
+}
+
+#[test]
+fn test_extra_arguments() {
+    let switch = Switch::default();
+
+    assert_eq!(
+        switch.apply("on example.com extra"),
+        Err("unexpected argument \"extra\"".to_string())
+    );
+    assert_eq!(
+        switch.apply("example.com other.com"),
+        Err("unexpected argument \"other.com\"".to_string())
+    );
+    assert_eq!(
+        switch.apply("clear example.com extra"),
+        Err("unexpected argument \"extra\"".to_string())
+    );
+
+    // nothing was applied
+    assert_eq!(switch.mode("example.com:443"), Mode::Config);
+}
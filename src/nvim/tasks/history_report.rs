@@ -1,4 +1,6 @@
-use tokio::sync::mpsc::Sender;
+use std::collections::HashMap;
+
+use tokio::sync::{broadcast::error::RecvError, mpsc::Sender};
 
 use crate::nvim::view::ViewOp;
 use prax::hist::{Hist, HistoryEvent};
@@ -7,9 +9,22 @@ pub fn history_report(actions: Sender<ViewOp>, history: &'static Hist) {
     tokio::spawn(async move {
         let mut recv = history.listen();
 
+        // how far each live body has been tailed, to catch up on skipped chunks
+        let mut tailed = HashMap::<usize, usize>::new();
+
         loop {
-            let Ok(event) = recv.recv().await else {
-                break;
+            let event = match recv.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    // rows would no longer line up with entries, redraw everything
+                    tracing::warn!("history view skipped {skipped} events");
+
+                    if actions.send(ViewOp::Resync).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
             };
 
             match event {
@@ -48,6 +63,27 @@ pub fn history_report(actions: Sender<ViewOp>, history: &'static Hist) {
                         break;
                     }
                 }
+                HistoryEvent::ResponseChunk { index, offset, len } => {
+                    let from = tailed.get(&index).copied().unwrap_or(offset).min(offset);
+                    let Some(chunk) = history.streamed_range(index, from..offset + len) else {
+                        continue;
+                    };
+
+                    tailed.insert(index, offset + len);
+
+                    let text = String::from_utf8_lossy(&chunk).to_string();
+
+                    if actions
+                        .send(ViewOp::Tail { entry: index, text })
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                HistoryEvent::ResponseEnd { index } => {
+                    tailed.remove(&index);
+                }
             }
         }
     });
@@ -1,22 +1,25 @@
-use std::{collections::VecDeque, sync::Arc};
+use std::sync::Arc;
 
 use tokio::sync::{
     mpsc::{Receiver, Sender},
-    Mutex, Notify,
+    Mutex,
 };
 
 use crate::nvim::{
     handler::Event,
-    view::{View, ViewOp},
+    queue::{self, Backlog, Side, Verdict},
+    switch::Switch,
+    view::{Compared, View, ViewOp},
 };
-use prax::hist::Hist;
+use prax::hist::{Diff, Hist, Node, Query};
 use prax::lines::ToLines;
 
 pub fn ui_binding(
     mut recv: Receiver<Event>,
     view: Arc<Mutex<View>>,
     actions: Sender<ViewOp>,
-    backlog: Arc<Mutex<VecDeque<Arc<Notify>>>>,
+    backlog: Backlog,
+    switch: Switch,
     history: &'static Hist,
 ) {
     tokio::spawn(async move {
@@ -26,49 +29,180 @@ pub fn ui_binding(
 
             match event {
                 Event::Detail => {
-                    let Ok(line) = view.find_line().await else {
+                    let Ok(index) = view.find_entry().await else {
                         continue;
                     };
 
-                    let index = line as usize - 1;
-
-                    let Some(entry) = history.entry(index) else {
+                    let Some(op) = detail(history, index) else {
                         continue;
                     };
 
-                    let Ok(req) = entry.request.to_lines() else {
-                        continue;
+                    let Ok(_) = actions.send(op).await else {
+                        return; // stop subtask if no reciever
                     };
+                }
 
-                    let res = match &entry.response {
-                        Some(response) => {
-                            let Ok(res) = response.to_lines() else {
+                Event::SiteMapSelect => {
+                    let op = match view.find_node().await {
+                        Ok(Node::Branch(path)) => ViewOp::Fold { path },
+                        Ok(Node::Entry(index)) => {
+                            let Some(op) = detail(history, index) else {
                                 continue;
                             };
 
-                            res
+                            op
                         }
+                        Err(_) => continue,
+                    };
 
-                        None => {
-                            vec![]
-                        }
+                    let Ok(_) = actions.send(op).await else {
+                        return;
                     };
+                }
 
-                    let Ok(_) = actions.send(ViewOp::Detail { req, res }).await else {
-                        return; // stop subtask if no reciever
+                Event::MarkEntry => {
+                    let Ok(entry) = view.find_entry().await else {
+                        continue;
+                    };
+
+                    let Ok(_) = actions.send(ViewOp::Mark { entry }).await else {
+                        return;
+                    };
+                }
+
+                Event::DiffMarked => {
+                    let op = match view.marks() {
+                        [left, right] => diff(history, *left, *right),
+                        _ => None,
+                    };
+
+                    let op = op.unwrap_or_else(|| ViewOp::Report {
+                        title: "prax: can not diff".to_string(),
+                        lines: vec![
+                            "mark two entries in the history list with :PraxMark".to_string()
+                        ],
+                    });
+
+                    let Ok(_) = actions.send(op).await else {
+                        return;
                     };
                 }
+
+                Event::ShowSiteMap => {
+                    let _ = actions.send(ViewOp::ShowSiteMap).await;
+                }
                 Event::SubmitIntercept => {
                     let mut backlog = backlog.lock().await;
 
-                    if let Some(notify) = backlog.pop_front() {
-                        if backlog.is_empty() {
-                            let Ok(_) = actions.send(ViewOp::DismissIntercept).await else {
-                                return;
-                            };
-                        }
-                        notify.notify_one();
+                    let Some(pending) = backlog.pop_front() else {
+                        continue;
+                    };
+
+                    pending.submit(typed(&view).await, false);
+
+                    let Ok(_) = queue::present(&backlog, &actions).await else {
+                        return;
+                    };
+                }
+
+                Event::AnswerIntercept => {
+                    let mut backlog = backlog.lock().await;
+
+                    let Some(pending) = backlog.front_mut() else {
+                        continue;
+                    };
+
+                    if pending.side != Side::Request || pending.answering {
+                        continue;
                     }
+
+                    let content = pending.answer();
+                    let title = pending.title();
+
+                    let Ok(_) = actions.send(ViewOp::Intercept { title, content }).await else {
+                        return;
+                    };
+                }
+
+                Event::FollowIntercept => {
+                    let mut backlog = backlog.lock().await;
+
+                    let Some(pending) = backlog.pop_front() else {
+                        continue;
+                    };
+
+                    pending.submit(typed(&view).await, true);
+
+                    let Ok(_) = queue::present(&backlog, &actions).await else {
+                        return;
+                    };
+                }
+
+                Event::ForwardAll => {
+                    let mut backlog = backlog.lock().await;
+
+                    if let Some(pending) = backlog.pop_front() {
+                        pending.submit(typed(&view).await, false);
+                    }
+
+                    for pending in backlog.drain(..) {
+                        pending.decide(Verdict::Forward(None));
+                    }
+
+                    let Ok(_) = queue::present(&backlog, &actions).await else {
+                        return;
+                    };
+                }
+
+                Event::DropIntercept { close } => {
+                    let mut backlog = backlog.lock().await;
+
+                    let Some(pending) = backlog.pop_front() else {
+                        continue;
+                    };
+
+                    pending.decide(if close {
+                        Verdict::Close
+                    } else {
+                        Verdict::BadGateway
+                    });
+
+                    let Ok(_) = queue::present(&backlog, &actions).await else {
+                        return;
+                    };
+                }
+
+                Event::ToggleIntercept(args) => {
+                    let op = match switch.apply(&args) {
+                        Ok(message) => ViewOp::Notice { message },
+                        Err(e) => ViewOp::Report {
+                            title: "prax: can not toggle intercept".to_string(),
+                            lines: vec![e],
+                        },
+                    };
+
+                    let Ok(_) = actions.send(op).await else {
+                        return;
+                    };
+                }
+
+                Event::FilterHistory(query) => {
+                    let op = match query.parse::<Query>() {
+                        Ok(_) if query.trim().is_empty() => ViewOp::Filter { query: None },
+                        Ok(query) => ViewOp::Filter { query: Some(query) },
+                        Err(e) => ViewOp::Report {
+                            title: "prax: can not filter history".to_string(),
+                            lines: vec![e.to_string()],
+                        },
+                    };
+
+                    let Ok(_) = actions.send(op).await else {
+                        return;
+                    };
+                }
+
+                Event::ShowQueue => {
+                    let _ = actions.send(ViewOp::ShowQueue).await;
                 }
 
                 Event::DismissDetail => {
@@ -77,6 +211,10 @@ pub fn ui_binding(
 
                 Event::Chan(chan) => {
                     view.chan = chan;
+
+                    if let Err(e) = view.commands().await {
+                        tracing::error!("failed to create commands: {e}");
+                    }
                 }
 
                 Event::Shutdown => {
@@ -86,3 +224,77 @@ pub fn ui_binding(
         }
     });
 }
+
+/// what the user typed into the intercept window
+async fn typed(view: &View) -> Option<Vec<String>> {
+    match view.intercept_buffer().await {
+        Ok(content) => Some(content),
+        Err(e) => {
+            tracing::error!("failed to read intercept buffer: {e}");
+            None
+        }
+    }
+}
+
+/// both sides of `index` rendered for the detail view
+fn detail(history: &Hist, index: usize) -> Option<ViewOp> {
+    let (req, res) = rendered(history, index)?;
+
+    Some(ViewOp::Detail {
+        entry: index,
+        req,
+        res,
+    })
+}
+
+/// the entries `left` and `right` rendered to compare
+fn diff(history: &Hist, left: usize, right: usize) -> Option<ViewOp> {
+    let changes = Diff::new(&history.entry(left)?, &history.entry(right)?).lines();
+
+    let compared = |index: usize| {
+        let (mut lines, res) = rendered(history, index)?;
+        lines.push(String::new());
+        lines.extend(res);
+
+        let request = history.request(index)?;
+        let title = format!("#{index} {} {}", request.method, request.path);
+
+        Some(Compared { title, lines })
+    };
+
+    Some(ViewOp::Diff {
+        left: compared(left)?,
+        right: compared(right)?,
+        changes,
+    })
+}
+
+/// the request and response of `index` as lines, live bodies as streamed so far
+fn rendered(history: &Hist, index: usize) -> Option<(Vec<String>, Vec<String>)> {
+    let entry = history.entry(index)?;
+
+    let Ok(req) = entry.request.to_lines();
+
+    let res = match (&entry.response, history.streamed(index)) {
+        (Some(response), Some(body)) => {
+            let mut response = (*response).clone();
+            response.body = body;
+
+            let Ok(res) = response.to_lines();
+
+            res
+        }
+
+        (Some(response), None) => {
+            let Ok(res) = response.to_lines();
+
+            res
+        }
+
+        (None, _) => {
+            vec![]
+        }
+    };
+
+    Some((req, res))
+}
@@ -1,4 +1,5 @@
 use nvim_rs::{error::CallError, Value};
+use prax::hist::{Hist, Node, Query, SiteMap};
 use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex};
 use tokio_util::sync::CancellationToken;
@@ -12,29 +13,64 @@ pub struct View {
 
     list: Buffer,
     intercept: Buffer,
+    queue: Buffer,
+    sitemap: Buffer,
 
     req_detail: Buffer,
     res_detail: Buffer,
+    diff_left: Buffer,
+    diff_right: Buffer,
 
     intercept_win: Option<Window>,
+    queue_win: Option<Window>,
+    sitemap_win: Option<Window>,
     req_win: Option<Window>,
     res_win: Option<Window>,
+    diff_wins: Vec<Window>,
+    detail: Option<usize>,
+
+    history: &'static Hist,
+    filtered: Option<Filtered>,
+    site: SiteMap,
+
+    /// what each row of the site map stands for
+    site_rows: Vec<Node>,
+
+    /// entries picked with `:PraxMark` to compare, the oldest first
+    marks: Vec<usize>,
 
     detail_group: i64,
     intercept_group: i64,
     namespace: i64,
+    marks_namespace: i64,
+}
+
+/// The history list narrowed down by `:PraxFilter`
+struct Filtered {
+    query: Query,
+
+    /// the entry shown on each row
+    rows: Vec<usize>,
 }
 
 impl View {
     pub async fn new(
         neovim: Neovim,
         cancel: CancellationToken,
+        history: &'static Hist,
     ) -> eyre::Result<(Arc<Mutex<Self>>, mpsc::Sender<ViewOp>)> {
         let list = neovim.create_buf(true, true).await?;
         let intercept = neovim.create_buf(false, true).await?;
+        let queue = neovim.create_buf(false, true).await?;
+        let sitemap = neovim.create_buf(false, true).await?;
         list.set_name("prax-history").await?;
+        queue.set_name("prax-queue").await?;
+        sitemap.set_name("prax-sitemap").await?;
         let intercept_win = None;
+        let queue_win = None;
+        let sitemap_win = None;
         let namespace = neovim.create_namespace("prax").await?;
+        let marks_namespace = neovim.create_namespace("prax-marks").await?;
 
         let win = neovim.get_current_win().await?;
         win.set_buf(&list).await?;
@@ -48,9 +84,23 @@ impl View {
             )
             .await?;
 
+        let actions = [
+            ("<c-x>", ":PraxDrop!<cr>"),
+            ("<c-a>", ":PraxAnswer<cr>"),
+            ("<c-t>", ":PraxFollow<cr>"),
+        ];
+
+        for (lhs, rhs) in actions {
+            intercept.set_keymap("n", lhs, rhs, vec![]).await?;
+        }
+
         list.set_keymap("n", "<cr>", ":lua require(\"prax\").detail()<cr>", vec![])
             .await?;
 
+        for (lhs, rhs) in [("M", ":PraxMark<cr>"), ("D", ":PraxDiff<cr>")] {
+            list.set_keymap("n", lhs, rhs, vec![]).await?;
+        }
+
         let intercept_group = neovim
             .create_augroup("PraxIntercept", vec![("clear".into(), true.into())])
             .await?;
@@ -61,9 +111,17 @@ impl View {
 
         let req_detail = neovim.create_buf(false, true).await?;
         let res_detail = neovim.create_buf(false, true).await?;
+        let diff_left = neovim.create_buf(false, true).await?;
+        let diff_right = neovim.create_buf(false, true).await?;
 
         let req_win = None;
         let res_win = None;
+        let diff_wins = Vec::new();
+        let marks = Vec::new();
+        let detail = None;
+        let filtered = None;
+        let site = SiteMap::from(history);
+        let site_rows = Vec::new();
         let chan = 0;
 
         let s = Self {
@@ -73,13 +131,27 @@ impl View {
 
             list,
             intercept,
+            queue,
+            sitemap,
 
             req_detail,
             res_detail,
+            diff_left,
+            diff_right,
             intercept_win,
+            queue_win,
+            sitemap_win,
             req_win,
             res_win,
+            diff_wins,
+            detail,
+            history,
+            filtered,
+            site,
+            site_rows,
+            marks,
             namespace,
+            marks_namespace,
             intercept_group,
             detail_group,
         };
@@ -113,17 +185,135 @@ impl View {
         Ok((res, send))
     }
 
-    pub async fn find_line(&self) -> eyre::Result<i64> {
+    /// the entry under the cursor in the history list
+    pub async fn find_entry(&self) -> eyre::Result<usize> {
         let win = self.neovim.get_current_win().await?;
         let buf = win.get_buf().await?;
 
-        if buf == self.list {
-            let (line, _) = win.get_cursor().await?;
-
-            Ok(line)
-        } else {
+        if buf != self.list {
             eyre::bail!("list is not the current window")
         }
+
+        let (line, _) = win.get_cursor().await?;
+        let row = line as usize - 1;
+
+        match &self.filtered {
+            Some(filtered) => match filtered.rows.get(row) {
+                Some(entry) => Ok(*entry),
+                None => eyre::bail!("no entry on line {line}"),
+            },
+            None => Ok(row),
+        }
+    }
+
+    pub fn marks(&self) -> &[usize] {
+        &self.marks
+    }
+
+    /// what the site map row under the cursor stands for
+    pub async fn find_node(&self) -> eyre::Result<Node> {
+        let win = self.neovim.get_current_win().await?;
+        let buf = win.get_buf().await?;
+
+        if buf != self.sitemap {
+            eyre::bail!("site map is not the current window")
+        }
+
+        let (line, _) = win.get_cursor().await?;
+
+        match self.site_rows.get(line as usize - 1) {
+            Some(node) => Ok(node.clone()),
+            None => eyre::bail!("nothing on line {line}"),
+        }
+    }
+
+    /// user commands calling back into prax, once its channel is known
+    pub async fn commands(&self) -> eyre::Result<()> {
+        let notify = |event: &str, args: &str| {
+            format!("lua vim.fn.rpcnotify({}, \"{event}\"{args})", self.chan)
+        };
+
+        let commands = [
+            (
+                "PraxIntercept",
+                notify("toggle_intercept", ", <q-args>"),
+                "intercept [on|off|config|clear] [target], flips on and off without a mode",
+                vec![("nargs".into(), "*".into())],
+            ),
+            (
+                "PraxAnswer",
+                notify("answer_intercept", ""),
+                "answer the intercepted request with a response typed in its place",
+                vec![],
+            ),
+            (
+                "PraxFollow",
+                notify("follow_intercept", ""),
+                "submit the intercepted request and intercept its response too",
+                vec![],
+            ),
+            (
+                "PraxForwardAll",
+                notify("forward_all", ""),
+                "forward every intercepted message",
+                vec![],
+            ),
+            (
+                "PraxDrop",
+                notify("drop_intercept", ", \"<bang>\" == \"!\""),
+                "drop the intercepted message with a 502, or close the connection with !",
+                vec![("bang".into(), true.into())],
+            ),
+            (
+                "PraxQueue",
+                notify("show_queue", ""),
+                "list intercepted messages",
+                vec![],
+            ),
+            (
+                "PraxSiteMap",
+                notify("show_sitemap", ""),
+                "show recorded traffic as a tree of hosts and paths",
+                vec![],
+            ),
+            (
+                "PraxMark",
+                notify("mark_entry", ""),
+                "mark the history entry under the cursor to compare, or unmark it",
+                vec![],
+            ),
+            (
+                "PraxDiff",
+                notify("diff_marked", ""),
+                "compare the two marked history entries side by side",
+                vec![],
+            ),
+            (
+                "PraxFilter",
+                notify("filter_history", ", <q-args>"),
+                "only list history matching a query, everything again without one",
+                vec![("nargs".into(), "*".into())],
+            ),
+        ];
+
+        for (name, command, desc, mut opts) in commands {
+            opts.push(("desc".into(), desc.into()));
+            self.neovim
+                .create_user_command(name, command.into(), opts)
+                .await?;
+        }
+
+        // expands and collapses branches, opens the detail of requests
+        self.sitemap
+            .set_keymap(
+                "n",
+                "<cr>",
+                &format!(":{}<cr>", notify("sitemap_select", "")),
+                vec![],
+            )
+            .await?;
+
+        Ok(())
     }
 
     pub async fn intercept_buffer(&self) -> Result<Vec<String>, Box<CallError>> {
@@ -140,8 +330,23 @@ impl View {
             } => self.handle_new_request(entry, method, path).await,
             ViewOp::NewResponse { entry, status } => self.handle_new_response(entry, status).await,
 
-            ViewOp::Detail { req, res } => self.handle_detail(req, res).await,
+            ViewOp::Detail { entry, req, res } => self.handle_detail(entry, req, res).await,
+            ViewOp::Tail { entry, text } => self.handle_tail(entry, text).await,
             ViewOp::Intercept { title, content } => self.handle_intercept(title, content).await,
+            ViewOp::Report { title, lines } => self.handle_report(title, lines).await,
+            ViewOp::Notice { message } => self.handle_notice(message).await,
+            ViewOp::Queue { lines } => self.handle_queue(lines).await,
+            ViewOp::ShowQueue => self.handle_show_queue().await,
+            ViewOp::Resync => self.handle_resync().await,
+            ViewOp::Filter { query } => self.handle_filter(query).await,
+            ViewOp::ShowSiteMap => self.handle_show_sitemap().await,
+            ViewOp::Fold { path } => self.handle_fold(path).await,
+            ViewOp::Mark { entry } => self.handle_mark(entry).await,
+            ViewOp::Diff {
+                left,
+                right,
+                changes,
+            } => self.handle_diff(left, right, changes).await,
 
             ViewOp::DismissIntercept => self.handle_dismiss_intercept().await,
             ViewOp::DismissDetail => self.handle_dismiss_detail().await,
@@ -158,30 +363,154 @@ impl View {
         method: String,
         path: String,
     ) -> eyre::Result<()> {
-        let color = color_method(&method);
-        let method_len = method.len();
+        if let Some(request) = self.history.request(entry) {
+            self.site.insert(entry, request);
+            self.redraw_sitemap().await?;
+        }
+
+        // after a resync the entry may already be listed, its row is redrawn then
+        let (row, listed) = match &mut self.filtered {
+            None => (entry, entry < self.list.line_count().await? as usize),
+            Some(filtered) => {
+                if let Some(row) = filtered.rows.iter().position(|shown| *shown == entry) {
+                    (row, true)
+                } else if matches(self.history, &filtered.query, entry) {
+                    filtered.rows.push(entry);
+                    (filtered.rows.len() - 1, false)
+                } else {
+                    return Ok(());
+                }
+            }
+        };
+
+        let end = if listed { row + 1 } else { row };
 
         self.list
             .set_lines(
-                entry as i64,
-                entry as i64,
+                row as i64,
+                end as i64,
                 false,
                 vec![format!("{} {}", method, path)],
             )
             .await?;
 
+        self.highlight_method(row, &method).await
+    }
+
+    async fn handle_new_response(&mut self, entry: usize, status: u16) -> eyre::Result<()> {
+        if let Some(request) = self.history.request(entry) {
+            self.site.answer(entry, request, status);
+            self.redraw_sitemap().await?;
+        }
+
+        let row = match &self.filtered {
+            None => entry,
+            Some(filtered) => {
+                let row = filtered.rows.iter().position(|shown| *shown == entry);
+
+                // the status may decide whether the entry is listed at all
+                match (row, matches(self.history, &filtered.query, entry)) {
+                    (Some(row), true) => row,
+                    (None, false) => return Ok(()),
+                    _ => return self.render().await,
+                }
+            }
+        };
+
+        self.mark_status(row, status).await
+    }
+
+    async fn handle_resync(&mut self) -> eyre::Result<()> {
+        for (entry, ent) in self.history {
+            self.site.insert(entry, ent.request);
+
+            if let Some(response) = ent.response {
+                self.site.answer(entry, ent.request, response.status);
+            }
+        }
+
+        self.redraw_sitemap().await?;
+        self.render().await
+    }
+
+    async fn handle_filter(&mut self, query: Option<Query>) -> eyre::Result<()> {
+        self.filtered = query.map(|query| Filtered {
+            query,
+            rows: Vec::new(),
+        });
+
+        self.render().await?;
+
+        let message = match &self.filtered {
+            Some(filtered) => format!(
+                "listing {} of {} requests",
+                filtered.rows.len(),
+                self.history.len()
+            ),
+            None => format!("listing all {} requests", self.history.len()),
+        };
+
+        self.handle_notice(message).await
+    }
+
+    /// redraws the whole history list, for the filter if there is one
+    async fn render(&mut self) -> eyre::Result<()> {
+        let history = self.history;
+        let entries: Vec<_> = history
+            .iter()
+            .filter(|(index, _)| match &self.filtered {
+                Some(filtered) => matches(history, &filtered.query, *index),
+                None => true,
+            })
+            .collect();
+
+        if let Some(filtered) = &mut self.filtered {
+            filtered.rows = entries.iter().map(|(index, _)| *index).collect();
+        }
+
+        let lines = entries
+            .iter()
+            .map(|(_, ent)| format!("{} {}", ent.request.method, ent.request.path))
+            .collect();
+
+        self.list.clear_namespace(self.namespace, 0, -1).await?;
+        self.list.set_lines(0, -1, false, lines).await?;
+
+        for (row, (_, ent)) in entries.iter().enumerate() {
+            self.highlight_method(row, &ent.request.method).await?;
+
+            if let Some(response) = ent.response {
+                self.mark_status(row, response.status).await?;
+            }
+        }
+
+        self.draw_marks().await
+    }
+
+    /// the row of the history list showing `entry`, if it is listed
+    fn row_of(&self, entry: usize) -> Option<usize> {
+        match &self.filtered {
+            Some(filtered) => filtered.rows.iter().position(|shown| *shown == entry),
+            None => Some(entry),
+        }
+    }
+
+    async fn highlight_method(&self, row: usize, method: &str) -> eyre::Result<()> {
+        let color = color_method(method);
+
         self.list
-            .add_highlight(self.namespace, color, entry as i64, 0, method_len as i64)
+            .add_highlight(self.namespace, color, row as i64, 0, method.len() as i64)
             .await?;
+
         Ok(())
     }
 
-    async fn handle_new_response(&mut self, entry: usize, status: u16) -> eyre::Result<()> {
+    async fn mark_status(&self, row: usize, status: u16) -> eyre::Result<()> {
         let color: Value = color_status(status).into();
         self.list
             .set_extmark(
                 self.namespace,
-                entry as i64,
+                row as i64,
                 -1,
                 vec![
                     (
@@ -199,7 +528,12 @@ impl View {
         Ok(())
     }
 
-    async fn handle_detail(&mut self, req: Vec<String>, res: Vec<String>) -> eyre::Result<()> {
+    async fn handle_detail(
+        &mut self,
+        entry: usize,
+        req: Vec<String>,
+        res: Vec<String>,
+    ) -> eyre::Result<()> {
         let pad = 4;
 
         self.req_detail.set_lines(0, -1, false, req).await?;
@@ -284,11 +618,42 @@ impl View {
 
         self.req_win = Some(req_win);
         self.res_win = Some(res_win);
+        self.detail = Some(entry);
+
+        Ok(())
+    }
+
+    async fn handle_tail(&mut self, entry: usize, text: String) -> eyre::Result<()> {
+        if self.detail != Some(entry) {
+            return Ok(());
+        }
+
+        let mut last = self
+            .res_detail
+            .get_lines(-2, -1, false)
+            .await?
+            .pop()
+            .unwrap_or_default();
+
+        last.push_str(&text);
+
+        let lines: Vec<String> = last.split('\n').map(ToString::to_string).collect();
+        self.res_detail.set_lines(-2, -1, false, lines).await?;
+
+        if let Some(win) = &self.res_win {
+            let count = self.res_detail.line_count().await?;
+            win.set_cursor((count, 0)).await?;
+        }
 
         Ok(())
     }
 
     async fn handle_intercept(&mut self, title: String, content: Vec<String>) -> eyre::Result<()> {
+        // closing the window ourselves should not submit what it showed
+        self.neovim
+            .clear_autocmds(vec![("group".into(), self.intercept_group.into())])
+            .await?;
+
         if let Some(s) = &self.intercept_win {
             if s.is_valid().await? {
                 s.close(true).await?;
@@ -304,10 +669,6 @@ impl View {
 
         self.intercept.set_lines(0, -1, true, content).await?;
 
-        self.neovim
-            .clear_autocmds(vec![("group".into(), self.intercept_group.into())])
-            .await?;
-
         let win = self
             .neovim
             .open_win(
@@ -346,6 +707,8 @@ impl View {
     }
 
     async fn handle_dismiss_detail(&mut self) -> eyre::Result<()> {
+        self.detail = None;
+
         if let Some(win) = self.req_win.take() {
             let _ = win.close(true).await;
         }
@@ -357,7 +720,186 @@ impl View {
         Ok(())
     }
 
+    async fn handle_report(&mut self, title: String, lines: Vec<String>) -> eyre::Result<()> {
+        const ERROR: i64 = 4; // vim.log.levels.ERROR
+
+        let msg = format!("{title}\n{}", lines.join("\n"));
+        self.neovim.notify(&msg, ERROR, vec![]).await?;
+
+        Ok(())
+    }
+
+    async fn handle_notice(&mut self, message: String) -> eyre::Result<()> {
+        const INFO: i64 = 2; // vim.log.levels.INFO
+
+        self.neovim.notify(&message, INFO, vec![]).await?;
+
+        Ok(())
+    }
+
+    async fn handle_queue(&mut self, lines: Vec<String>) -> eyre::Result<()> {
+        self.queue.set_lines(0, -1, false, lines).await?;
+
+        Ok(())
+    }
+
+    async fn handle_show_queue(&mut self) -> eyre::Result<()> {
+        if let Some(win) = &self.queue_win {
+            if win.is_valid().await? {
+                self.neovim.set_current_win(win).await?;
+                return Ok(());
+            }
+        }
+
+        self.neovim.command("botright split").await?;
+
+        let win = self.neovim.get_current_win().await?;
+        win.set_buf(&self.queue).await?;
+        win.set_height(10).await?;
+
+        self.queue_win = Some(win);
+
+        Ok(())
+    }
+
+    async fn handle_show_sitemap(&mut self) -> eyre::Result<()> {
+        self.draw_sitemap().await?;
+
+        if let Some(win) = &self.sitemap_win {
+            if win.is_valid().await? {
+                self.neovim.set_current_win(win).await?;
+                return Ok(());
+            }
+        }
+
+        self.neovim.command("topleft vsplit").await?;
+
+        let win = self.neovim.get_current_win().await?;
+        win.set_buf(&self.sitemap).await?;
+        win.set_width(50).await?;
+
+        self.sitemap_win = Some(win);
+
+        Ok(())
+    }
+
+    async fn handle_fold(&mut self, path: Vec<String>) -> eyre::Result<()> {
+        if self.site.toggle(&path) {
+            self.draw_sitemap().await?;
+        }
+
+        Ok(())
+    }
+
+    /// keeps an open site map current, a closed one is drawn when shown again
+    async fn redraw_sitemap(&mut self) -> eyre::Result<()> {
+        let Some(win) = &self.sitemap_win else {
+            return Ok(());
+        };
+
+        if win.is_valid().await? {
+            self.draw_sitemap().await?;
+        }
+
+        Ok(())
+    }
+
+    async fn draw_sitemap(&mut self) -> eyre::Result<()> {
+        let (lines, nodes) = self
+            .site
+            .rows()
+            .into_iter()
+            .map(|row| (row.text, row.node))
+            .unzip();
+
+        self.sitemap.set_lines(0, -1, false, lines).await?;
+        self.site_rows = nodes;
+
+        Ok(())
+    }
+
+    async fn handle_mark(&mut self, entry: usize) -> eyre::Result<()> {
+        if let Some(pos) = self.marks.iter().position(|marked| *marked == entry) {
+            self.marks.remove(pos);
+        } else {
+            if self.marks.len() == 2 {
+                self.marks.remove(0);
+            }
+
+            self.marks.push(entry);
+        }
+
+        self.draw_marks().await
+    }
+
+    async fn draw_marks(&mut self) -> eyre::Result<()> {
+        self.list
+            .clear_namespace(self.marks_namespace, 0, -1)
+            .await?;
+
+        for (n, entry) in self.marks.iter().enumerate() {
+            let Some(row) = self.row_of(*entry) else {
+                continue;
+            };
+
+            let sign = if n == 0 { "A" } else { "B" };
+
+            self.list
+                .set_extmark(
+                    self.marks_namespace,
+                    row as i64,
+                    0,
+                    vec![("sign_text".into(), sign.into())],
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_diff(
+        &mut self,
+        left: Compared,
+        right: Compared,
+        changes: Vec<String>,
+    ) -> eyre::Result<()> {
+        for win in std::mem::take(&mut self.diff_wins) {
+            if win.is_valid().await? {
+                win.close(true).await?;
+            }
+        }
+
+        self.diff_left.set_lines(0, -1, false, left.lines).await?;
+        self.diff_right.set_lines(0, -1, false, right.lines).await?;
+
+        self.neovim.command("tab split").await?;
+        let left_win = self.neovim.get_current_win().await?;
+        left_win.set_buf(&self.diff_left).await?;
+        left_win.set_option("winbar", left.title.into()).await?;
+
+        self.neovim.command("rightbelow vsplit").await?;
+        let right_win = self.neovim.get_current_win().await?;
+        right_win.set_buf(&self.diff_right).await?;
+        right_win.set_option("winbar", right.title.into()).await?;
+
+        self.neovim.command("windo diffthis").await?;
+
+        self.diff_wins = vec![left_win, right_win];
+
+        let message = if changes.is_empty() {
+            "prax: no differences".to_string()
+        } else {
+            changes.join("\n")
+        };
+
+        self.handle_notice(message).await
+    }
+
     async fn handle_dismiss_intercept(&mut self) -> eyre::Result<()> {
+        self.neovim
+            .clear_autocmds(vec![("group".into(), self.intercept_group.into())])
+            .await?;
+
         if let Some(win) = self.intercept_win.take() {
             let _ = win.close(true).await;
         }
@@ -379,6 +921,10 @@ impl View {
             close |= close || buf == self.req_detail;
             close |= close || buf == self.res_detail;
             close |= close || buf == self.intercept;
+            close |= close || buf == self.queue;
+            close |= close || buf == self.sitemap;
+            close |= close || buf == self.diff_left;
+            close |= close || buf == self.diff_right;
 
             if close {
                 tracing::debug!("closing window");
@@ -398,6 +944,18 @@ impl View {
         self.intercept
             .delete(vec![("force".into(), true.into())])
             .await?;
+        self.queue
+            .delete(vec![("force".into(), true.into())])
+            .await?;
+        self.sitemap
+            .delete(vec![("force".into(), true.into())])
+            .await?;
+        self.diff_left
+            .delete(vec![("force".into(), true.into())])
+            .await?;
+        self.diff_right
+            .delete(vec![("force".into(), true.into())])
+            .await?;
 
         tracing::debug!("looking for windows to close");
 
@@ -421,19 +979,85 @@ pub enum ViewOp {
     },
 
     Detail {
+        entry: usize,
         req: Vec<String>,
         res: Vec<String>,
     },
 
+    Tail {
+        entry: usize,
+        text: String,
+    },
+
     Intercept {
         title: String,
         content: Vec<String>,
     },
 
+    /// problems to show the user (config failures)
+    Report {
+        title: String,
+        lines: Vec<String>,
+    },
+
+    /// something the user asked for took effect
+    Notice {
+        message: String,
+    },
+
+    /// what is waiting to be intercepted
+    Queue {
+        lines: Vec<String>,
+    },
+
+    ShowQueue,
+
+    /// the history list missed updates, redraw it from the history
+    Resync,
+
+    /// narrow the history list down, or list everything again
+    Filter {
+        query: Option<Query>,
+    },
+
+    /// open the site map, or jump to it
+    ShowSiteMap,
+
+    /// expand or collapse a site map branch
+    Fold {
+        path: Vec<String>,
+    },
+
+    /// mark an entry to compare, or unmark it
+    Mark {
+        entry: usize,
+    },
+
+    /// two entries side by side in diff mode, with their structured differences
+    Diff {
+        left: Compared,
+        right: Compared,
+        changes: Vec<String>,
+    },
+
     DismissDetail,
     DismissIntercept,
 }
 
+/// One side of a diff, the request followed by its response
+#[derive(Debug)]
+pub struct Compared {
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+/// whether the recorded `entry` is wanted by `query`
+fn matches(history: &Hist, query: &Query, entry: usize) -> bool {
+    history
+        .entry(entry)
+        .is_some_and(|ent| query.matches(&ent, history.time(entry)))
+}
+
 fn color_method(method: &str) -> &'static str {
     match method.to_uppercase().as_str() {
         "GET" => "PraxMethodGET",
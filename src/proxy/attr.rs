@@ -1,6 +1,6 @@
 use std::str::FromStr;
 
-use http::{uri::PathAndQuery, HeaderName, HeaderValue, Uri};
+use http::{uri::PathAndQuery, HeaderName, HeaderValue, StatusCode, Uri};
 use hyper::Method;
 
 use super::Attr;
@@ -16,18 +16,27 @@ pub enum AttrError {
     #[error("{0}")]
     Method(#[from] http::method::InvalidMethod),
 
-    #[error("")]
+    #[error("{0}")]
     Uri(#[from] http::uri::InvalidUri),
 
+    #[error("{0}")]
+    UriParts(#[from] http::uri::InvalidUriParts),
+
     #[error("{0}")]
     HeaderName(#[from] http::header::InvalidHeaderName),
 
     #[error("{0}")]
     HeaderValue(#[from] http::header::InvalidHeaderValue),
+
+    #[error("{0}")]
+    Status(#[from] http::status::InvalidStatusCode),
 }
 
 pub trait Attributable {
     fn set(&mut self, attr: &Attr, value: Vec<u8>) -> Result<(), AttrError>;
+
+    /// the current value of an attribute, `None` if it is missing or does not apply
+    fn get(&self, attr: &Attr) -> Option<Vec<u8>>;
 }
 
 impl Attributable for hyper::Request<Vec<u8>> {
@@ -55,7 +64,7 @@ impl Attributable for hyper::Request<Vec<u8>> {
 
                 parts.path_and_query = Some(pq);
 
-                *self.uri_mut() = Uri::from_parts(parts).unwrap();
+                *self.uri_mut() = Uri::from_parts(parts)?;
             }
             Attr::Query(key) => {
                 let value = String::from_utf8(value)?;
@@ -83,7 +92,7 @@ impl Attributable for hyper::Request<Vec<u8>> {
 
                 parts.path_and_query = Some(pq);
 
-                *self.uri_mut() = Uri::from_parts(parts).unwrap();
+                *self.uri_mut() = Uri::from_parts(parts)?;
             }
             Attr::Header(key) => {
                 let header = HeaderValue::from_bytes(&value)?;
@@ -98,4 +107,57 @@ impl Attributable for hyper::Request<Vec<u8>> {
 
         Ok(())
     }
+
+    fn get(&self, attr: &Attr) -> Option<Vec<u8>> {
+        match attr {
+            Attr::Method => Some(self.method().as_str().as_bytes().to_vec()),
+            Attr::Status => None,
+            Attr::Path => Some(self.uri().path().as_bytes().to_vec()),
+            Attr::Query(key) => self.uri().query()?.split('&').find_map(|kv| {
+                let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+                (k == key).then(|| v.as_bytes().to_vec())
+            }),
+            Attr::Header(key) => self
+                .headers()
+                .get(key.as_str())
+                .map(|v| v.as_bytes().to_vec()),
+            Attr::Body => Some(self.body().clone()),
+        }
+    }
+}
+
+impl Attributable for hyper::Response<Vec<u8>> {
+    fn set(&mut self, attr: &Attr, value: Vec<u8>) -> Result<(), AttrError> {
+        match attr {
+            Attr::Method | Attr::Path | Attr::Query(_) => {}
+            Attr::Status => {
+                let status = StatusCode::from_bytes(&value)?;
+
+                *self.status_mut() = status;
+            }
+            Attr::Header(key) => {
+                let header = HeaderValue::from_bytes(&value)?;
+
+                self.headers_mut()
+                    .insert(HeaderName::from_bytes(key.as_bytes())?, header);
+            }
+            Attr::Body => {
+                *self.body_mut() = value;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get(&self, attr: &Attr) -> Option<Vec<u8>> {
+        match attr {
+            Attr::Method | Attr::Path | Attr::Query(_) => None,
+            Attr::Status => Some(self.status().as_str().as_bytes().to_vec()),
+            Attr::Header(key) => self
+                .headers()
+                .get(key.as_str())
+                .map(|v| v.as_bytes().to_vec()),
+            Attr::Body => Some(self.body().clone()),
+        }
+    }
 }
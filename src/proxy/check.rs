@@ -0,0 +1,268 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use http::uri::Authority;
+use http::HeaderName;
+use mlua::Lua;
+
+use super::{attr::Attributable, Attr, Rule, Value};
+
+/// A problem with a config, pointing at the line of the script responsible
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub source: String,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// Which half of an exchange rules are attached to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Request,
+    Response,
+}
+
+impl Diagnostic {
+    /// points at the lua code currently calling into prax
+    pub fn at(lua: &Lua, message: String) -> Diagnostic {
+        let mut level = 1;
+
+        while let Some(frame) = lua.inspect_stack(level) {
+            level += 1;
+
+            let source = frame.source();
+            let line = frame.curr_line();
+
+            let (Some(chunk), Some(short)) = (source.source, source.short_src) else {
+                continue;
+            };
+
+            // configs are loaded as named chunks, unlike mlua's own glue
+            let named = chunk.starts_with('@') || chunk.starts_with('=');
+            if line <= 0 || source.what == "C" || !named {
+                continue;
+            }
+
+            return Diagnostic {
+                source: short.to_string(),
+                line: Some(line as usize),
+                message,
+            };
+        }
+
+        Diagnostic {
+            source: "prax-config".to_string(),
+            line: None,
+            message,
+        }
+    }
+
+    /// describes why `path` failed to load
+    pub fn from_report(path: &Path, report: &eyre::Report) -> Diagnostic {
+        match report.downcast_ref::<mlua::Error>() {
+            Some(err) => Diagnostic::from_lua(path, err),
+            None => Diagnostic {
+                source: path.display().to_string(),
+                line: None,
+                message: report.to_string(),
+            },
+        }
+    }
+
+    /// describes a lua error, pointing at the script line that raised it
+    pub fn from_lua(path: &Path, err: &mlua::Error) -> Diagnostic {
+        let mut cause = err;
+        let mut frame = None;
+
+        while let mlua::Error::CallbackError {
+            traceback,
+            cause: inner,
+        } = cause
+        {
+            frame = frame.or_else(|| {
+                traceback
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.starts_with("[C]"))
+                    .find_map(located)
+            });
+
+            cause = inner;
+        }
+
+        let message = match cause {
+            mlua::Error::SyntaxError { message, .. } => message.clone(),
+            mlua::Error::RuntimeError(message) => message.clone(),
+            cause => cause.to_string(),
+        };
+
+        if let Some((source, line, message)) = located(&message) {
+            return Diagnostic {
+                source,
+                line: Some(line),
+                message,
+            };
+        }
+
+        match frame {
+            Some((source, line, _)) => Diagnostic {
+                source,
+                line: Some(line),
+                message,
+            },
+
+            None => Diagnostic {
+                source: path.display().to_string(),
+                line: None,
+                message,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.source, line, self.message),
+            None => write!(f, "{}: {}", self.source, self.message),
+        }
+    }
+}
+
+impl std::fmt::Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Side::Request => write!(f, "req()"),
+            Side::Response => write!(f, "resp()"),
+        }
+    }
+}
+
+/// splits lua's `source:line: message` format
+fn located(text: &str) -> Option<(String, usize, String)> {
+    let mut rest = text;
+    let mut offset = 0;
+
+    while let Some(colon) = rest.find(':') {
+        let after = &rest[colon + 1..];
+        let digits = after.bytes().take_while(u8::is_ascii_digit).count();
+
+        if digits > 0 && after[digits..].starts_with(':') {
+            let source = &text[..offset + colon];
+            let line = after[..digits].parse().ok()?;
+            let message = after[digits + 1..].trim();
+
+            if !source.is_empty() {
+                return Some((source.to_string(), line, message.to_string()));
+            }
+        }
+
+        offset += colon + 1;
+        rest = after;
+    }
+
+    None
+}
+
+/// why a target can never match
+pub fn target(hostname: &str) -> Option<String> {
+    match Authority::from_str(hostname) {
+        Ok(_) => None,
+        Err(e) => Some(format!("target \"{hostname}\" is not a host: {e}")),
+    }
+}
+
+/// why a rule can never apply on a side of the exchange, configs doing so are rejected
+pub fn never_applies(rule: &Rule, side: Side) -> Option<String> {
+    match rule {
+        Rule::Intercept
+        | Rule::Dump
+        | Rule::Hook(_)
+        | Rule::Delay(..)
+        | Rule::Throttle(_)
+        | Rule::Drop
+        | Rule::Fail(..) => None,
+
+        Rule::Redirect(_) => match side {
+            Side::Request => None,
+            Side::Response => Some("redirect only applies in req()".to_string()),
+        },
+
+        Rule::Set(attr, _) | Rule::Capture(attr, _) | Rule::Subst(attr, _) => {
+            attr_applies(attr, side)
+        }
+    }
+}
+
+/// why an applicable rule would still fail each time it runs
+pub fn rule(rule: &Rule, side: Side) -> Option<String> {
+    match rule {
+        Rule::Redirect(host) => target(host).map(|e| format!("redirect to {e}")),
+
+        Rule::Set(attr, Value::Literal(value)) => {
+            let bytes = value.clone().into_bytes();
+            let invalid = match side {
+                Side::Request => hyper::Request::new(Vec::new()).set(attr, bytes),
+                Side::Response => hyper::Response::new(Vec::new()).set(attr, bytes),
+            };
+
+            invalid
+                .err()
+                .map(|e| format!("can not set {} to \"{value}\": {e}", name(attr)))
+        }
+
+        _ => None,
+    }
+}
+
+fn attr_applies(attr: &Attr, side: Side) -> Option<String> {
+    let applies = match attr {
+        Attr::Status => side == Side::Response,
+        Attr::Method | Attr::Path | Attr::Query(_) => side == Side::Request,
+        Attr::Header(key) => {
+            if let Err(e) = HeaderName::from_str(key) {
+                return Some(format!("invalid header name \"{key}\": {e}"));
+            }
+
+            true
+        }
+        Attr::Body => true,
+    };
+
+    if applies {
+        None
+    } else {
+        Some(format!("{} does not apply in {side}", name(attr)))
+    }
+}
+
+fn name(attr: &Attr) -> String {
+    match attr {
+        Attr::Method => "method".to_string(),
+        Attr::Status => "status".to_string(),
+        Attr::Path => "path".to_string(),
+        Attr::Query(key) => format!("query(\"{key}\")"),
+        Attr::Header(key) => format!("header(\"{key}\")"),
+        Attr::Body => "body".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn location() {
+        assert_eq!(
+            located("config.lua:3: unexpected symbol"),
+            Some(("config.lua".into(), 3, "unexpected symbol".into()))
+        );
+
+        assert_eq!(
+            located("/tmp/a:b/config.lua:12: in main chunk"),
+            Some(("/tmp/a:b/config.lua".into(), 12, "in main chunk".into()))
+        );
+
+        assert_eq!(located("invalid target"), None);
+    }
+}
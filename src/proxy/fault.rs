@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use hyper::{header, StatusCode};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::{Fault, Faults, Res};
+
+/// waits somewhere between `min` and `max`, returning how long it waited
+pub async fn delay(min: Duration, max: Duration) -> Duration {
+    let spread = max.saturating_sub(min).as_millis() as u64;
+    let delay = min + Duration::from_millis(random() % (spread + 1));
+
+    tokio::time::sleep(delay).await;
+
+    delay
+}
+
+/// a made up response with `status`, as often as `probability` says
+pub fn fail(status: u16, probability: f64, earlier: Vec<Fault>) -> Option<Res<Vec<u8>>> {
+    let roll = random() as f64 / u64::MAX as f64;
+    if roll >= probability {
+        return None;
+    }
+
+    let body = format!("prax: simulated failure ({status})").into_bytes();
+    let length = body.len();
+
+    let mut res = Res::new(body);
+    *res.status_mut() = StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY);
+    res.headers_mut()
+        .insert(header::CONTENT_TYPE, "text/plain".parse().ok()?);
+    res.headers_mut()
+        .insert(header::CONTENT_LENGTH, length.into());
+
+    let mut faults = Faults(earlier);
+    faults.0.push(Fault::Failed(status));
+    res.extensions_mut().insert(faults);
+
+    Some(res)
+}
+
+fn random() -> u64 {
+    let mut bytes = [0u8; 8];
+    if SystemRandom::new().fill(&mut bytes).is_err() {
+        tracing::error!("system randomness unavailable");
+    }
+
+    u64::from_le_bytes(bytes)
+}
@@ -4,9 +4,9 @@ use hyper::{
     Method, StatusCode, Uri,
 };
 
-use crate::{proxy::query::Query, Filter, Result};
+use crate::{proxy::query::Query, Fault, Faults, Filter, Origin, Outcome, Result};
 
-use super::{Attr, Config, Rule};
+use super::{attr::Attributable, fault, hook::Message, interp::Val, Attr, Config, Rule};
 
 use std::{io::Write, str::FromStr};
 
@@ -18,10 +18,10 @@ where
         &self,
         hostname: &mut String,
         req: &mut crate::Req<Vec<u8>>,
-    ) -> Result<()> {
+    ) -> Result<Outcome> {
         tracing::debug!("applying config request rules to {hostname}");
-        let Some(target) = &self.proxy.targets.iter().find(|t| t.hostname == *hostname) else {
-            return Ok(());
+        let Some(target) = self.target(hostname) else {
+            return Ok(Outcome::Continue);
         };
 
         for rule in &target.req {
@@ -45,62 +45,103 @@ where
                     }
                 }
 
-                Rule::Intercept => self.intercept.modify_request(hostname, req).await?,
+                Rule::Intercept => match self.intercept.modify_request(hostname, req).await? {
+                    Outcome::Continue => (),
+                    outcome => return Ok(outcome),
+                },
 
-                Rule::Set(attr, value) => match attr {
-                    super::Attr::Method => {
-                        let update = Method::from_str(value.as_str())?;
+                Rule::Set(attr, value) => {
+                    let Some(value) = value.resolve(self.interp.state()) else {
+                        tracing::debug!("skipping {rule:?}, nothing captured yet");
+                        continue;
+                    };
+                    let value = &value;
+
+                    match attr {
+                        Attr::Method => {
+                            *req.method_mut() = match Method::from_str(value.as_str()) {
+                                Ok(method) => method,
+                                Err(e) => {
+                                    tracing::error!("{e}");
+                                    continue;
+                                }
+                            };
+                        }
+                        Attr::Status => {}
+                        Attr::Path => {
+                            let mut parts = req.uri().clone().into_parts();
+                            let pq = match parts.path_and_query.as_ref().and_then(|pq| pq.query()) {
+                                Some(query) => PathAndQuery::from_str(&format!("{value}?{query}")),
+                                None => PathAndQuery::from_str(value),
+                            };
+
+                            parts.path_and_query = match pq {
+                                Ok(pq) => Some(pq),
+                                Err(e) => {
+                                    tracing::error!("{e}");
+                                    continue;
+                                }
+                            };
 
-                        *req.method_mut() = update;
-                    }
-                    Attr::Status => {}
-                    Attr::Path => {
-                        let mut parts = req.uri().clone().into_parts();
-                        let pq = if let Some(pq) = parts.path_and_query {
-                            if let Some(query) = pq.query() {
-                                PathAndQuery::from_str(&format!("{}?{}", value, query))?
+                            *req.uri_mut() = match Uri::from_parts(parts) {
+                                Ok(uri) => uri,
+                                Err(e) => {
+                                    tracing::error!("{e}");
+                                    continue;
+                                }
+                            };
+                        }
+                        Attr::Query(key) => {
+                            let val = if value.is_empty() {
+                                "".to_string()
                             } else {
-                                PathAndQuery::from_str(value)?
-                            }
-                        } else {
-                            PathAndQuery::from_str(value)?
-                        };
-
-                        parts.path_and_query = Some(pq);
+                                format!("={value}")
+                            };
 
-                        *req.uri_mut() = Uri::from_parts(parts).unwrap();
-                    }
-                    Attr::Query(key) => {
-                        let val = if value.is_empty() {
-                            "".to_string()
-                        } else {
-                            format!("={value}")
-                        };
+                            let mut parts = req.uri().clone().into_parts();
+                            let pq = if let Some(pq) = parts.path_and_query {
+                                let mut query = Query::from(&pq);
+                                query.push(key, Some(value));
 
-                        let mut parts = req.uri().clone().into_parts();
-                        let pq = if let Some(pq) = parts.path_and_query {
-                            let mut query = Query::from(&pq);
-                            query.push(key, Some(value));
-
-                            query.to_path_and_query(pq.path())?
-                        } else {
-                            PathAndQuery::from_str(&format!("/?{key}{val}"))?
-                        };
+                                query.to_path_and_query(pq.path())
+                            } else {
+                                PathAndQuery::from_str(&format!("/?{key}{val}"))
+                            };
+
+                            parts.path_and_query = match pq {
+                                Ok(pq) => Some(pq),
+                                Err(e) => {
+                                    tracing::error!("{e}");
+                                    continue;
+                                }
+                            };
 
-                        parts.path_and_query = Some(pq);
+                            *req.uri_mut() = match Uri::from_parts(parts) {
+                                Ok(uri) => uri,
+                                Err(e) => {
+                                    tracing::error!("{e}");
+                                    continue;
+                                }
+                            };
+                        }
+                        Attr::Header(key) => {
+                            let name = HeaderName::from_bytes(key.as_bytes());
 
-                        *req.uri_mut() = Uri::from_parts(parts).unwrap();
-                    }
-                    Attr::Header(key) => {
-                        if let Ok(header) = HeaderValue::from_str(value) {
-                            req.headers_mut()
-                                .insert(HeaderName::from_bytes(key.as_bytes()).unwrap(), header);
+                            if let (Ok(name), Ok(header)) = (name, HeaderValue::from_str(value)) {
+                                req.headers_mut().insert(name, header);
+                            }
+                        }
+                        Attr::Body => {
+                            *req.body_mut() = value.as_bytes().to_owned();
                         }
                     }
-                    Attr::Body => {
-                        *req.body_mut() = value.as_bytes().to_owned();
+                }
+
+                Rule::Capture(attr, name) => {
+                    if let Some(value) = Attributable::get(&*req, attr) {
+                        self.interp.state().set(name.clone(), Val::from(value));
                     }
-                },
+                }
 
                 Rule::Subst(attr, sub) => match attr {
                     Attr::Method => {
@@ -146,7 +187,13 @@ where
 
                         parts.path_and_query = Some(pq);
 
-                        *req.uri_mut() = Uri::from_parts(parts).unwrap();
+                        *req.uri_mut() = match Uri::from_parts(parts) {
+                            Ok(uri) => uri,
+                            Err(e) => {
+                                tracing::error!("{e}");
+                                continue;
+                            }
+                        };
                     }
                     Attr::Query(q) => {
                         if let Some(query) = req.uri().query() {
@@ -185,7 +232,13 @@ where
                                     }
                                 };
 
-                                *req.uri_mut() = Uri::from_parts(parts).unwrap();
+                                *req.uri_mut() = match Uri::from_parts(parts) {
+                                    Ok(uri) => uri,
+                                    Err(e) => {
+                                        tracing::error!("{e}");
+                                        continue;
+                                    }
+                                };
                             }
                         }
                     }
@@ -219,7 +272,9 @@ where
                         }
                     }
                     Attr::Body => {
-                        let body = match std::str::from_utf8(req.body()) {
+                        let body = req.body().clone();
+
+                        let res = match sub.subst_bytes(&self.interp, body).await {
                             Ok(s) => s,
                             Err(e) => {
                                 tracing::error!("{}", e);
@@ -227,37 +282,78 @@ where
                             }
                         };
 
-                        let res = match sub.subst(&self.interp, body.to_string()).await {
-                            Ok(s) => s,
+                        *req.body_mut() = res;
+                    }
+                },
+
+                Rule::Redirect(host) => {
+                    tracing::debug!(from = %hostname, to = %host);
+                    *hostname = host.to_string()
+                }
+
+                Rule::Delay(min, max) => {
+                    let delay = fault::delay(*min, *max).await;
+                    Faults::record(req.extensions_mut(), Fault::Delayed(delay));
+                }
+
+                Rule::Throttle(rate) => {
+                    Faults::record(req.extensions_mut(), Fault::Throttled(*rate));
+                }
+
+                Rule::Drop => {
+                    Faults::record(req.extensions_mut(), Fault::Dropped);
+                    return Ok(Outcome::Drop);
+                }
+
+                Rule::Fail(status, probability) => {
+                    let earlier = req
+                        .extensions()
+                        .get::<Faults>()
+                        .map(|Faults(faults)| faults.clone())
+                        .unwrap_or_default();
+
+                    if let Some(res) = fault::fail(*status, *probability, earlier) {
+                        return Ok(Outcome::Respond(res));
+                    }
+                }
+
+                Rule::Hook(func) => {
+                    let before = Message::request(req);
+
+                    let (after, _, outcome) =
+                        match self.interp.hook(*func, before.clone(), None).await {
+                            Ok(res) => res,
                             Err(e) => {
                                 tracing::error!("{}", e);
                                 continue;
                             }
                         };
 
-                        *req.body_mut() = res.as_bytes().to_vec();
+                    if let Err(e) = after.apply_request(&before, req) {
+                        tracing::error!("{}", e);
                     }
-                },
 
-                Rule::Redirect(host) => {
-                    tracing::debug!(from = %hostname, to = %host);
-                    *hostname = host.to_string()
+                    match outcome.into_outcome() {
+                        Ok(Outcome::Continue) => (),
+                        Ok(outcome) => return Ok(outcome),
+                        Err(e) => tracing::error!("{}", e),
+                    }
                 }
             }
         }
 
         tracing::trace!("finished applying config request rules to {hostname}");
-        Ok(())
+        Ok(Outcome::Continue)
     }
 
     async fn modify_response(
         &self,
         hostname: &mut String,
         res: &mut crate::Res<Vec<u8>>,
-    ) -> Result<()> {
+    ) -> Result<Outcome> {
         tracing::debug!("applying response rules to {hostname}");
-        let Some(target) = self.proxy.targets.iter().find(|t| t.hostname == *hostname) else {
-            return Ok(());
+        let Some(target) = self.target(hostname) else {
+            return Ok(Outcome::Continue);
         };
 
         for rule in &target.resp {
@@ -285,26 +381,49 @@ where
                     }
                 }
 
-                Rule::Intercept => self.intercept.modify_response(hostname, res).await?,
+                Rule::Intercept => match self.intercept.modify_response(hostname, res).await? {
+                    Outcome::Continue => (),
+                    outcome => return Ok(outcome),
+                },
 
-                Rule::Set(attr, value) => match attr {
-                    Attr::Method => {}
-                    Attr::Path => {}
-                    Attr::Query(_) => {}
-                    Attr::Status => {
-                        let status = StatusCode::from_str(value).unwrap();
-                        *res.status_mut() = status;
-                    }
-                    Attr::Header(key) => {
-                        if let Ok(header) = HeaderValue::from_str(value) {
-                            res.headers_mut()
-                                .insert(HeaderName::from_bytes(key.as_bytes()).unwrap(), header);
+                Rule::Set(attr, value) => {
+                    let Some(value) = value.resolve(self.interp.state()) else {
+                        tracing::debug!("skipping {rule:?}, nothing captured yet");
+                        continue;
+                    };
+                    let value = &value;
+
+                    match attr {
+                        Attr::Method => {}
+                        Attr::Path => {}
+                        Attr::Query(_) => {}
+                        Attr::Status => {
+                            *res.status_mut() = match StatusCode::from_str(value) {
+                                Ok(status) => status,
+                                Err(e) => {
+                                    tracing::error!("{e}");
+                                    continue;
+                                }
+                            };
+                        }
+                        Attr::Header(key) => {
+                            let name = HeaderName::from_bytes(key.as_bytes());
+
+                            if let (Ok(name), Ok(header)) = (name, HeaderValue::from_str(value)) {
+                                res.headers_mut().insert(name, header);
+                            }
+                        }
+                        Attr::Body => {
+                            *res.body_mut() = value.as_bytes().to_owned();
                         }
                     }
-                    Attr::Body => {
-                        *res.body_mut() = value.as_bytes().to_owned();
+                }
+
+                Rule::Capture(attr, name) => {
+                    if let Some(value) = Attributable::get(&*res, attr) {
+                        self.interp.state().set(name.clone(), Val::from(value));
                     }
-                },
+                }
 
                 Rule::Subst(attr, sub) => match attr {
                     Attr::Method => {}
@@ -371,7 +490,9 @@ where
                     }
 
                     Attr::Body => {
-                        let body = match std::str::from_utf8(res.body()) {
+                        let body = res.body().clone();
+
+                        let new = match sub.subst_bytes(&self.interp, body).await {
                             Ok(s) => s,
                             Err(e) => {
                                 tracing::error!("{}", e);
@@ -379,24 +500,69 @@ where
                             }
                         };
 
-                        let new = match sub.subst(&self.interp, body.to_string()).await {
-                            Ok(s) => s,
+                        *res.body_mut() = new;
+                    }
+                },
+
+                Rule::Redirect(_) => {
+                    // cannot change host after request was sent
+                }
+
+                Rule::Delay(min, max) => {
+                    let delay = fault::delay(*min, *max).await;
+                    Faults::record(res.extensions_mut(), Fault::Delayed(delay));
+                }
+
+                Rule::Throttle(rate) => {
+                    Faults::record(res.extensions_mut(), Fault::Throttled(*rate));
+                }
+
+                Rule::Drop => {
+                    Faults::record(res.extensions_mut(), Fault::Dropped);
+                    return Ok(Outcome::Drop);
+                }
+
+                Rule::Fail(status, probability) => {
+                    let earlier = res
+                        .extensions()
+                        .get::<Faults>()
+                        .map(|Faults(faults)| faults.clone())
+                        .unwrap_or_default();
+
+                    if let Some(res) = fault::fail(*status, *probability, earlier) {
+                        return Ok(Outcome::Respond(res));
+                    }
+                }
+
+                Rule::Hook(func) => {
+                    let req = match res.extensions().get::<Origin>() {
+                        Some(Origin(req)) => Message::request(req),
+                        None => Message::default(),
+                    };
+                    let before = Message::response(res);
+
+                    let (_, after, outcome) =
+                        match self.interp.hook(*func, req, Some(before.clone())).await {
+                            Ok(res) => res,
                             Err(e) => {
                                 tracing::error!("{}", e);
                                 continue;
                             }
                         };
 
-                        *res.body_mut() = new.as_bytes().to_vec();
+                    if let Some(Err(e)) = after.map(|after| after.apply_response(&before, res)) {
+                        tracing::error!("{}", e);
                     }
-                },
 
-                Rule::Redirect(_) => {
-                    // cannot change host after request was sent
+                    match outcome.into_outcome() {
+                        Ok(Outcome::Continue) => (),
+                        Ok(outcome) => return Ok(outcome),
+                        Err(e) => tracing::error!("{}", e),
+                    }
                 }
             }
         }
 
-        Ok(())
+        Ok(Outcome::Continue)
     }
 }
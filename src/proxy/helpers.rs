@@ -0,0 +1,399 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use mlua::{Lua, Table};
+use ring::{
+    digest,
+    hmac::{self, Algorithm},
+    rand::{SecureRandom, SystemRandom},
+};
+
+/// json nesting deeper than this is refused rather than recursed into
+const MAX_DEPTH: usize = 64;
+
+/// The `prax` global, encoders and crypto primitives scripts would otherwise shell out for
+///
+/// Binary data goes in and out as lua strings,
+/// hashes and macs are raw bytes so they compose with the encoders.
+pub fn module(lua: &Lua) -> mlua::Result<Table<'_>> {
+    let prax = lua.create_table()?;
+
+    prax.set(
+        "base64",
+        codec(
+            lua,
+            |bytes| Ok(STANDARD.encode(bytes).into_bytes()),
+            |text| {
+                STANDARD
+                    .decode(text)
+                    .map_err(|e| runtime(format!("invalid base64: {e}")))
+            },
+        )?,
+    )?;
+
+    prax.set(
+        "base64url",
+        codec(
+            lua,
+            |bytes| Ok(URL_SAFE_NO_PAD.encode(bytes).into_bytes()),
+            |text| {
+                let text = trim_padding(text);
+                URL_SAFE_NO_PAD
+                    .decode(text)
+                    .map_err(|e| runtime(format!("invalid base64url: {e}")))
+            },
+        )?,
+    )?;
+
+    prax.set("url", codec(lua, |b| Ok(url_encode(b)), url_decode)?)?;
+    prax.set("hex", codec(lua, |b| Ok(hex_encode(b)), hex_decode)?)?;
+
+    prax.set(
+        "sha1",
+        lua.create_function(|lua, data: mlua::String| {
+            let hash = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, data.as_bytes());
+            lua.create_string(hash.as_ref())
+        })?,
+    )?;
+
+    prax.set(
+        "sha256",
+        lua.create_function(|lua, data: mlua::String| {
+            let hash = digest::digest(&digest::SHA256, data.as_bytes());
+            lua.create_string(hash.as_ref())
+        })?,
+    )?;
+
+    prax.set(
+        "hmac",
+        lua.create_function(
+            |lua, (alg, key, data): (String, mlua::String, mlua::String)| {
+                let key = hmac::Key::new(hmac_algorithm(&alg)?, key.as_bytes());
+                lua.create_string(hmac::sign(&key, data.as_bytes()).as_ref())
+            },
+        )?,
+    )?;
+
+    prax.set(
+        "random",
+        lua.create_function(|lua, len: usize| lua.create_string(random(len)?))?,
+    )?;
+
+    prax.set("uuid", lua.create_function(|_, ()| uuid())?)?;
+
+    prax.set(
+        "now",
+        lua.create_function(|_, ()| Ok(since_epoch()?.as_secs()))?,
+    )?;
+
+    prax.set(
+        "millis",
+        lua.create_function(|_, ()| Ok(since_epoch()?.as_millis() as u64))?,
+    )?;
+
+    prax.set("jwt", jwt(lua)?)?;
+
+    Ok(prax)
+}
+
+/// a table with `encode` and `decode` between lua strings
+fn codec<'lua, E, D>(lua: &'lua Lua, encode: E, decode: D) -> mlua::Result<Table<'lua>>
+where
+    E: Fn(&[u8]) -> mlua::Result<Vec<u8>> + 'static,
+    D: Fn(&[u8]) -> mlua::Result<Vec<u8>> + 'static,
+{
+    let table = lua.create_table()?;
+
+    table.set(
+        "encode",
+        lua.create_function(move |lua, data: mlua::String| {
+            lua.create_string(encode(data.as_bytes())?)
+        })?,
+    )?;
+
+    table.set(
+        "decode",
+        lua.create_function(move |lua, data: mlua::String| {
+            lua.create_string(decode(data.as_bytes())?)
+        })?,
+    )?;
+
+    Ok(table)
+}
+
+fn jwt(lua: &Lua) -> mlua::Result<Table<'_>> {
+    let table = lua.create_table()?;
+
+    table.set(
+        "decode",
+        lua.create_function(|lua, (token, secret): (String, Option<mlua::String>)| {
+            let mut parts = token.split('.');
+            let (Some(header), Some(claims), Some(signature), None) =
+                (parts.next(), parts.next(), parts.next(), parts.next())
+            else {
+                return Err(runtime("jwt must have three segments".to_string()));
+            };
+
+            let segment = |part: &str| -> mlua::Result<serde_json::Value> {
+                let bytes = URL_SAFE_NO_PAD
+                    .decode(trim_padding(part.as_bytes()))
+                    .map_err(|e| runtime(format!("invalid jwt segment: {e}")))?;
+
+                serde_json::from_slice(&bytes)
+                    .map_err(|e| runtime(format!("invalid jwt json: {e}")))
+            };
+
+            let head = segment(header)?;
+
+            if let Some(secret) = secret {
+                let alg = head.get("alg").and_then(|alg| alg.as_str()).unwrap_or("");
+                let key = hmac::Key::new(jwt_algorithm(alg)?, secret.as_bytes());
+                let signature = URL_SAFE_NO_PAD
+                    .decode(trim_padding(signature.as_bytes()))
+                    .map_err(|e| runtime(format!("invalid jwt signature: {e}")))?;
+
+                let signed = format!("{header}.{claims}");
+                hmac::verify(&key, signed.as_bytes(), &signature)
+                    .map_err(|_| runtime("jwt signature does not match".to_string()))?;
+            }
+
+            Ok((to_lua(lua, segment(claims)?, 0)?, to_lua(lua, head, 0)?))
+        })?,
+    )?;
+
+    table.set(
+        "encode",
+        lua.create_function(
+            |_, (claims, secret, alg): (mlua::Value, mlua::String, Option<String>)| {
+                let alg = alg.unwrap_or_else(|| "HS256".to_string());
+                let key = hmac::Key::new(jwt_algorithm(&alg)?, secret.as_bytes());
+
+                let header = serde_json::json!({ "alg": alg, "typ": "JWT" });
+                let claims = from_lua(claims, 0)?;
+
+                let signed = format!(
+                    "{}.{}",
+                    URL_SAFE_NO_PAD.encode(header.to_string()),
+                    URL_SAFE_NO_PAD.encode(claims.to_string())
+                );
+
+                let signature = hmac::sign(&key, signed.as_bytes());
+
+                Ok(format!("{signed}.{}", URL_SAFE_NO_PAD.encode(signature)))
+            },
+        )?,
+    )?;
+
+    Ok(table)
+}
+
+fn to_lua(lua: &Lua, value: serde_json::Value, depth: usize) -> mlua::Result<mlua::Value<'_>> {
+    if depth > MAX_DEPTH {
+        return Err(runtime("json nested too deeply".to_string()));
+    }
+
+    let value = match value {
+        serde_json::Value::Null => mlua::Value::Nil,
+        serde_json::Value::Bool(b) => mlua::Value::Boolean(b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => mlua::Value::Integer(i),
+            None => mlua::Value::Number(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(s) => mlua::Value::String(lua.create_string(s)?),
+        serde_json::Value::Array(items) => {
+            let table = lua.create_table()?;
+            for item in items {
+                table.push(to_lua(lua, item, depth + 1)?)?;
+            }
+            mlua::Value::Table(table)
+        }
+        serde_json::Value::Object(fields) => {
+            let table = lua.create_table()?;
+            for (key, field) in fields {
+                table.set(key, to_lua(lua, field, depth + 1)?)?;
+            }
+            mlua::Value::Table(table)
+        }
+    };
+
+    Ok(value)
+}
+
+/// sequences become arrays, any other table an object keyed by strings
+fn from_lua(value: mlua::Value, depth: usize) -> mlua::Result<serde_json::Value> {
+    if depth > MAX_DEPTH {
+        return Err(runtime("table nested too deeply".to_string()));
+    }
+
+    let value = match value {
+        mlua::Value::Nil => serde_json::Value::Null,
+        mlua::Value::Boolean(b) => serde_json::Value::Bool(b),
+        mlua::Value::Integer(i) => serde_json::Value::from(i),
+        mlua::Value::Number(n) => serde_json::Value::from(n),
+        mlua::Value::String(s) => serde_json::Value::String(s.to_str()?.to_string()),
+        mlua::Value::Table(table) => {
+            let len = table.raw_len();
+
+            if len > 0 && table.clone().pairs::<mlua::Value, mlua::Value>().count() == len {
+                let mut items = Vec::with_capacity(len);
+                for item in table.sequence_values::<mlua::Value>() {
+                    items.push(from_lua(item?, depth + 1)?);
+                }
+                serde_json::Value::Array(items)
+            } else {
+                let mut fields = serde_json::Map::new();
+                for pair in table.pairs::<mlua::Value, mlua::Value>() {
+                    let (key, field) = pair?;
+                    let key = match key {
+                        mlua::Value::String(s) => s.to_str()?.to_string(),
+                        mlua::Value::Integer(i) => i.to_string(),
+                        other => {
+                            return Err(runtime(format!(
+                                "json keys must be strings not [{}]",
+                                other.type_name()
+                            )))
+                        }
+                    };
+                    fields.insert(key, from_lua(field, depth + 1)?);
+                }
+                serde_json::Value::Object(fields)
+            }
+        }
+        other => {
+            return Err(runtime(format!(
+                "[{}] can not be encoded as json",
+                other.type_name()
+            )))
+        }
+    };
+
+    Ok(value)
+}
+
+fn hmac_algorithm(name: &str) -> mlua::Result<Algorithm> {
+    match name.to_ascii_lowercase().as_str() {
+        "sha1" => Ok(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY),
+        "sha256" => Ok(hmac::HMAC_SHA256),
+        "sha384" => Ok(hmac::HMAC_SHA384),
+        "sha512" => Ok(hmac::HMAC_SHA512),
+        _ => Err(runtime(format!("unsupported hmac algorithm \"{name}\""))),
+    }
+}
+
+fn jwt_algorithm(name: &str) -> mlua::Result<Algorithm> {
+    match name {
+        "HS256" => Ok(hmac::HMAC_SHA256),
+        "HS384" => Ok(hmac::HMAC_SHA384),
+        "HS512" => Ok(hmac::HMAC_SHA512),
+        _ => Err(runtime(format!("unsupported jwt algorithm \"{name}\""))),
+    }
+}
+
+fn random(len: usize) -> mlua::Result<Vec<u8>> {
+    let mut bytes = vec![0; len];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| runtime("system randomness unavailable".to_string()))?;
+
+    Ok(bytes)
+}
+
+/// a version 4 uuid
+fn uuid() -> mlua::Result<String> {
+    let mut bytes = random(16)?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = String::from_utf8(hex_encode(&bytes)).unwrap_or_default();
+
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    ))
+}
+
+fn since_epoch() -> mlua::Result<std::time::Duration> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| runtime("system clock before the epoch".to_string()))
+}
+
+fn trim_padding(text: &[u8]) -> &[u8] {
+    let end = text.iter().rposition(|b| *b != b'=').map_or(0, |i| i + 1);
+    &text[..end]
+}
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+fn hex_encode(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|b| [HEX[(b >> 4) as usize], HEX[(b & 0xf) as usize]])
+        .collect()
+}
+
+fn hex_decode(text: &[u8]) -> mlua::Result<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return Err(runtime("hex must have an even length".to_string()));
+    }
+
+    text.chunks(2)
+        .map(|pair| Ok(nibble(pair[0])? << 4 | nibble(pair[1])?))
+        .collect()
+}
+
+fn nibble(b: u8) -> mlua::Result<u8> {
+    match b {
+        b'0'..=b'9' => Ok(b - b'0'),
+        b'a'..=b'f' => Ok(b - b'a' + 10),
+        b'A'..=b'F' => Ok(b - b'A' + 10),
+        _ => Err(runtime(format!("invalid hex digit \"{}\"", b as char))),
+    }
+}
+
+/// percent encodes everything outside of the unreserved set
+pub(super) fn url_encode(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+
+    for b in bytes {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(*b),
+            _ => out.extend([b'%', HEX[(b >> 4) as usize], HEX[(b & 0xf) as usize]]),
+        }
+    }
+
+    out
+}
+
+/// percent decodes, `+` is left alone since it only means space in forms
+pub(super) fn url_decode(text: &[u8]) -> mlua::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len());
+    let mut i = 0;
+
+    while i < text.len() {
+        if text[i] == b'%' {
+            let (Some(hi), Some(lo)) = (text.get(i + 1), text.get(i + 2)) else {
+                return Err(runtime("truncated percent encoding".to_string()));
+            };
+
+            out.push(nibble(*hi)? << 4 | nibble(*lo)?);
+            i += 3;
+        } else {
+            out.push(text[i]);
+            i += 1;
+        }
+    }
+
+    Ok(out)
+}
+
+fn runtime(msg: String) -> mlua::Error {
+    mlua::Error::RuntimeError(msg)
+}
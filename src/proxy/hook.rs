@@ -0,0 +1,359 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use hyper::{
+    header::{HeaderName, HeaderValue},
+    http::uri::PathAndQuery,
+    Method, StatusCode, Uri,
+};
+use mlua::{FromLua, IntoLua, Lua, Table};
+
+use crate::{Req, Res, Result};
+
+use super::helpers::{url_decode, url_encode};
+
+/// A request or response as lua hooks see it
+///
+/// Hooks get it as a table they may mutate in place,
+/// only the fields they change are written back to the message.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Message {
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub query: Option<Vec<Param>>,
+    pub status: Option<u16>,
+    pub headers: BTreeMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// A query parameter, percent decoded
+///
+/// Parameters keep their order and repeated names, a bare `name` without `=` has no value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: Vec<u8>,
+    pub value: Option<Vec<u8>>,
+}
+
+/// What a hook decided should happen to the exchange
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Continue,
+    Drop,
+    Respond(Message),
+}
+
+impl Message {
+    pub fn request(req: &Req<Vec<u8>>) -> Message {
+        let query = segments(req.uri().query()).map(Param::parse).collect();
+
+        Message {
+            method: Some(req.method().to_string()),
+            path: Some(req.uri().path().to_string()),
+            query: Some(query),
+            status: None,
+            headers: headers(req.headers()),
+            body: req.body().clone(),
+        }
+    }
+
+    pub fn response(res: &Res<Vec<u8>>) -> Message {
+        Message {
+            method: None,
+            path: None,
+            query: None,
+            status: Some(res.status().as_u16()),
+            headers: headers(res.headers()),
+            body: res.body().clone(),
+        }
+    }
+
+    /// writes what changed since `before` into `req`, leaving it untouched on error
+    pub fn apply_request(&self, before: &Message, req: &mut Req<Vec<u8>>) -> Result<()> {
+        let mut next = req.clone();
+        self.write_request(before, &mut next)?;
+        *req = next;
+
+        Ok(())
+    }
+
+    /// writes what changed since `before` into `res`, leaving it untouched on error
+    pub fn apply_response(&self, before: &Message, res: &mut Res<Vec<u8>>) -> Result<()> {
+        let mut next = res.clone();
+        self.write_response(before, &mut next)?;
+        *res = next;
+
+        Ok(())
+    }
+
+    fn write_request(&self, before: &Message, req: &mut Req<Vec<u8>>) -> Result<()> {
+        if self.method != before.method {
+            if let Some(method) = &self.method {
+                *req.method_mut() = Method::from_str(method)?;
+            }
+        }
+
+        if self.path != before.path || self.query != before.query {
+            let path = self.path.as_deref().unwrap_or("/");
+
+            let pq = match &self.query {
+                Some(query) if !query.is_empty() => {
+                    // parameters the hook left alone keep their original encoding
+                    let raw: Vec<&str> = segments(req.uri().query()).collect();
+                    let unchanged = |i: usize, param: &Param| {
+                        let before = before.query.as_ref()?.get(i)?;
+                        let raw = raw.get(i)?;
+
+                        (before == param).then(|| raw.to_string())
+                    };
+
+                    let query = query
+                        .iter()
+                        .enumerate()
+                        .map(|(i, param)| unchanged(i, param).unwrap_or_else(|| param.encode()))
+                        .collect::<Vec<_>>()
+                        .join("&");
+
+                    PathAndQuery::from_str(&format!("{path}?{query}"))?
+                }
+
+                _ => PathAndQuery::from_str(path)?,
+            };
+
+            let mut parts = req.uri().clone().into_parts();
+            parts.path_and_query = Some(pq);
+            *req.uri_mut() = Uri::from_parts(parts).map_err(hyper::http::Error::from)?;
+        }
+
+        self.apply_headers(before, req.headers_mut())?;
+
+        if self.body != before.body {
+            *req.body_mut() = self.body.clone();
+            self.fit_length(before, req.headers_mut());
+        }
+
+        Ok(())
+    }
+
+    fn write_response(&self, before: &Message, res: &mut Res<Vec<u8>>) -> Result<()> {
+        if self.status != before.status {
+            if let Some(status) = self.status {
+                *res.status_mut() = StatusCode::from_u16(status)?;
+            }
+        }
+
+        self.apply_headers(before, res.headers_mut())?;
+
+        if self.body != before.body {
+            *res.body_mut() = self.body.clone();
+            self.fit_length(before, res.headers_mut());
+        }
+
+        Ok(())
+    }
+
+    /// a response built from scratch, for hooks that answer the request themselves
+    pub fn into_response(self) -> Result<Res<Vec<u8>>> {
+        let before = Message::default();
+        let mut res = Res::new(Vec::new());
+
+        self.write_response(&before, &mut res)?;
+
+        Ok(res)
+    }
+
+    /// keeps a stale content-length from describing the old body
+    fn fit_length(&self, before: &Message, headers: &mut hyper::HeaderMap) {
+        let length = hyper::header::CONTENT_LENGTH;
+        let explicit = self.headers.get(length.as_str()) != before.headers.get(length.as_str());
+
+        if headers.contains_key(&length) && !explicit {
+            headers.insert(length, HeaderValue::from(self.body.len()));
+        }
+    }
+
+    /// only touches headers that changed so repeated headers (set-cookie) survive
+    fn apply_headers(&self, before: &Message, headers: &mut hyper::HeaderMap) -> Result<()> {
+        for key in before.headers.keys() {
+            if !self.headers.contains_key(key) {
+                headers.remove(key.as_str());
+            }
+        }
+
+        for (key, value) in &self.headers {
+            if before.headers.get(key) != Some(value) {
+                headers.insert(
+                    HeaderName::from_bytes(key.as_bytes())?,
+                    HeaderValue::from_str(value)?,
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Param {
+    /// `name=value` as found in a query, undecodable parts are taken as they are
+    fn parse(raw: &str) -> Param {
+        let decode = |s: &str| url_decode(s.as_bytes()).unwrap_or_else(|_| s.as_bytes().to_vec());
+
+        match raw.split_once('=') {
+            Some((name, value)) => Param {
+                name: decode(name),
+                value: Some(decode(value)),
+            },
+            None => Param {
+                name: decode(raw),
+                value: None,
+            },
+        }
+    }
+
+    fn encode(&self) -> String {
+        let name = String::from_utf8_lossy(&url_encode(&self.name)).into_owned();
+
+        match &self.value {
+            Some(value) => format!("{name}={}", String::from_utf8_lossy(&url_encode(value))),
+            None => name,
+        }
+    }
+}
+
+fn segments(query: Option<&str>) -> impl Iterator<Item = &str> {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|kv| !kv.is_empty())
+}
+
+fn headers(map: &hyper::HeaderMap) -> BTreeMap<String, String> {
+    let mut headers = BTreeMap::<String, String>::new();
+
+    for (key, value) in map {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+
+        headers
+            .entry(key.to_string())
+            .and_modify(|v| {
+                v.push_str(", ");
+                v.push_str(value);
+            })
+            .or_insert_with(|| value.to_string());
+    }
+
+    headers
+}
+
+impl<'lua> IntoLua<'lua> for Message {
+    fn into_lua(self, lua: &'lua Lua) -> mlua::Result<mlua::Value<'lua>> {
+        let table = lua.create_table()?;
+
+        table.set("method", self.method)?;
+        table.set("path", self.path)?;
+        table.set("status", self.status)?;
+        if let Some(query) = self.query {
+            let params = lua.create_table()?;
+
+            for param in query {
+                let pair = lua.create_table()?;
+                pair.set("name", lua.create_string(&param.name)?)?;
+                if let Some(value) = param.value {
+                    pair.set("value", lua.create_string(&value)?)?;
+                }
+
+                params.push(pair)?;
+            }
+
+            table.set("query", params)?;
+        }
+        table.set("headers", lua.create_table_from(self.headers)?)?;
+        table.set("body", lua.create_string(&self.body)?)?;
+
+        Ok(mlua::Value::Table(table))
+    }
+}
+
+impl<'lua> FromLua<'lua> for Message {
+    fn from_lua(value: mlua::Value<'lua>, _: &'lua Lua) -> mlua::Result<Self> {
+        let mlua::Value::Table(table) = value else {
+            return Err(mlua::Error::RuntimeError(format!(
+                "expected a message table but got [{}]",
+                value.type_name()
+            )));
+        };
+
+        let map = |key: &str| -> mlua::Result<Option<BTreeMap<String, String>>> {
+            table
+                .get::<_, Option<Table>>(key)?
+                .map(|t| t.pairs::<String, String>().collect())
+                .transpose()
+        };
+
+        let query = table
+            .get::<_, Option<Table>>("query")?
+            .map(|params| {
+                params
+                    .sequence_values::<Table>()
+                    .map(|pair| {
+                        let pair = pair?;
+                        let name: mlua::String = pair.get("name")?;
+                        let value: Option<mlua::String> = pair.get("value")?;
+
+                        Ok(Param {
+                            name: name.as_bytes().to_vec(),
+                            value: value.map(|value| value.as_bytes().to_vec()),
+                        })
+                    })
+                    .collect::<mlua::Result<Vec<_>>>()
+            })
+            .transpose()?;
+
+        let body = table
+            .get::<_, Option<mlua::String>>("body")?
+            .map(|body| body.as_bytes().to_vec())
+            .unwrap_or_default();
+
+        Ok(Message {
+            method: table.get("method")?,
+            path: table.get("path")?,
+            query,
+            status: table.get("status")?,
+            headers: map("headers")?.unwrap_or_default(),
+            body,
+        })
+    }
+}
+
+impl<'lua> FromLua<'lua> for Outcome {
+    fn from_lua(value: mlua::Value<'lua>, lua: &'lua Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::Nil => Ok(Outcome::Continue),
+            mlua::Value::String(s) => match s.to_str()? {
+                "continue" => Ok(Outcome::Continue),
+                "drop" => Ok(Outcome::Drop),
+                other => Err(mlua::Error::RuntimeError(format!(
+                    "hooks may return \"continue\", \"drop\" or a response, not \"{other}\""
+                ))),
+            },
+            mlua::Value::Table(_) => Ok(Outcome::Respond(Message::from_lua(value, lua)?)),
+            _ => Err(mlua::Error::RuntimeError(format!(
+                "invalid hook outcome [{}]",
+                value.type_name()
+            ))),
+        }
+    }
+}
+
+impl Outcome {
+    /// what the proxy should do with the exchange the hook saw
+    pub fn into_outcome(self) -> Result<crate::Outcome> {
+        match self {
+            Outcome::Continue => Ok(crate::Outcome::Continue),
+            Outcome::Drop => Ok(crate::Outcome::Drop),
+            Outcome::Respond(message) => Ok(crate::Outcome::Respond(message.into_response()?)),
+        }
+    }
+}
@@ -1,23 +1,57 @@
-use std::{path::Path, sync::Arc};
-
-use mlua::{AppDataRefMut, FromLua, Function, IntoLua, Lua, UserData, Variadic};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use mlua::{
+    AppDataRefMut, FromLua, Function, IntoLua, IntoLuaMulti, Lua, Table, UserData, Variadic,
+};
 use tokio::sync::mpsc::Sender;
 
-use crate::proxy::Target;
+use crate::{proxy::Target, Identity, Timeouts};
+
+use super::{
+    check::{self, Diagnostic, Side},
+    helpers,
+    hook::{Message, Outcome},
+    state::State,
+    sub::{Program, System},
+    Attr, Func, Proxy, Rule, Subst, Value,
+};
 
-use super::{Attr, Func, Proxy, Rule, Subst};
+enum Input {
+    Value(Val),
+    Hook(Box<(Message, Option<Message>)>),
+}
 
-type Return = Val;
-type Input = Val;
+enum Return {
+    Value(Val),
+    Hook(Box<(Message, Option<Message>, Outcome)>),
+}
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Val {
     Nil,
     Bool(bool),
     Number(i64),
+    Float(f64),
     String(String),
+
+    /// a lua string that is not valid utf-8
+    Bytes(Vec<u8>),
+
+    /// a table with only the keys 1..n
+    Array(Vec<Val>),
+    Map(Vec<(Val, Val)>),
 }
 
+/// tables nested deeper than this are assumed to be cyclic
+const MAX_DEPTH: usize = 64;
+
 type Chan<T> = tokio::sync::oneshot::Sender<T>;
 
 struct Invocation {
@@ -26,68 +60,143 @@ struct Invocation {
     input: Input,
 }
 
+/// A pool of lua states loaded from the same config
+///
+/// Every state runs the whole config, top level side effects happen once per state
+/// and upvalues are not shared, only [`State`] is.
 #[derive(Clone, Default)]
 pub struct Interp {
-    sender: Option<Sender<Invocation>>,
+    workers: Arc<[Worker]>,
+    state: State,
 }
 
+struct Worker {
+    sender: Sender<Invocation>,
+
+    /// invocations sent but not yet answered
+    busy: AtomicUsize,
+
+    /// the lua state died of a panic
+    panicked: Arc<AtomicBool>,
+}
+
+/// marks a worker busy for as long as an invocation is in flight
+struct Busy<'a>(&'a AtomicUsize);
+
+/// flags a worker whose call panicked, before the invocation goes unanswered
+struct Unwinding<'a>(&'a AtomicBool);
+
+type Loaded = tokio::sync::oneshot::Sender<mlua::Result<Proxy>>;
+
 #[derive(Default)]
 struct AppData {
     proxy: Proxy,
     funcs: Vec<Function<'static>>,
+    dir: Option<PathBuf>,
 }
 
 impl Interp {
-    pub fn new(path: &Path, proxy: tokio::sync::oneshot::Sender<mlua::Result<Proxy>>) -> Self {
-        let (tx, rx) = tokio::sync::mpsc::channel::<Invocation>(1);
-
+    pub fn new(path: &Path, states: usize, state: State, proxy: Loaded) -> Self {
         let path = path.to_path_buf();
-        std::thread::spawn(move || {
-            let lua = match Self::load_path(&path) {
-                Ok(l) => l,
 
-                Err(e) => {
-                    let _ = proxy.send(Err(e));
-                    return;
-                }
-            };
+        Self::spawn(states, state, proxy, move |state| {
+            Self::load_path(&path, state)
+        })
+    }
 
-            Self::runloop(lua, rx, proxy);
-        });
+    pub fn test(content: &'static str, states: usize, proxy: Loaded) -> Self {
+        Self::spawn(states, State::default(), proxy, move |state| {
+            Self::load_literal(content.as_bytes().to_vec(), None, state)
+        })
+    }
 
-        let sender = Some(tx);
+    /// how many lua states are in the pool
+    pub fn states(&self) -> usize {
+        self.workers.len()
+    }
 
-        Interp { sender }
+    /// whether any lua state died of a panic, only possible where panics unwind
+    #[cfg(test)]
+    pub fn panicked(&self) -> bool {
+        self.workers
+            .iter()
+            .any(|worker| worker.panicked.load(Ordering::SeqCst))
     }
 
-    pub fn test(
-        content: &'static str,
-        proxy: tokio::sync::oneshot::Sender<mlua::Result<Proxy>>,
-    ) -> Self {
-        let (tx, rx) = tokio::sync::mpsc::channel::<Invocation>(1);
+    /// the values shared between lua states and rules
+    pub fn state(&self) -> &State {
+        &self.state
+    }
 
-        std::thread::spawn(move || {
-            let lua = match Self::load_literal(content.as_bytes().to_vec(), None) {
-                Ok(l) => l,
+    /// starts a thread per lua state, `proxy` gets the config once all of them loaded it
+    fn spawn<L>(states: usize, state: State, proxy: Loaded, load: L) -> Self
+    where
+        L: Fn(State) -> mlua::Result<Lua> + Clone + Send + 'static,
+    {
+        let (loaded, results) = std::sync::mpsc::channel();
+
+        let mut workers = Vec::new();
+        for i in 0..states.max(1) {
+            let (tx, rx) = tokio::sync::mpsc::channel::<Invocation>(1);
+
+            let load = load.clone();
+            let loaded = loaded.clone();
+            let state = state.clone();
+            let panicked = Arc::new(AtomicBool::new(false));
+            let unwinding = panicked.clone();
+            std::thread::spawn(move || {
+                let lua = match load(state) {
+                    Ok(l) => l,
+
+                    Err(e) => {
+                        let _ = loaded.send((i, Err(e)));
+                        return;
+                    }
+                };
+
+                Self::runloop(lua, rx, &unwinding, move |res| {
+                    let _ = loaded.send((i, res));
+                });
+            });
+
+            workers.push(Worker {
+                sender: tx,
+                busy: AtomicUsize::new(0),
+                panicked,
+            });
+        }
 
-                Err(e) => {
-                    let _ = proxy.send(Err(e));
-                    return;
+        let states = workers.len();
+        std::thread::spawn(move || {
+            let mut config = None;
+
+            for (i, res) in results.iter().take(states) {
+                match res {
+                    Ok(p) if i == 0 => config = Some(Ok(p)),
+                    Ok(_) => (),
+                    Err(e) => {
+                        let _ = proxy.send(Err(e));
+                        return;
+                    }
                 }
-            };
+            }
 
-            Self::runloop(lua, rx, proxy);
+            if let Some(config) = config {
+                let _ = proxy.send(config);
+            }
         });
 
-        let sender = Some(tx);
-
-        Interp { sender }
+        Interp {
+            workers: workers.into(),
+            state,
+        }
     }
 
     fn runloop(
         lua: Lua,
         mut rx: tokio::sync::mpsc::Receiver<Invocation>,
-        proxy: tokio::sync::oneshot::Sender<mlua::Result<Proxy>>,
+        panicked: &AtomicBool,
+        proxy: impl FnOnce(mlua::Result<Proxy>),
     ) {
         let mut swap = Proxy::default();
         let mut funcs: Vec<Function<'static>> = Vec::new();
@@ -96,21 +205,28 @@ impl Interp {
                 std::mem::swap(&mut swap, &mut appdata.proxy);
                 std::mem::swap(&mut funcs, &mut appdata.funcs);
 
-                let _ = proxy.send(Ok(swap));
+                proxy(Ok(swap));
             }
             Err(e) => {
-                let _ = proxy.send(Err(e));
+                proxy(Err(e));
                 return;
             }
         }
 
         while let Some(s) = rx.blocking_recv() {
+            let _unwinding = Unwinding(panicked);
+
             let Some(func) = funcs.get(s.selector) else {
                 tracing::error!("invalid func dereferenced");
                 continue;
             };
 
-            let r: Val = match func.call(s.input) {
+            let r = match s.input {
+                Input::Value(val) => func.call(val).map(Return::Value),
+                Input::Hook(hook) => call_hook(&lua, func, hook.0, hook.1),
+            };
+
+            let r = match r {
                 Ok(r) => r,
                 Err(e) => {
                     if s.chan.send(Err(e)).is_err() {
@@ -127,12 +243,40 @@ impl Interp {
         }
     }
 
-    fn load_literal(content: Vec<u8>, path: Option<&Path>) -> mlua::Result<Lua> {
+    fn load_literal(content: Vec<u8>, path: Option<&Path>, state: State) -> mlua::Result<Lua> {
         let lua = Lua::new();
-        let appdata = AppData::default();
+        let dir = path.and_then(Path::parent).map(Path::to_path_buf);
+        let appdata = AppData {
+            dir: dir.clone(),
+            proxy: Proxy {
+                sources: path.map(Path::to_path_buf).into_iter().collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
 
         lua.set_app_data(appdata);
 
+        {
+            let package: Table = lua.globals().get("package")?;
+
+            if let Some(dir) = dir {
+                let dir = if dir.as_os_str().is_empty() {
+                    PathBuf::from(".")
+                } else {
+                    dir
+                };
+
+                let search: String = package.get("path")?;
+                let dir = dir.display();
+                package.set("path", format!("{dir}/?.lua;{dir}/?/init.lua;{search}"))?;
+            }
+
+            // lua's own file searcher, swapped so required files are known to --watch
+            let searchers: Table = package.get("searchers")?;
+            searchers.raw_set(2, lua.create_function(search)?)?;
+        }
+
         {
             let globals = lua.globals();
 
@@ -142,10 +286,20 @@ impl Interp {
             globals.set("query", lua.create_function(query)?)?;
 
             globals.set("set", lua.create_function(set)?)?;
+            globals.set("capture", lua.create_function(capture)?)?;
+            globals.set("var", lua.create_function(var)?)?;
             globals.set("sub", lua.create_function(sub)?)?;
 
             globals.set("redirect", lua.create_function(redirect)?)?;
 
+            globals.set("delay", lua.create_function(delay)?)?;
+            globals.set("throttle", lua.create_function(throttle)?)?;
+            globals.set("drop", lua.create_function(drop_exchange)?)?;
+            globals.set("fail", lua.create_function(fail)?)?;
+
+            globals.set("state", state)?;
+            globals.set("prax", helpers::module(&lua)?)?;
+
             globals.set("dump", lua.create_userdata(Rule::Dump)?)?;
             globals.set("intercept", lua.create_userdata(Rule::Intercept)?)?;
 
@@ -156,9 +310,9 @@ impl Interp {
         }
 
         let chunk = lua.load(content).set_name(if let Some(path) = path {
-            path.display().to_string()
+            format!("@{}", path.display())
         } else {
-            "prax-config".to_string()
+            "=prax-config".to_string()
         });
 
         chunk.exec()?;
@@ -166,7 +320,7 @@ impl Interp {
         Ok(lua)
     }
 
-    fn load_path(path: &Path) -> mlua::Result<Lua> {
+    fn load_path(path: &Path, state: State) -> mlua::Result<Lua> {
         let content = match std::fs::read(path) {
             Ok(content) => content,
             Err(e) => {
@@ -178,16 +332,45 @@ impl Interp {
             }
         };
 
-        Self::load_literal(content, Some(path))
+        Self::load_literal(content, Some(path), state)
     }
 
     pub async fn invoke(&self, func: Func, arg: Val) -> mlua::Result<Val> {
-        let Some(sender) = &self.sender else {
+        match self.call(func, Input::Value(arg)).await? {
+            Return::Value(val) => Ok(val),
+            Return::Hook(..) => Err(mlua::Error::RuntimeError(
+                "expected a value from lua".to_string(),
+            )),
+        }
+    }
+
+    /// runs a hook over a request (and response), returning them as the hook left them
+    pub async fn hook(
+        &self,
+        func: Func,
+        req: Message,
+        res: Option<Message>,
+    ) -> mlua::Result<(Message, Option<Message>, Outcome)> {
+        match self.call(func, Input::Hook(Box::new((req, res)))).await? {
+            Return::Hook(hooked) => Ok(*hooked),
+            Return::Value(_) => Err(mlua::Error::RuntimeError(
+                "expected a hook result from lua".to_string(),
+            )),
+        }
+    }
+
+    async fn call(&self, func: Func, arg: Input) -> mlua::Result<Return> {
+        let Some(worker) = self
+            .workers
+            .iter()
+            .min_by_key(|w| w.busy.load(Ordering::Relaxed))
+        else {
             return Err(mlua::Error::RuntimeError(
                 "lua interpreter is not initiated".to_string(),
             ));
         };
 
+        let _busy = Busy::new(&worker.busy);
         let (tx, rx) = tokio::sync::oneshot::channel();
 
         let invok = Invocation {
@@ -196,23 +379,94 @@ impl Interp {
             input: arg,
         };
 
-        if let Err(e) = sender.send(invok).await {
+        if let Err(e) = worker.sender.send(invok).await {
             return Err(mlua::Error::RuntimeError(format!(
                 "lua thread exited {}",
                 e
             )));
         }
 
-        rx.await
-            .map_err(|_| mlua::Error::RuntimeError("failed to receive ".to_string()))?
+        rx.await.map_err(|_| {
+            if worker.panicked.load(Ordering::SeqCst) {
+                mlua::Error::RuntimeError("lua state panicked".to_string())
+            } else {
+                mlua::Error::RuntimeError("failed to receive ".to_string())
+            }
+        })?
+    }
+}
+
+impl<'a> Busy<'a> {
+    fn new(busy: &'a AtomicUsize) -> Self {
+        busy.fetch_add(1, Ordering::Relaxed);
+        Busy(busy)
+    }
+}
+
+impl Drop for Busy<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Unwinding<'_> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.0.store(true, Ordering::SeqCst);
+        }
     }
 }
 
-fn app_data_mut(lua: &Lua) -> mlua::Result<AppDataRefMut<AppData>> {
+fn call_hook<'lua>(
+    lua: &'lua Lua,
+    func: &Function<'lua>,
+    req: Message,
+    res: Option<Message>,
+) -> mlua::Result<Return> {
+    let req = req.into_lua(lua)?;
+    let res = res.map(|res| res.into_lua(lua)).transpose()?;
+
+    let outcome: Outcome = func.call((req.clone(), res.clone()))?;
+
+    let req = Message::from_lua(req, lua)?;
+    let res = res.map(|res| Message::from_lua(res, lua)).transpose()?;
+
+    Ok(Return::Hook(Box::new((req, res, outcome))))
+}
+
+fn app_data_mut(lua: &Lua) -> mlua::Result<AppDataRefMut<'_, AppData>> {
     lua.app_data_mut()
         .ok_or_else(|| mlua::Error::RuntimeError("app data not set".to_string()))
 }
 
+/// finds `require`d modules on `package.path`, recording the file as a source of the config
+///
+/// only requires made while the config loads are recorded, later ones happen after
+/// the sources were handed over to be watched
+fn search<'a>(lua: &'a Lua, name: String) -> mlua::Result<mlua::MultiValue<'a>> {
+    let package: Table = lua.globals().get("package")?;
+    let searchpath: Function = package.get("searchpath")?;
+    let path: String = package.get("path")?;
+
+    let (found, tried): (Option<String>, Option<String>) = searchpath.call((name, path))?;
+    let Some(found) = found else {
+        return tried.unwrap_or_default().into_lua_multi(lua);
+    };
+
+    let content = std::fs::read(&found).map_err(|e| {
+        mlua::Error::RuntimeError(format!("could not read lua file \"{found}\" {e}"))
+    })?;
+
+    let loader = lua
+        .load(content)
+        .set_name(format!("@{found}"))
+        .into_function()?;
+
+    app_data_mut(lua)?.proxy.sources.push(PathBuf::from(&found));
+
+    (loader, found).into_lua_multi(lua)
+}
+
 fn header(_: &Lua, (key,): (String,)) -> mlua::Result<Attr> {
     Ok(Attr::Header(key))
 }
@@ -231,6 +485,10 @@ fn target(lua: &Lua, (hostname,): (String,)) -> mlua::Result<TargetRef> {
     let mut data = app_data_mut(lua)?;
     tracing::info!("Targeting {}", &hostname);
 
+    if let Some(problem) = check::target(&hostname) {
+        data.proxy.diagnostics.push(Diagnostic::at(lua, problem));
+    }
+
     let r = TargetRef {
         hostname: hostname.clone(),
     };
@@ -239,72 +497,206 @@ fn target(lua: &Lua, (hostname,): (String,)) -> mlua::Result<TargetRef> {
         hostname,
         req: vec![],
         resp: vec![],
+        identity: None,
+        timeouts: Timeouts::default(),
     });
 
     Ok(r)
 }
 
-fn set(_: &Lua, (attr, value): (Attr, String)) -> mlua::Result<Rule> {
+fn set(_: &Lua, (attr, value): (Attr, Value)) -> mlua::Result<Rule> {
     Ok(Rule::Set(attr, value))
 }
 
+fn capture(_: &Lua, (attr, name): (Attr, String)) -> mlua::Result<Rule> {
+    Ok(Rule::Capture(attr, name))
+}
+
+fn var(_: &Lua, (name,): (String,)) -> mlua::Result<Value> {
+    Ok(Value::Var(name))
+}
+
 fn redirect(_: &Lua, (host,): (String,)) -> mlua::Result<Rule> {
     Ok(Rule::Redirect(host))
 }
 
-fn sub<'a>(lua: &'a Lua, (attr, value): (Attr, mlua::Value<'a>)) -> mlua::Result<Rule> {
+fn delay(_: &Lua, (min, max): (u64, Option<u64>)) -> mlua::Result<Rule> {
+    let max = max.unwrap_or(min);
+    if max < min {
+        return Err(mlua::Error::RuntimeError(format!(
+            "delay range {min}ms to {max}ms is backwards"
+        )));
+    }
+
+    Ok(Rule::Delay(
+        Duration::from_millis(min),
+        Duration::from_millis(max),
+    ))
+}
+
+fn throttle(_: &Lua, (rate,): (u64,)) -> mlua::Result<Rule> {
+    if rate == 0 {
+        return Err(mlua::Error::RuntimeError(
+            "throttle needs at least a byte a second".to_string(),
+        ));
+    }
+
+    Ok(Rule::Throttle(rate))
+}
+
+fn drop_exchange(_: &Lua, (): ()) -> mlua::Result<Rule> {
+    Ok(Rule::Drop)
+}
+
+fn fail(_: &Lua, (status, probability): (u16, Option<f64>)) -> mlua::Result<Rule> {
+    if hyper::StatusCode::from_u16(status).is_err() {
+        return Err(mlua::Error::RuntimeError(format!(
+            "fail with invalid status {status}"
+        )));
+    }
+
+    let probability = probability.unwrap_or(1.0);
+    if !(0.0..=1.0).contains(&probability) {
+        return Err(mlua::Error::RuntimeError(format!(
+            "fail probability {probability} is not between 0 and 1"
+        )));
+    }
+
+    Ok(Rule::Fail(status, probability))
+}
+
+fn sub<'a>(
+    lua: &'a Lua,
+    (attr, value, opts): (Attr, mlua::Value<'a>, Option<Table<'a>>),
+) -> mlua::Result<Rule> {
+    let invalid = |msg: &str| mlua::Error::BadArgument {
+        to: Some("sub".to_owned()),
+        pos: 2,
+        name: Some("transform".to_owned()),
+        cause: Arc::new(mlua::Error::RuntimeError(msg.to_string())),
+    };
+
     match value {
         mlua::Value::Function(func) => {
+            if opts.is_some() {
+                return Err(invalid("options only apply to commands"));
+            }
+
             let mut data = app_data_mut(lua)?;
 
             let index = data.funcs.len();
             let func = unsafe {
                 // funcs are held just as long as the lua interpreter is
-                std::mem::transmute(func)
+                std::mem::transmute::<Function<'_>, Function<'static>>(func)
             };
             data.funcs.push(func);
 
             Ok(Rule::Subst(attr, Subst::Func(index)))
         }
 
-        mlua::Value::String(s) => Ok(Rule::Subst(attr, Subst::System(s.to_str()?.to_string()))),
-        mlua::Value::UserData(data) => {
-            if data.is::<Subst>() {
-                Ok(Rule::Subst(attr, data.user_value()?))
-            } else {
-                Err(mlua::Error::UserDataTypeMismatch)
+        mlua::Value::String(s) => {
+            let program = Program::Shell(s.to_str()?.to_string());
+            Ok(Rule::Subst(attr, Subst::System(system(program, opts)?)))
+        }
+
+        mlua::Value::Table(argv) => {
+            let argv = argv
+                .sequence_values::<String>()
+                .collect::<mlua::Result<Vec<_>>>()?;
+
+            if argv.is_empty() {
+                return Err(invalid("a command needs a program to run"));
             }
+
+            let program = Program::Argv(argv);
+            Ok(Rule::Subst(attr, Subst::System(system(program, opts)?)))
         }
 
-        _ => Err(mlua::Error::BadArgument {
-            to: Some("sub".to_owned()),
-            pos: 1,
-            name: Some("strategy".to_owned()),
-            cause: Arc::new(mlua::Error::RuntimeError(
-                "invalid type given to sub".to_string(),
-            )),
-        }),
+        _ => Err(invalid("invalid type given to sub")),
     }
 }
 
+/// a command with the `timeout` (ms) and `env` options of `sub`
+fn system(program: Program, opts: Option<Table>) -> mlua::Result<System> {
+    let Some(opts) = opts else {
+        return Ok(System {
+            program,
+            timeout: None,
+            env: None,
+        });
+    };
+
+    Ok(System {
+        program,
+        timeout: opts
+            .get::<_, Option<u64>>("timeout")?
+            .map(Duration::from_millis),
+        env: opts.get("env")?,
+    })
+}
+
 impl<'lua> IntoLua<'lua> for Val {
     fn into_lua(self, lua: &'lua Lua) -> mlua::prelude::LuaResult<mlua::Value<'lua>> {
         match self {
             Val::Nil => Ok(mlua::Value::Nil),
             Val::Bool(b) => Ok(mlua::Value::Boolean(b)),
             Val::Number(n) => Ok(mlua::Value::Integer(n)),
+            Val::Float(n) => Ok(mlua::Value::Number(n)),
             Val::String(s) => Ok(mlua::Value::String(lua.create_string(s)?)),
+            Val::Bytes(b) => Ok(mlua::Value::String(lua.create_string(b)?)),
+            Val::Array(items) => Ok(mlua::Value::Table(lua.create_sequence_from(items)?)),
+            Val::Map(pairs) => Ok(mlua::Value::Table(lua.create_table_from(pairs)?)),
         }
     }
 }
 
 impl<'lua> FromLua<'lua> for Val {
     fn from_lua(value: mlua::Value<'lua>, _: &'lua Lua) -> mlua::prelude::LuaResult<Self> {
+        Val::from_value(value, 0)
+    }
+}
+
+impl Val {
+    fn from_value(value: mlua::Value<'_>, depth: usize) -> mlua::Result<Val> {
         match value {
             mlua::Value::Nil => Ok(Val::Nil),
             mlua::Value::Boolean(b) => Ok(Val::Bool(b)),
-            mlua::Value::String(s) => Ok(Val::String(s.to_str()?.to_string())),
+            mlua::Value::String(s) => Ok(Val::from(s.as_bytes().to_vec())),
             mlua::Value::Integer(n) => Ok(Val::Number(n)),
+            mlua::Value::Number(n) => Ok(Val::Float(n)),
+            mlua::Value::Table(table) => {
+                if depth >= MAX_DEPTH {
+                    return Err(mlua::Error::RuntimeError(
+                        "table is nested too deep to be coorced into Val".to_string(),
+                    ));
+                }
+
+                let mut pairs = Vec::new();
+                for pair in table.pairs::<mlua::Value, mlua::Value>() {
+                    let (key, value) = pair?;
+                    pairs.push((
+                        Val::from_value(key, depth + 1)?,
+                        Val::from_value(value, depth + 1)?,
+                    ));
+                }
+
+                let sequence = pairs.iter().all(|(key, _)| {
+                    matches!(key, Val::Number(n) if *n >= 1 && *n as usize <= pairs.len())
+                });
+
+                if sequence {
+                    pairs.sort_by_key(|(key, _)| match key {
+                        Val::Number(n) => *n,
+                        _ => 0,
+                    });
+
+                    Ok(Val::Array(
+                        pairs.into_iter().map(|(_, value)| value).collect(),
+                    ))
+                } else {
+                    Ok(Val::Map(pairs))
+                }
+            }
             _ => Err(mlua::Error::RuntimeError(format!(
                 "Invalid type to be coorced into Val [{}]",
                 value.type_name()
@@ -313,6 +705,35 @@ impl<'lua> FromLua<'lua> for Val {
     }
 }
 
+impl<'lua> FromLua<'lua> for Value {
+    fn from_lua(value: mlua::Value<'lua>, _: &'lua Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::String(s) => Ok(Value::Literal(s.to_str()?.to_string())),
+            mlua::Value::Integer(n) => Ok(Value::Literal(n.to_string())),
+            mlua::Value::UserData(data) => Ok(data.borrow::<Value>()?.clone()),
+            _ => Err(mlua::Error::RuntimeError(format!(
+                "expected a string or var but got [{}]",
+                value.type_name()
+            ))),
+        }
+    }
+}
+
+impl Value {
+    /// the text to set, `None` when a var has not been captured yet
+    pub fn resolve(&self, state: &State) -> Option<String> {
+        match self {
+            Value::Literal(s) => Some(s.clone()),
+            Value::Var(name) => match state.get(name) {
+                Val::Nil => None,
+                Val::String(s) => Some(s),
+                Val::Bytes(b) => Some(String::from_utf8_lossy(&b).into_owned()),
+                val => Some(val.to_string()),
+            },
+        }
+    }
+}
+
 #[derive(FromLua, Clone)]
 pub struct TargetRef {
     pub hostname: String,
@@ -326,6 +747,10 @@ impl UserData for TargetRef {
     fn add_methods<'lua, M: mlua::prelude::LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_async_function("req", target_ref_req);
         methods.add_async_function("resp", target_ref_resp);
+        methods.add_async_function("identity", target_ref_identity);
+        methods.add_async_function("timeouts", target_ref_timeouts);
+        methods.add_async_function("on_request", target_ref_on_request);
+        methods.add_async_function("on_response", target_ref_on_response);
     }
 }
 
@@ -334,6 +759,7 @@ async fn target_ref_req(
     (target, rules): (TargetRef, Variadic<Rule>),
 ) -> mlua::Result<TargetRef> {
     let mut appdata = app_data_mut(lua)?;
+    let mut diagnostics = Vec::new();
 
     let t = appdata
         .proxy
@@ -345,9 +771,20 @@ async fn target_ref_req(
         })?;
 
     for r in rules {
+        if let Some(problem) = check::never_applies(&r, Side::Request) {
+            let diagnostic = Diagnostic::at(lua, problem);
+            return Err(mlua::Error::RuntimeError(diagnostic.to_string()));
+        }
+
+        if let Some(problem) = check::rule(&r, Side::Request) {
+            diagnostics.push(Diagnostic::at(lua, problem));
+        }
+
         t.req.push(r);
     }
 
+    appdata.proxy.diagnostics.extend(diagnostics);
+
     Ok(target)
 }
 
@@ -356,6 +793,7 @@ async fn target_ref_resp(
     (target, rules): (TargetRef, Variadic<Rule>),
 ) -> mlua::Result<TargetRef> {
     let mut appdata = app_data_mut(lua)?;
+    let mut diagnostics = Vec::new();
 
     let t = appdata
         .proxy
@@ -367,12 +805,109 @@ async fn target_ref_resp(
         })?;
 
     for r in rules {
+        if let Some(problem) = check::never_applies(&r, Side::Response) {
+            let diagnostic = Diagnostic::at(lua, problem);
+            return Err(mlua::Error::RuntimeError(diagnostic.to_string()));
+        }
+
+        if let Some(problem) = check::rule(&r, Side::Response) {
+            diagnostics.push(Diagnostic::at(lua, problem));
+        }
+
         t.resp.push(r);
     }
 
+    appdata.proxy.diagnostics.extend(diagnostics);
+
+    Ok(target)
+}
+
+async fn target_ref_identity(
+    lua: &Lua,
+    (target, cert, key): (TargetRef, String, String),
+) -> mlua::Result<TargetRef> {
+    let mut appdata = app_data_mut(lua)?;
+    let (cert, key) = (PathBuf::from(cert), PathBuf::from(key));
+
+    let (cert, key) = match &appdata.dir {
+        Some(dir) => (dir.join(cert), dir.join(key)),
+        None => (cert, key),
+    };
+
+    let t = appdata
+        .proxy
+        .targets
+        .iter_mut()
+        .find(|name| name.hostname == target.hostname)
+        .ok_or_else(|| {
+            mlua::Error::RuntimeError(format!("invalid host target \"{}\"", target.hostname))
+        })?;
+
+    t.identity = Some(Identity { cert, key });
+
+    Ok(target)
+}
+
+async fn target_ref_timeouts(
+    lua: &Lua,
+    (target, opts): (TargetRef, Table<'_>),
+) -> mlua::Result<TargetRef> {
+    let millis = |key: &str| -> mlua::Result<Option<Duration>> {
+        Ok(opts.get::<_, Option<u64>>(key)?.map(Duration::from_millis))
+    };
+
+    let timeouts = Timeouts {
+        connect: millis("connect")?,
+        read: millis("read")?,
+        total: millis("total")?,
+        retries: opts.get("retries")?,
+        backoff: millis("backoff")?,
+    };
+
+    let mut appdata = app_data_mut(lua)?;
+    let t = appdata
+        .proxy
+        .targets
+        .iter_mut()
+        .find(|name| name.hostname == target.hostname)
+        .ok_or_else(|| {
+            mlua::Error::RuntimeError(format!("invalid host target \"{}\"", target.hostname))
+        })?;
+
+    t.timeouts = timeouts;
+
     Ok(target)
 }
 
+async fn target_ref_on_request<'lua>(
+    lua: &'lua Lua,
+    (target, func): (TargetRef, Function<'lua>),
+) -> mlua::Result<TargetRef> {
+    let rule = hook(lua, func)?;
+    target_ref_req(lua, (target, Variadic::from_iter([rule]))).await
+}
+
+async fn target_ref_on_response<'lua>(
+    lua: &'lua Lua,
+    (target, func): (TargetRef, Function<'lua>),
+) -> mlua::Result<TargetRef> {
+    let rule = hook(lua, func)?;
+    target_ref_resp(lua, (target, Variadic::from_iter([rule]))).await
+}
+
+fn hook<'lua>(lua: &'lua Lua, func: Function<'lua>) -> mlua::Result<Rule> {
+    let mut data = app_data_mut(lua)?;
+
+    let index = data.funcs.len();
+    let func = unsafe {
+        // funcs are held just as long as the lua interpreter is
+        std::mem::transmute::<Function<'_>, Function<'static>>(func)
+    };
+    data.funcs.push(func);
+
+    Ok(Rule::Hook(index))
+}
+
 impl From<String> for Val {
     fn from(value: String) -> Self {
         Val::String(value)
@@ -385,13 +920,44 @@ impl From<i64> for Val {
     }
 }
 
+impl From<Vec<u8>> for Val {
+    fn from(value: Vec<u8>) -> Self {
+        match String::from_utf8(value) {
+            Ok(s) => Val::String(s),
+            Err(e) => Val::Bytes(e.into_bytes()),
+        }
+    }
+}
+
 impl std::fmt::Display for Val {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Val::Nil => write!(f, "nil"),
             Val::Bool(b) => write!(f, "{}", b),
             Val::Number(n) => write!(f, "{}", n),
+            Val::Float(n) => write!(f, "{}", n),
             Val::String(s) => write!(f, "\"{}\"", s),
+            Val::Bytes(b) => write!(f, "<{} bytes>", b.len()),
+            Val::Array(items) => {
+                write!(f, "{{")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "}}")
+            }
+            Val::Map(pairs) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "[{}] = {}", key, value)?;
+                }
+                write!(f, "}}")
+            }
         }
     }
 }
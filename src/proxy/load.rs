@@ -4,19 +4,36 @@ use tokinotify::{INotify, Mask};
 
 use crate::Filter;
 
-use super::{interp::Interp, Config};
+use super::{interp::Interp, state::State, Config, Diagnostic};
+
+/// A reloaded config or why it failed to load
+pub type Reload<F> = Result<Config<F>, Diagnostic>;
 
 impl<F> Config<F>
 where
     F: Filter + Send + Sync + Clone + 'static,
 {
-    pub async fn load(path: &Path, intercept: F) -> eyre::Result<Self> {
+    /// loads the config at `path` into a pool of `states` lua states
+    pub async fn load(path: &Path, intercept: F, states: usize) -> eyre::Result<Self> {
+        Self::load_state(path, intercept, states, State::default()).await
+    }
+
+    async fn load_state(
+        path: &Path,
+        intercept: F,
+        states: usize,
+        state: State,
+    ) -> eyre::Result<Self> {
         let (tx, rx) = tokio::sync::oneshot::channel();
 
-        let interp = Interp::new(path, tx);
+        let interp = Interp::new(path, states, state, tx);
 
         let proxy = rx.await??;
 
+        for diagnostic in &proxy.diagnostics {
+            tracing::warn!("{diagnostic}");
+        }
+
         let config = Config {
             proxy,
             intercept,
@@ -26,12 +43,38 @@ where
         Ok(config)
     }
 
-    pub fn watch(&self, path: PathBuf) -> tokio::sync::mpsc::Receiver<Self> {
+    /// loads the config at `path` only to find problems with it
+    pub async fn check(path: &Path, intercept: F) -> Vec<Diagnostic> {
+        match Self::load(path, intercept, 1).await {
+            Ok(config) => config.proxy.diagnostics,
+            Err(report) => vec![Diagnostic::from_report(path, &report)],
+        }
+    }
+
+    /// problems found while loading the config
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.proxy.diagnostics
+    }
+
+    /// the config file and every file it required
+    pub fn sources(&self) -> &[PathBuf] {
+        &self.proxy.sources
+    }
+
+    /// reloads the config when `path` or anything it required changes,
+    /// `keep_state` carries captured values over and failed reloads send why
+    pub fn watch(&self, path: PathBuf, keep_state: bool) -> tokio::sync::mpsc::Receiver<Reload<F>> {
         let (tx, rx) = tokio::sync::mpsc::channel(1);
 
         let intercept = self.intercept.clone();
+        let states = self.interp.states();
+        let state = self.interp.state().clone();
+
+        let mut watched = self.sources().to_vec();
+        if !watched.contains(&path) {
+            watched.insert(0, path.clone());
+        }
 
-        let watch = path.clone();
         tokio::spawn(async move {
             let interest = Mask::CREATE | Mask::MODIFY | Mask::CLOSE_WRITE | Mask::DELETE_SELF;
 
@@ -45,11 +88,17 @@ where
 
             let i = intercept.clone();
 
-            if let Err(e) = notify.add(&watch, interest) {
+            if let Err(e) = notify.add(&path, interest) {
                 tracing::error!("failed to start watch: {e}");
                 return;
             }
 
+            for source in &watched[1..] {
+                if let Err(e) = notify.add(source, interest) {
+                    tracing::error!("failed to watch {}: {e}", source.display());
+                }
+            }
+
             loop {
                 let event = match notify.watch().await {
                     Ok(e) => e,
@@ -61,21 +110,48 @@ where
 
                 tracing::debug!("event = {event:?}");
 
+                // editors replace files on save, the watch has to follow the new file
                 if event.mask.contains(Mask::IGNORED) {
-                    if let Err(e) = notify.add(&watch, interest) {
-                        tracing::error!("failed to readd watch: {e}");
+                    for source in &watched {
+                        if let Err(e) = notify.add(source, interest) {
+                            tracing::error!("failed to readd watch on {}: {e}", source.display());
+                        }
                     }
                     continue;
                 }
 
-                match Config::load(&path, i.clone()).await {
+                let state = if keep_state {
+                    state.clone()
+                } else {
+                    State::default()
+                };
+
+                match Config::load_state(&path, i.clone(), states, state).await {
                     Ok(config) => {
-                        if tx.send(config).await.is_err() {
+                        for source in config.sources() {
+                            if watched.contains(source) {
+                                continue;
+                            }
+
+                            match notify.add(source, interest) {
+                                Ok(_) => watched.push(source.clone()),
+                                Err(e) => {
+                                    tracing::error!("failed to watch {}: {e}", source.display())
+                                }
+                            }
+                        }
+
+                        if tx.send(Ok(config)).await.is_err() {
                             tracing::error!("failed to send config");
                         }
                     }
                     Err(err) => {
                         tracing::error!("failed to load config {err}");
+
+                        let diagnostic = Diagnostic::from_report(&path, &err);
+                        if tx.send(Err(diagnostic)).await.is_err() {
+                            tracing::error!("failed to send config failure");
+                        }
                     }
                 }
             }
@@ -90,9 +166,17 @@ where
     F: Filter + Clone + Send + Sync + 'static,
 {
     pub async fn test(content: &'static str, intercept: F) -> eyre::Result<Self> {
+        Self::test_states(content, intercept, 1).await
+    }
+
+    pub async fn test_states(
+        content: &'static str,
+        intercept: F,
+        states: usize,
+    ) -> eyre::Result<Self> {
         let (tx, rx) = tokio::sync::oneshot::channel();
 
-        let interp = Interp::test(content, tx);
+        let interp = Interp::test(content, states, tx);
 
         let proxy = rx.await??;
 
@@ -1,30 +1,46 @@
+use std::{path::PathBuf, time::Duration};
+
 use mlua::{FromLua, UserData};
 
 mod attr;
+mod check;
 mod err;
+mod fault;
 mod filter;
+mod helpers;
+mod hook;
 mod load;
 mod query;
+mod state;
 mod sub;
+mod upstream;
 
 mod interp;
 
 #[cfg(test)]
 mod test;
 
+pub use check::Diagnostic;
 pub use err::ConfError;
+pub use load::Reload;
 pub use query::Query;
 
-use crate::Filter;
+use crate::{Filter, Identity, Timeouts};
 
 use self::interp::Interp;
 
-pub use sub::{Func, Subst};
+pub use sub::{Func, Program, Subst, System};
 
 #[derive(Default, Clone, Debug)]
 pub struct Proxy {
     pub targets: Vec<Target>,
     pub focus: bool,
+
+    /// the config file and every file it required
+    pub sources: Vec<PathBuf>,
+
+    /// problems found while loading the config
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 #[derive(FromLua, Debug, Clone)]
@@ -32,15 +48,31 @@ pub struct Target {
     pub hostname: String,
     pub req: Vec<Rule>,
     pub resp: Vec<Rule>,
+    pub identity: Option<Identity>,
+    pub timeouts: Timeouts,
 }
 
 #[derive(FromLua, Debug, Clone)]
 pub enum Rule {
     Intercept,
     Dump,
-    Set(Attr, String),
+    Set(Attr, Value),
+    Capture(Attr, String),
     Subst(Attr, Subst),
     Redirect(String),
+    Hook(Func),
+
+    /// hold the message back somewhere between the two durations
+    Delay(Duration, Duration),
+
+    /// pass the body on at most this many bytes a second
+    Throttle(u64),
+
+    /// close the connection instead of passing the message on
+    Drop,
+
+    /// answer with a status instead of passing the message on, at a probability
+    Fail(u16, f64),
 }
 
 #[derive(FromLua, Debug, Clone)]
@@ -60,5 +92,15 @@ pub struct Config<F: Filter + Sync> {
     intercept: F,
 }
 
+/// A value given to `set`
+#[derive(Debug, Clone)]
+pub enum Value {
+    Literal(String),
+
+    /// looked up in the config's state when the rule is applied
+    Var(String),
+}
+
 impl UserData for Rule {}
+impl UserData for Value {}
 impl UserData for Attr {}
@@ -20,7 +20,7 @@ impl Query {
         }
     }
 
-    pub fn iter(&self) -> QueryIter {
+    pub fn iter(&self) -> QueryIter<'_> {
         QueryIter {
             query: self,
             pos: 0,
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use mlua::{MetaMethod, UserData};
+
+use super::interp::Val;
+
+/// Values shared between every lua state of a config
+///
+/// Exposed to scripts as the `state` global, values are copied in and out
+/// so tables have to be assigned back after being changed.
+#[derive(Clone, Default, Debug)]
+pub struct State(Arc<Mutex<HashMap<String, Val>>>);
+
+impl State {
+    pub fn get(&self, key: &str) -> Val {
+        let Ok(values) = self.0.lock() else {
+            return Val::Nil;
+        };
+
+        values.get(key).cloned().unwrap_or(Val::Nil)
+    }
+
+    pub fn set(&self, key: String, value: Val) {
+        let Ok(mut values) = self.0.lock() else {
+            tracing::error!("state poisoned, dropping {key}");
+            return;
+        };
+
+        if value == Val::Nil {
+            values.remove(&key);
+        } else {
+            values.insert(key, value);
+        }
+    }
+}
+
+impl UserData for State {
+    fn add_methods<'lua, M: mlua::prelude::LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_method(MetaMethod::Index, |_, this, key: String| Ok(this.get(&key)));
+
+        methods.add_meta_method(
+            MetaMethod::NewIndex,
+            |_, this, (key, value): (String, Val)| {
+                this.set(key, value);
+                Ok(())
+            },
+        );
+    }
+}
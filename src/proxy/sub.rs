@@ -1,16 +1,40 @@
-use std::process::Stdio;
+use std::{collections::BTreeMap, io::ErrorKind, process::Stdio, time::Duration};
 
 use mlua::FromLua;
-use tokio::io::AsyncWriteExt;
+use tokio::{io::AsyncWriteExt, process::Command};
 
 use super::interp::{Interp, Val};
 
 pub type Func = usize;
 
+/// how long commands may run when their rule does not say
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(FromLua, Debug, Clone)]
 pub enum Subst {
     Func(Func),
-    System(String),
+    System(System),
+}
+
+/// A command given the value on stdin, its stdout replaces the value
+#[derive(Debug, Clone, PartialEq)]
+pub struct System {
+    pub program: Program,
+
+    /// how long the command may run before it is killed, [DEFAULT_TIMEOUT] when unset
+    pub timeout: Option<Duration>,
+
+    /// replaces the environment of the command (keeping PATH) when set
+    pub env: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Program {
+    /// run with `/bin/sh -c`
+    Shell(String),
+
+    /// run directly, without a shell to interpret the arguments
+    Argv(Vec<String>),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -21,14 +45,26 @@ pub enum SubstError {
     #[error("lua subst error {0}")]
     Io(#[from] tokio::io::Error),
 
-    #[error("failed to run command: {0}")]
-    SystemFailure(i32),
+    #[error("command exited with {0}: {1}")]
+    SystemFailure(i32, String),
+
+    #[error("command timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("command has no program to run")]
+    EmptyCommand,
 
     #[error("system output not utf8: {0}")]
     NonUTFOutput(#[from] std::string::FromUtf8Error),
 
     #[error("lua invoked and expected a string but got {0}")]
     TypeMismatch(Val),
+
+    #[error("expected a whole number but got \"{0}\"")]
+    NotNumber(String),
+
+    #[error("failed to open stdin of command")]
+    NoStdin,
 }
 
 impl Subst {
@@ -39,11 +75,17 @@ impl Subst {
 
                 match res {
                     Val::Number(n) => Ok(n),
+                    Val::Float(n) if n.fract() == 0.0 => Ok(n as i64),
+                    Val::String(s) => number(&s),
                     _ => Err(SubstError::TypeMismatch(res)),
                 }
             }
 
-            Subst::System(_) => todo!("implement running system command"),
+            Subst::System(sh) => {
+                let out = sh.run(num.to_string().as_bytes()).await?;
+
+                number(&String::from_utf8(out)?)
+            }
         }
     }
 
@@ -59,32 +101,129 @@ impl Subst {
             }
 
             Subst::System(sh) => {
-                let mut proc = tokio::process::Command::new("/bin/sh")
-                    .arg("-c")
-                    .arg(sh)
-                    .stdin(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .spawn()?;
-
-                proc.stdin
-                    .take()
-                    .unwrap()
-                    .write_all(content.as_bytes())
-                    .await?;
-                let out = proc.wait_with_output().await?;
-
-                if !out.status.success() {
-                    return Err(SubstError::SystemFailure(out.status.code().unwrap_or(-1)));
-                }
+                let out = sh.run(content.as_bytes()).await?;
 
-                let mut out = String::from_utf8(out.stdout)?;
+                Ok(String::from_utf8(out)?)
+            }
+        }
+    }
+
+    /// substitutes a body, which may not be text
+    pub async fn subst_bytes(
+        &self,
+        interp: &Interp,
+        content: Vec<u8>,
+    ) -> Result<Vec<u8>, SubstError> {
+        match self {
+            Subst::Func(slot) => {
+                let res = interp.invoke(*slot, content.into()).await?;
 
-                if out.ends_with('\n') {
-                    out.pop();
+                match res {
+                    Val::String(s) => Ok(s.into_bytes()),
+                    Val::Bytes(b) => Ok(b),
+                    _ => Err(SubstError::TypeMismatch(res)),
                 }
+            }
+
+            Subst::System(sh) => sh.run(&content).await,
+        }
+    }
+}
+
+impl System {
+    async fn run(&self, content: &[u8]) -> Result<Vec<u8>, SubstError> {
+        let mut cmd = match &self.program {
+            Program::Shell(sh) => {
+                let mut cmd = Command::new("/bin/sh");
+                cmd.arg("-c").arg(sh);
+                cmd
+            }
+
+            Program::Argv(argv) => {
+                let (program, args) = argv.split_first().ok_or(SubstError::EmptyCommand)?;
+                let mut cmd = Command::new(program);
+                cmd.args(args);
+                cmd
+            }
+        };
+
+        if let Some(env) = &self.env {
+            cmd.env_clear();
+            if let Some(path) = std::env::var_os("PATH") {
+                cmd.env("PATH", path);
+            }
+            cmd.envs(env);
+        }
 
-                Ok(out)
+        let mut proc = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let mut stdin = proc.stdin.take().ok_or(SubstError::NoStdin)?;
+
+        // written while stdout is drained, large values would fill both pipes otherwise
+        let write = async move {
+            match stdin.write_all(content).await {
+                // the command answered without reading all of it
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+                res => res,
             }
+        };
+
+        let timeout = self.timeout.unwrap_or(DEFAULT_TIMEOUT);
+        let run = async { tokio::join!(write, proc.wait_with_output()) };
+
+        let (written, out) = tokio::time::timeout(timeout, run)
+            .await
+            .map_err(|_| SubstError::Timeout(timeout))?;
+
+        written?;
+        let out = out?;
+
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        let stderr = stderr.trim();
+
+        if !out.status.success() {
+            let code = out.status.code().unwrap_or(-1);
+            return Err(SubstError::SystemFailure(code, stderr.to_string()));
+        }
+
+        if !stderr.is_empty() {
+            tracing::warn!(command = %self, "{stderr}");
         }
+
+        let mut out = out.stdout;
+
+        if out.ends_with(b"\n") {
+            out.pop();
+        }
+
+        Ok(out)
+    }
+}
+
+impl std::fmt::Display for System {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.program {
+            Program::Shell(sh) => write!(f, "{sh}"),
+            Program::Argv(argv) => write!(f, "{}", argv.join(" ")),
+        }
+    }
+}
+
+/// parses a whole number, allowing the `500.0` lua and tools like jq produce
+fn number(text: &str) -> Result<i64, SubstError> {
+    let text = text.trim();
+
+    if let Ok(n) = text.parse::<i64>() {
+        return Ok(n);
+    }
+
+    match text.parse::<f64>() {
+        Ok(n) if n.fract() == 0.0 && n.is_finite() => Ok(n as i64),
+        _ => Err(SubstError::NotNumber(text.to_string())),
     }
 }
@@ -6,6 +6,7 @@ use crate::{
 use trace::Trace;
 
 mod filter_check;
+mod no_abort;
 mod trace;
 
 mod intercept {
@@ -78,6 +79,63 @@ mod intercept {
             assert!(config.intercept.responses.get(1).is_none());
         }
     }
+
+    mod short_circuit {
+        use super::super::*;
+        use crate::Outcome;
+
+        const CONFIG: &str = r#"
+target("example.com:3000")
+    :req(intercept, set(header("x-after"), "1"))
+    :resp(intercept, set(header("x-after"), "1"))"#;
+
+        /// drops requests and answers every response with a 418
+        #[derive(Clone)]
+        struct Block;
+
+        impl Filter for Block {
+            async fn modify_request(
+                &self,
+                _: &mut String,
+                _: &mut crate::Req<Vec<u8>>,
+            ) -> crate::Result<Outcome> {
+                Ok(Outcome::Drop)
+            }
+
+            async fn modify_response(
+                &self,
+                _: &mut String,
+                _: &mut crate::Res<Vec<u8>>,
+            ) -> crate::Result<Outcome> {
+                let mut res = hyper::Response::new(Vec::new());
+                *res.status_mut() = hyper::StatusCode::IM_A_TEAPOT;
+
+                Ok(Outcome::Respond(res))
+            }
+        }
+
+        #[tokio::test]
+        async fn outcome() {
+            let config = Config::test(CONFIG, Block).await.unwrap();
+            let mut host = String::from("example.com:3000");
+
+            let mut req = hyper::Request::new(Vec::new());
+            let outcome = config.modify_request(&mut host, &mut req).await.unwrap();
+
+            assert!(matches!(outcome, Outcome::Drop));
+            assert!(req.headers().get("x-after").is_none());
+
+            let mut res = hyper::Response::new(Vec::new());
+            let Outcome::Respond(answer) =
+                config.modify_response(&mut host, &mut res).await.unwrap()
+            else {
+                panic!("expected the intercept to respond");
+            };
+
+            assert_eq!(answer.status(), 418);
+            assert!(res.headers().get("x-after").is_none());
+        }
+    }
 }
 
 #[tokio::test]
@@ -114,3 +172,712 @@ target("example.com:3000")
         panic!("{}\n{:#?}\n\n != \n\n{:#?}", buf, input_req, output_req);
     }
 }
+
+#[tokio::test]
+async fn portless_target() {
+    const IN: &str = "GET /\nhost: example.com:3000\n";
+    const OUT: &str = "GET /\nhost: example.com:3000\nx-target: any\n";
+    const CONFIG: &str = r#"
+focus()
+target("example.com"):req(set(header("x-target"), "any"))"#;
+
+    let config = Config::test(CONFIG, ()).await.unwrap();
+
+    assert!(crate::Upstream::in_scope(&config, "example.com:3000"));
+    filter_check::check_req(&config, IN, OUT).await;
+}
+
+#[tokio::test]
+async fn exact_target_first() {
+    const IN: &str = "GET /\nhost: example.com:3000\n";
+    const OUT: &str = "GET /\nhost: example.com:3000\nx-target: exact\n";
+    const CONFIG: &str = r#"
+target("example.com"):req(set(header("x-target"), "any"))
+target("example.com:3000"):req(set(header("x-target"), "exact"))"#;
+
+    let config = Config::test(CONFIG, ()).await.unwrap();
+
+    filter_check::check_req(&config, IN, OUT).await;
+}
+
+#[tokio::test]
+async fn identity() {
+    use crate::{Identity, Upstream};
+    use std::path::PathBuf;
+
+    const CONFIG: &str = r#"
+target("example.com:443"):identity("client.pem", "client.key")
+target("example.org:443")"#;
+
+    let config = Config::test(CONFIG, ()).await.unwrap();
+
+    let expected = Identity {
+        cert: PathBuf::from("client.pem"),
+        key: PathBuf::from("client.key"),
+    };
+
+    assert_eq!(config.identity("example.com:443"), Some(expected));
+    assert_eq!(config.identity("example.org:443"), None);
+    assert_eq!(config.identity("example.net:443"), None);
+}
+
+#[tokio::test]
+async fn scope() {
+    use crate::Upstream;
+
+    let config = Config::test(r#"target("example.com")"#, ()).await.unwrap();
+
+    assert!(config.in_scope("example.com:443"));
+    assert!(config.in_scope("example.org:443"));
+
+    const FOCUSED: &str = r#"
+focus()
+target("example.com")"#;
+
+    let config = Config::test(FOCUSED, ()).await.unwrap();
+
+    assert!(config.in_scope("example.com:443"));
+    assert!(!config.in_scope("example.org:443"));
+}
+
+#[tokio::test]
+async fn timeouts() {
+    use crate::{Timeouts, Upstream};
+    use std::time::Duration;
+
+    const CONFIG: &str = r#"
+target("example.com"):timeouts({ connect = 500, total = 2000, retries = 1 })
+target("example.org")"#;
+
+    let config = Config::test(CONFIG, ()).await.unwrap();
+
+    let expected = Timeouts {
+        connect: Some(Duration::from_millis(500)),
+        read: None,
+        total: Some(Duration::from_secs(2)),
+        retries: Some(1),
+        backoff: None,
+    };
+
+    assert_eq!(config.timeouts("example.com:443"), expected);
+    assert_eq!(config.timeouts("example.org:443"), Timeouts::default());
+}
+
+mod hooks {
+    use super::*;
+    use crate::{Origin, Outcome};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn request() {
+        const CONFIG: &str = r#"
+target("example.com:3000"):on_request(function(req)
+    req.method = "POST"
+    for _, param in ipairs(req.query) do
+        if param.name == "subject" then
+            param.value = "world"
+        end
+    end
+    req.headers["x-hooked"] = req.path
+end)"#;
+
+        const IN: &str = "GET /greet?subject=hello\nhost: example.com:3000\n";
+        const OUT: &str = "POST /greet?subject=world\nhost: example.com:3000\nx-hooked: /greet\n";
+
+        let config = Config::test(CONFIG, ()).await.unwrap();
+
+        filter_check::check_req(&config, IN, OUT).await;
+    }
+
+    #[tokio::test]
+    async fn query() {
+        const CONFIG: &str = r#"
+target("example.com:3000"):on_request(function(req)
+    req.headers["x-tag"] = req.query[2].value
+    table.insert(req.query, { name = "q", value = "a&b c" })
+end)"#;
+
+        const IN: &str = "GET /search?tag=a&tag=b%20c&raw=x+y&flag\nhost: example.com:3000\n";
+        const OUT: &str = "GET /search?tag=a&tag=b%20c&raw=x+y&flag&q=a%26b%20c\nhost: example.com:3000\nx-tag: b c\n";
+
+        let config = Config::test(CONFIG, ()).await.unwrap();
+
+        filter_check::check_req(&config, IN, OUT).await;
+    }
+
+    #[tokio::test]
+    async fn response() {
+        const CONFIG: &str = r#"
+target("example.com:3000"):on_response(function(req, res)
+    res.status = 201
+    res.headers.server = nil
+    res.body = req.path
+end)"#;
+
+        let config = Config::test(CONFIG, ()).await.unwrap();
+
+        let mut origin = hyper::Request::new(Vec::new());
+        *origin.uri_mut() = "/greet".parse().unwrap();
+
+        let mut res = hyper::Response::new(b"hello".to_vec());
+        res.headers_mut().insert("server", "nginx".parse().unwrap());
+        res.headers_mut()
+            .insert("content-length", "5".parse().unwrap());
+        res.extensions_mut().insert(Origin(Arc::new(origin)));
+
+        let mut host = String::from("example.com:3000");
+        config.modify_response(&mut host, &mut res).await.unwrap();
+
+        assert_eq!(res.status(), 201);
+        assert!(res.headers().get("server").is_none());
+        assert_eq!(res.body(), b"/greet");
+        assert_eq!(res.headers()["content-length"], "6");
+    }
+
+    #[tokio::test]
+    async fn drop() {
+        const CONFIG: &str = r#"
+target("example.com:3000"):on_request(function(req)
+    return "drop"
+end)"#;
+
+        let config = Config::test(CONFIG, ()).await.unwrap();
+
+        let mut req = hyper::Request::new(Vec::new());
+        let mut host = String::from("example.com:3000");
+
+        let outcome = config.modify_request(&mut host, &mut req).await.unwrap();
+        assert!(matches!(outcome, Outcome::Drop));
+    }
+
+    #[tokio::test]
+    async fn respond() {
+        const CONFIG: &str = r#"
+target("example.com:3000"):on_request(function(req)
+    return { status = 403, headers = { ["content-type"] = "text/plain" }, body = "denied" }
+end)"#;
+
+        let config = Config::test(CONFIG, ()).await.unwrap();
+
+        let mut req = hyper::Request::new(Vec::new());
+        let mut host = String::from("example.com:3000");
+
+        let Ok(Outcome::Respond(res)) = config.modify_request(&mut host, &mut req).await else {
+            panic!("expected the hook to respond");
+        };
+
+        assert_eq!(res.status(), 403);
+        assert_eq!(res.headers()["content-type"], "text/plain");
+        assert_eq!(res.body(), b"denied");
+    }
+
+    #[tokio::test]
+    async fn malformed() {
+        const CONFIG: &str = r#"
+target("example.com:3000"):on_request(function(req)
+    req.method = "POST"
+    req.path = "no slash"
+    return { status = 42 }
+end)"#;
+
+        let config = Config::test(CONFIG, ()).await.unwrap();
+
+        let mut req = hyper::Request::new(Vec::new());
+        let mut host = String::from("example.com:3000");
+
+        let outcome = config.modify_request(&mut host, &mut req).await;
+
+        assert!(matches!(outcome, Ok(Outcome::Continue)));
+        assert_eq!(req.method(), hyper::Method::GET);
+    }
+}
+
+mod values {
+    use super::*;
+    use crate::proxy::interp::Val;
+
+    #[tokio::test]
+    async fn binary_body() {
+        const CONFIG: &str = r#"
+target("example.com:3000"):req(sub(body, function(b) return b:reverse() end))"#;
+
+        let config = Config::test(CONFIG, ()).await.unwrap();
+
+        let mut req = hyper::Request::new(vec![0xff, 0x00, 0x7f]);
+        let mut host = String::from("example.com:3000");
+        config.modify_request(&mut host, &mut req).await.unwrap();
+
+        assert_eq!(req.body(), &[0x7f, 0x00, 0xff]);
+    }
+
+    #[tokio::test]
+    async fn tables() {
+        const CONFIG: &str = r#"
+target("example.com:3000"):req(sub(body, function(t)
+    return { t[2], t[1] * 1.5, { name = t[3].name } }
+end))"#;
+
+        let config = Config::test(CONFIG, ()).await.unwrap();
+
+        let input = Val::Array(vec![
+            Val::Number(3),
+            Val::Bytes(vec![0xff]),
+            Val::Map(vec![(
+                Val::String("name".into()),
+                Val::String("prax".into()),
+            )]),
+        ]);
+
+        let output = config.interp.invoke(0, input).await.unwrap();
+
+        assert_eq!(
+            output,
+            Val::Array(vec![
+                Val::Bytes(vec![0xff]),
+                Val::Float(4.5),
+                Val::Map(vec![(
+                    Val::String("name".into()),
+                    Val::String("prax".into())
+                )]),
+            ])
+        );
+    }
+}
+
+#[tokio::test]
+async fn pool() {
+    use crate::proxy::interp::Val;
+
+    // top level code runs once per state, each gets its own id
+    const CONFIG: &str = r#"
+local id = prax.uuid()
+
+target("example.com:3000"):req(
+    sub(header("id"), function(_) return id end))"#;
+
+    let config = Config::test_states(CONFIG, (), 2).await.unwrap();
+
+    // the first call is in flight while the second is dispatched, so it takes the idle state
+    let (first, second) = tokio::join!(
+        config.interp.invoke(0, Val::Nil),
+        config.interp.invoke(0, Val::Nil)
+    );
+
+    let (first, second) = (first.unwrap(), second.unwrap());
+    assert!(matches!(first, Val::String(_)));
+    assert_ne!(first, second);
+
+    // one after another they land on the same idle state
+    let again = config.interp.invoke(0, Val::Nil).await.unwrap();
+    assert_eq!(again, first);
+}
+
+#[tokio::test]
+async fn capture() {
+    use crate::proxy::interp::Val;
+
+    const CONFIG: &str = r#"
+target("example.com:3000")
+    :resp(capture(header("x-csrf-token"), "csrf"))
+    :req(set(header("x-csrf-token"), var("csrf")))
+    :req(sub(header("x-seen"), function(_) return state.csrf end))"#;
+
+    let config = Config::test(CONFIG, ()).await.unwrap();
+    let mut host = String::from("example.com:3000");
+
+    let mut req = hyper::Request::new(Vec::new());
+    config.modify_request(&mut host, &mut req).await.unwrap();
+    assert!(req.headers().get("x-csrf-token").is_none());
+
+    let mut res = hyper::Response::new(Vec::new());
+    res.headers_mut()
+        .insert("x-csrf-token", "abc123".parse().unwrap());
+    config.modify_response(&mut host, &mut res).await.unwrap();
+
+    assert_eq!(
+        config.interp.state().get("csrf"),
+        Val::String("abc123".into())
+    );
+
+    let mut req = hyper::Request::new(Vec::new());
+    req.headers_mut().insert("x-seen", "".parse().unwrap());
+    config.modify_request(&mut host, &mut req).await.unwrap();
+
+    assert_eq!(req.headers()["x-csrf-token"], "abc123");
+    assert_eq!(req.headers()["x-seen"], "abc123");
+}
+
+#[tokio::test]
+async fn helpers() {
+    use crate::proxy::interp::Val;
+
+    const CONFIG: &str = r#"
+target("example.com:3000"):req(
+    sub(body, function(_)
+        return {
+            prax.base64.encode("hello?"),
+            prax.base64url.encode("hello?"),
+            prax.base64url.decode("aGVsbG8_"),
+            prax.url.encode("a b&c"),
+            prax.url.decode("a%20b%26c"),
+            prax.hex.encode(prax.sha1("abc")),
+            prax.hex.encode(prax.sha256("abc")),
+            prax.hex.encode(prax.hmac("sha256", "key", "The quick brown fox jumps over the lazy dog")),
+            #prax.random(12),
+            #prax.uuid(),
+        }
+    end),
+    sub(body, function(_)
+        local token = prax.jwt.encode({ sub = "admin" }, "secret")
+        local claims, header = prax.jwt.decode(token, "secret")
+        local ok = pcall(prax.jwt.decode, token, "wrong")
+        return { claims.sub, header.alg, ok }
+    end))"#;
+
+    let config = Config::test(CONFIG, ()).await.unwrap();
+
+    let string = |s: &str| Val::String(s.to_string());
+
+    assert_eq!(
+        config.interp.invoke(0, Val::Nil).await.unwrap(),
+        Val::Array(vec![
+            string("aGVsbG8/"),
+            string("aGVsbG8_"),
+            string("hello?"),
+            string("a%20b%26c"),
+            string("a b&c"),
+            string("a9993e364706816aba3e25717850c26c9cd0d89d"),
+            string("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            string("f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"),
+            Val::Number(12),
+            Val::Number(36),
+        ])
+    );
+
+    assert_eq!(
+        config.interp.invoke(1, Val::Nil).await.unwrap(),
+        Val::Array(vec![string("admin"), string("HS256"), Val::Bool(false)])
+    );
+}
+
+#[tokio::test]
+async fn require() {
+    let dir = std::env::temp_dir().join(format!("prax-require-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("auth")).unwrap();
+
+    let config = dir.join("config.lua");
+    let module = dir.join("auth").join("init.lua");
+
+    std::fs::write(
+        &config,
+        r#"
+local auth = require("auth")
+target("example.com:3000"):req(set(header("Authorization"), auth.bearer))"#,
+    )
+    .unwrap();
+
+    std::fs::write(&module, r#"return { bearer = "Bearer foobarxyz" }"#).unwrap();
+
+    let loaded = Config::load(&config, (), 1).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+    let loaded = loaded.unwrap();
+
+    assert_eq!(loaded.sources().len(), 2);
+    assert_eq!(loaded.sources()[0], config);
+    assert!(loaded.sources()[1].ends_with("auth/init.lua"));
+
+    let mut req = hyper::Request::new(Vec::new());
+    let mut host = String::from("example.com:3000");
+    loaded.modify_request(&mut host, &mut req).await.unwrap();
+
+    assert_eq!(req.headers()["authorization"], "Bearer foobarxyz");
+}
+
+#[tokio::test]
+async fn diagnostics() {
+    const CONFIG: &str = r#"
+target("example.com:3000"):req(redirect("example org"))
+target("example.com:3000"):resp(set(header("x-ok"), "ok"))
+target("example.com:3000"):resp(set(status, "teapot"))"#;
+
+    let config = Config::test(CONFIG, ()).await.unwrap();
+
+    let found: Vec<String> = config
+        .diagnostics()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    assert_eq!(
+        found,
+        vec![
+            "prax-config:2: redirect to target \"example org\" is not a host: invalid uri character",
+            "prax-config:4: can not set status to \"teapot\": invalid status code",
+        ]
+    );
+
+    // bad values are only warned about, applying them must not take the proxy down
+    let mut res = hyper::Response::new(Vec::new());
+    let mut host = String::from("example.com:3000");
+    config.modify_response(&mut host, &mut res).await.unwrap();
+
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-ok"], "ok");
+}
+
+#[tokio::test]
+async fn never_applies() {
+    use crate::proxy::Diagnostic;
+    use std::path::Path;
+
+    let rejected = [
+        (
+            r#"target("example.com"):req(set(status, "500"))"#,
+            "status does not apply in req()",
+        ),
+        (
+            r#"target("example.com"):req(sub(status, "echo 500"))"#,
+            "status does not apply in req()",
+        ),
+        (
+            r#"target("example.com"):resp(set(method, "GET"))"#,
+            "method does not apply in resp()",
+        ),
+        (
+            r#"target("example.com"):resp(capture(path, "p"))"#,
+            "path does not apply in resp()",
+        ),
+        (
+            r#"target("example.com"):resp(set(query("q"), "1"))"#,
+            "query(\"q\") does not apply in resp()",
+        ),
+        (
+            r#"target("example.com"):resp(redirect("example.org"))"#,
+            "redirect only applies in req()",
+        ),
+        (
+            r#"target("example.com"):req(set(header("bad header"), "1"))"#,
+            "invalid header name \"bad header\": invalid HTTP header name",
+        ),
+    ];
+
+    for (config, message) in rejected {
+        let Err(report) = Config::test(config, ()).await else {
+            panic!("expected {config} to be rejected");
+        };
+
+        let diagnostic = Diagnostic::from_report(Path::new("prax-config"), &report);
+        assert_eq!(diagnostic.line, Some(1), "{config}");
+        assert_eq!(diagnostic.message, message, "{config}");
+    }
+}
+
+#[tokio::test]
+async fn numeric_system() {
+    const CONFIG: &str = r#"
+target("example.com:3000")
+    :resp(sub(status, "echo $(( $(cat) + 1 ))"))
+    :resp(sub(status, "echo 500.0"))
+target("example.org:3000"):resp(sub(status, "echo teapot"))"#;
+
+    let config = Config::test(CONFIG, ()).await.unwrap();
+
+    let mut res = hyper::Response::new(Vec::new());
+    let mut host = String::from("example.com:3000");
+    config.modify_response(&mut host, &mut res).await.unwrap();
+    assert_eq!(res.status(), 500);
+
+    let mut res = hyper::Response::new(Vec::new());
+    let mut host = String::from("example.org:3000");
+    config.modify_response(&mut host, &mut res).await.unwrap();
+    assert_eq!(res.status(), 200);
+}
+
+mod system {
+    use super::*;
+    use crate::proxy::{Program, Subst, System};
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn argv() {
+        const CONFIG: &str = r#"
+target("example.com:3000"):req(sub(header("x-shout"), { "tr", "a-z", "A-Z" }))"#;
+
+        const IN: &str = "GET /\nhost: example.com:3000\nx-shout: $(hello)\n";
+        const OUT: &str = "GET /\nhost: example.com:3000\nx-shout: $(HELLO)\n";
+
+        let config = Config::test(CONFIG, ()).await.unwrap();
+
+        filter_check::check_req(&config, IN, OUT).await;
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        const CONFIG: &str = r#"
+target("example.com:3000"):req(sub(header("x-slow"), "sleep 5; echo late", { timeout = 100 }))"#;
+
+        let config = Config::test(CONFIG, ()).await.unwrap();
+
+        let mut req = hyper::Request::new(Vec::new());
+        req.headers_mut().insert("x-slow", "early".parse().unwrap());
+        let mut host = String::from("example.com:3000");
+
+        let start = Instant::now();
+        config.modify_request(&mut host, &mut req).await.unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(req.headers()["x-slow"], "early");
+    }
+
+    #[tokio::test]
+    async fn large_body() {
+        const CONFIG: &str = r#"target("example.com:3000"):req(sub(body, "cat"))"#;
+
+        let config = Config::test(CONFIG, ()).await.unwrap();
+
+        // well beyond what the stdin and stdout pipes buffer
+        let body = vec![b'a'; 4 << 20];
+        let mut req = hyper::Request::new(body.clone());
+        let mut host = String::from("example.com:3000");
+
+        let modify = config.modify_request(&mut host, &mut req);
+        tokio::time::timeout(Duration::from_secs(5), modify)
+            .await
+            .expect("piping a large body should not deadlock")
+            .unwrap();
+
+        assert_eq!(req.body(), &body);
+    }
+
+    #[tokio::test]
+    async fn stderr() {
+        let config = Config::test(r#"target("example.com")"#, ()).await.unwrap();
+
+        let system = Subst::System(System {
+            program: Program::Shell("echo \"bad $FLAVOR\" >&2; exit 3".to_string()),
+            timeout: None,
+            env: Some([("FLAVOR".to_string(), "input".to_string())].into()),
+        });
+
+        let err = system
+            .subst(&config.interp, String::new())
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "command exited with 3: bad input");
+    }
+}
+
+mod faults {
+    use super::*;
+    use crate::{Fault, Faults, Outcome};
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn delay() {
+        const CONFIG: &str = r#"
+target("example.com:3000"):req(delay(50)):resp(delay(20, 40), throttle(512))"#;
+
+        let config = Config::test(CONFIG, ()).await.unwrap();
+        let mut host = String::from("example.com:3000");
+
+        let mut req = hyper::Request::new(Vec::new());
+        let start = Instant::now();
+        config.modify_request(&mut host, &mut req).await.unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(
+            req.extensions().get::<Faults>(),
+            Some(&Faults(vec![Fault::Delayed(Duration::from_millis(50))]))
+        );
+
+        let mut res = hyper::Response::new(Vec::new());
+        config.modify_response(&mut host, &mut res).await.unwrap();
+
+        let Some(Faults(faults)) = res.extensions().get::<Faults>() else {
+            panic!("response should be marked");
+        };
+
+        let Fault::Delayed(delay) = faults[0] else {
+            panic!("expected a delay but got {}", faults[0]);
+        };
+
+        assert!(delay >= Duration::from_millis(20) && delay <= Duration::from_millis(40));
+        assert_eq!(faults[1], Fault::Throttled(512));
+        assert_eq!(Faults::throttle(res.extensions()), Some(512));
+    }
+
+    #[tokio::test]
+    async fn drop() {
+        const CONFIG: &str = r#"target("example.com:3000"):resp(drop())"#;
+
+        let config = Config::test(CONFIG, ()).await.unwrap();
+        let mut host = String::from("example.com:3000");
+
+        let mut res = hyper::Response::new(Vec::new());
+        let outcome = config.modify_response(&mut host, &mut res).await.unwrap();
+
+        assert!(matches!(outcome, Outcome::Drop));
+        assert_eq!(
+            res.extensions().get::<Faults>(),
+            Some(&Faults(vec![Fault::Dropped]))
+        );
+    }
+
+    #[tokio::test]
+    async fn fail() {
+        const CONFIG: &str = r#"
+target("example.com:3000"):req(fail(500, 0)):resp(delay(1), fail(503))"#;
+
+        let config = Config::test(CONFIG, ()).await.unwrap();
+        let mut host = String::from("example.com:3000");
+
+        let mut req = hyper::Request::new(Vec::new());
+        config.modify_request(&mut host, &mut req).await.unwrap();
+
+        let mut res = hyper::Response::new(Vec::new());
+        let Ok(Outcome::Respond(failed)) = config.modify_response(&mut host, &mut res).await else {
+            panic!("expected the response to be failed");
+        };
+
+        assert_eq!(failed.status(), 503);
+        assert_eq!(failed.body(), b"prax: simulated failure (503)");
+        assert_eq!(
+            failed.extensions().get::<Faults>(),
+            Some(&Faults(vec![
+                Fault::Delayed(Duration::from_millis(1)),
+                Fault::Failed(503)
+            ]))
+        );
+
+        // a failed request keeps what was done to it before
+        let config = Config::test(
+            r#"target("example.com:3000"):req(throttle(10), fail(500))"#,
+            (),
+        )
+        .await
+        .unwrap();
+
+        let mut req = hyper::Request::new(Vec::new());
+        let Ok(Outcome::Respond(failed)) = config.modify_request(&mut host, &mut req).await else {
+            panic!("expected the request to be failed");
+        };
+
+        assert_eq!(
+            failed.extensions().get::<Faults>(),
+            Some(&Faults(vec![Fault::Throttled(10), Fault::Failed(500)]))
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid() {
+        for config in [
+            r#"target("example.com"):req(delay(10, 5))"#,
+            r#"target("example.com"):req(throttle(0))"#,
+            r#"target("example.com"):resp(fail(1000))"#,
+            r#"target("example.com"):resp(fail(503, 2))"#,
+        ] {
+            assert!(Config::test(config, ()).await.is_err(), "{config}");
+        }
+    }
+}
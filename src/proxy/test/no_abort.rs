@@ -0,0 +1,154 @@
+//! Profiles are built with `panic = "abort"`, a panicking rule takes the whole proxy down.
+//! Every rule is run against every attribute with hostile values here, failing is fine, panicking is not.
+
+use std::panic::AssertUnwindSafe;
+
+use futures::FutureExt;
+
+use crate::{proxy::Config, Filter};
+
+const ATTRS: &[&str] = &[
+    "method",
+    "status",
+    "path",
+    "query(\"q\")",
+    "header(\"h\")",
+    "body",
+];
+
+const RULES: &[&str] = &[
+    "dump",
+    "intercept",
+    "set({attr}, \"\")",
+    "set({attr}, \"not valid\\n\\0\")",
+    "set({attr}, \"9999\")",
+    "set({attr}, var(\"missing\"))",
+    "capture({attr}, \"captured\")",
+    "sub({attr}, function(_) return nil end)",
+    "sub({attr}, function(_) return {} end)",
+    "sub({attr}, function(_) return 1.5 end)",
+    "sub({attr}, function(_) return 1e300 end)",
+    "sub({attr}, function(_) return \"\\255\\0\" end)",
+    "sub({attr}, function(_) return \"a b\\n\" end)",
+    "sub({attr}, function(_) error(\"boom\") end)",
+    "sub({attr}, \"exit 3\")",
+    "sub({attr}, \"printf '\\\\377'\")",
+    "sub({attr}, \"echo notanumber\")",
+    "sub({attr}, \"echo 99999999999999999999\")",
+    "sub({attr}, \"echo 500\")",
+    "sub({attr}, \"cat\")",
+    "sub({attr}, \"cat >&2; exit 1\")",
+    "sub({attr}, \"sleep 1\", { timeout = 10 })",
+    "sub({attr}, { \"false\" })",
+    "sub({attr}, { \"/does/not/exist\" })",
+    "sub({attr}, {})",
+    "redirect(\"example.org:3000\")",
+    "delay(0, 5)",
+    "throttle(1)",
+    "drop()",
+    "fail(503)",
+    "fail(503, 0.5)",
+];
+
+const HOOKS: &[&str] = &[
+    "function(m, r) m.method = \"bad method\"; m.path = \"no slash\"; m.headers[\"bad header\"] = \"x\" end",
+    "function(m, r) m.query = { { name = \"a b\", value = \"\\n\" }, { value = 1 } }; m.body = nil end",
+    "function(m, r) if r then r.status = 99999; r.headers.h = \"\\n\" end end",
+    "function(m, r) return \"explode\" end",
+    "function(m, r) return { status = 42 } end",
+    "function(m, r) return { status = 200, headers = { [\"\"] = \"\" } } end",
+    "function(m, r) error(\"boom\") end",
+];
+
+/// runs an exchange through `config`, false if anything panicked
+///
+/// panics on lua worker threads would go unnoticed otherwise, the pool keeps track of them
+async fn apply(config: String) -> bool {
+    let config: &'static str = Box::leak(config.into_boxed_str());
+
+    // rules that can never apply are rejected while loading
+    let Ok(config) = Config::test(config, ()).await else {
+        return true;
+    };
+
+    let exchanged = AssertUnwindSafe(exchange(&config)).catch_unwind().await;
+
+    exchanged.is_ok() && !config.interp.panicked()
+}
+
+async fn exchange(config: &Config<()>) {
+    let mut req = hyper::Request::new(b"body".to_vec());
+    *req.uri_mut() = "/p?q=1".parse().unwrap();
+    req.headers_mut().insert("h", "v".parse().unwrap());
+
+    let mut res = hyper::Response::new(b"body".to_vec());
+    res.headers_mut().insert("h", "v".parse().unwrap());
+
+    let mut host = String::from("example.com:3000");
+    let _ = config.modify_request(&mut host, &mut req).await;
+
+    let mut host = String::from("example.com:3000");
+    let _ = config.modify_response(&mut host, &mut res).await;
+}
+
+#[tokio::test]
+async fn rules() {
+    let mut panicked = Vec::new();
+
+    for side in ["req", "resp"] {
+        for attr in ATTRS {
+            for rule in RULES {
+                let rule = rule.replace("{attr}", attr);
+                let config = format!("target(\"example.com:3000\"):{side}({rule})");
+
+                if !apply(config.clone()).await {
+                    panicked.push(config);
+                }
+            }
+        }
+    }
+
+    assert!(panicked.is_empty(), "{panicked:#?}");
+}
+
+#[tokio::test]
+async fn hooks() {
+    let mut panicked = Vec::new();
+
+    for side in ["on_request", "on_response"] {
+        for hook in HOOKS {
+            let config = format!("target(\"example.com:3000\"):{side}({hook})");
+
+            if !apply(config.clone()).await {
+                panicked.push(config);
+            }
+        }
+    }
+
+    assert!(panicked.is_empty(), "{panicked:#?}");
+}
+
+#[tokio::test]
+async fn bad_captures() {
+    use crate::Outcome;
+
+    for attr in ["method", "path"] {
+        let config = format!(
+            "target(\"example.com:3000\"):req(capture(header(\"h\"), \"x\"), set({attr}, var(\"x\")))"
+        );
+        let config = Config::test(Box::leak(config.into_boxed_str()), ())
+            .await
+            .unwrap();
+
+        let mut req = hyper::Request::new(Vec::new());
+        *req.uri_mut() = "/p?q=1".parse().unwrap();
+        req.headers_mut().insert("h", "not valid".parse().unwrap());
+
+        let mut host = String::from("example.com:3000");
+        let outcome = config.modify_request(&mut host, &mut req).await;
+
+        assert!(matches!(outcome, Ok(Outcome::Continue)), "{attr}");
+        assert_eq!(req.method(), hyper::Method::GET);
+        assert_eq!(req.uri(), "/p?q=1");
+    }
+}
@@ -2,13 +2,15 @@ use std::sync::Arc;
 
 use crate::{
     store::{Append, Store},
-    Filter,
+    Filter, Outcome,
 };
 
+type Traced<T> = Arc<Store<(String, T), Append>>;
+
 #[derive(Default, Clone)]
 pub struct Trace {
-    pub requests: Arc<Store<(String, crate::Req<Vec<u8>>), Append>>,
-    pub responses: Arc<Store<(String, crate::Res<Vec<u8>>), Append>>,
+    pub requests: Traced<crate::Req<Vec<u8>>>,
+    pub responses: Traced<crate::Res<Vec<u8>>>,
 }
 
 impl Filter for Trace {
@@ -16,23 +18,23 @@ impl Filter for Trace {
         &self,
         hostname: &mut String,
         req: &mut crate::Req<Vec<u8>>,
-    ) -> crate::Result<()> {
+    ) -> crate::Result<Outcome> {
         let hostname = hostname.to_string();
         let req = req.clone();
         self.requests.push((hostname, req));
 
-        Ok(())
+        Ok(Outcome::Continue)
     }
 
     async fn modify_response(
         &self,
         hostname: &mut String,
         res: &mut crate::Res<Vec<u8>>,
-    ) -> crate::Result<()> {
+    ) -> crate::Result<Outcome> {
         let hostname = hostname.to_string();
         let res = res.clone();
         self.responses.push((hostname, res));
 
-        Ok(())
+        Ok(Outcome::Continue)
     }
 }
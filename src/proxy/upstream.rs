@@ -0,0 +1,37 @@
+use crate::{Filter, Identity, Timeouts, Upstream};
+
+use super::{Config, Target};
+
+impl<F> Upstream for Config<F>
+where
+    F: Filter + Sync,
+{
+    fn identity(&self, hostname: &str) -> Option<Identity> {
+        self.target(hostname).and_then(|t| t.identity.clone())
+    }
+
+    fn timeouts(&self, hostname: &str) -> Timeouts {
+        self.target(hostname)
+            .map(|t| t.timeouts.clone())
+            .unwrap_or_default()
+    }
+
+    fn in_scope(&self, hostname: &str) -> bool {
+        !self.proxy.focus || self.target(hostname).is_some()
+    }
+}
+
+impl<F> Config<F>
+where
+    F: Filter + Sync,
+{
+    /// the target for "host:port", falling back to a target without a port
+    pub(crate) fn target(&self, hostname: &str) -> Option<&Target> {
+        let targets = &self.proxy.targets;
+
+        targets.iter().find(|t| t.hostname == hostname).or_else(|| {
+            let (host, _) = hostname.split_once(':')?;
+            targets.iter().find(|t| t.hostname == host)
+        })
+    }
+}
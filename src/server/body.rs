@@ -0,0 +1,308 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use http::{Extensions, HeaderMap};
+use http_body_util::{combinators::UnsyncBoxBody, BodyExt, BodyStream, Full, StreamBody};
+use hyper::body::{Bytes, Frame, Incoming};
+
+use crate::{Live, Result, Scribe, Truncated, Withheld};
+
+pub type ProxyBody = UnsyncBoxBody<Bytes, hyper::Error>;
+
+/// largest prefix of a streamed body that will be recorded
+const PREFIX_LEN: usize = 0x10000;
+
+#[derive(Clone, Debug)]
+pub struct Streaming {
+    /// bodies larger than this are streamed instead of buffered
+    pub threshold: usize,
+
+    /// content types (or prefixes like "video/") which are always streamed
+    pub types: Vec<String>,
+
+    /// content types which are passed through as they arrive and recorded live
+    pub live: Vec<String>,
+
+    /// how long a body of unknown length may stall before it is passed through live,
+    /// without it only the `live` content types are
+    pub idle: Option<Duration>,
+}
+
+/// A body read from the wire
+///
+/// Small bodies are buffered in full so that they can be filtered,
+/// anything else only has a prefix read before being streamed through.
+///
+/// Bodies of the live content types (server sent events) are passed through as they arrive,
+/// as are bodies that stall for longer than [`Streaming::idle`] if set.
+pub enum Payload {
+    Full(Vec<u8>),
+    Partial(Vec<u8>, Incoming),
+    Live(Vec<u8>, Incoming),
+}
+
+impl Default for Streaming {
+    fn default() -> Self {
+        Streaming {
+            threshold: 0x800000,
+            types: Vec::new(),
+            live: vec!["text/event-stream".to_string()],
+            idle: None,
+        }
+    }
+}
+
+impl Streaming {
+    fn live(&self, headers: &HeaderMap) -> bool {
+        content_type(headers).is_some_and(|ct| self.live.iter().any(|t| ct.starts_with(t.as_str())))
+    }
+
+    /// whether the headers alone are enough to know the body should be streamed
+    fn eager(&self, headers: &HeaderMap) -> bool {
+        let length = headers
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<usize>().ok());
+
+        if length.is_some_and(|len| len > self.threshold) {
+            return true;
+        }
+
+        content_type(headers)
+            .is_some_and(|ct| self.types.iter().any(|t| ct.starts_with(t.as_str())))
+    }
+}
+
+fn content_type(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+}
+
+impl Payload {
+    /// reads the body, giving up if a sized body stalls longer than `patience`
+    pub async fn read(
+        mut incoming: Incoming,
+        headers: &HeaderMap,
+        streaming: &Streaming,
+        patience: Option<Duration>,
+    ) -> Result<Payload> {
+        if streaming.live(headers) {
+            return Ok(Payload::Live(Vec::new(), incoming));
+        }
+
+        let limit = if streaming.eager(headers) {
+            PREFIX_LEN
+        } else {
+            streaming.threshold
+        };
+
+        let sized = headers.contains_key(http::header::CONTENT_LENGTH);
+
+        let mut buf = Vec::with_capacity(limit.min(0x2000));
+        while buf.len() <= limit {
+            let next = if sized {
+                match patience {
+                    Some(patience) => tokio::time::timeout(patience, incoming.frame())
+                        .await
+                        .map_err(|_| {
+                            io::Error::new(
+                                io::ErrorKind::TimedOut,
+                                format!("body stalled for {patience:?}"),
+                            )
+                        })?,
+                    None => incoming.frame().await,
+                }
+            } else if let Some(idle) = streaming.idle {
+                match tokio::time::timeout(idle, incoming.frame()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        tracing::debug!("body stalled after {} bytes, going live", buf.len());
+                        return Ok(Payload::Live(buf, incoming));
+                    }
+                }
+            } else {
+                incoming.frame().await
+            };
+
+            let Some(next) = next else {
+                return Ok(Payload::Full(buf));
+            };
+
+            if let Some(chunk) = next?.data_ref() {
+                buf.extend_from_slice(chunk);
+            }
+        }
+
+        tracing::debug!("streaming body past {} bytes", buf.len());
+
+        Ok(Payload::Partial(buf, incoming))
+    }
+
+    /// the body that filters get to see, streamed bodies are not filterable
+    /// and get their message marked [`Withheld`] instead
+    pub fn take_filterable(&mut self, extensions: &mut Extensions) -> Vec<u8> {
+        match self {
+            Payload::Full(buf) => std::mem::take(buf),
+            Payload::Partial(_, _) | Payload::Live(_, _) => {
+                extensions.insert(Withheld);
+                Vec::new()
+            }
+        }
+    }
+
+    /// the body scribes should record if the body is streamed
+    pub fn recorded(&self) -> Option<Vec<u8>> {
+        match self {
+            Payload::Full(_) => None,
+            Payload::Partial(prefix, _) => Some(prefix[..prefix.len().min(PREFIX_LEN)].to_vec()),
+            Payload::Live(prefix, _) => Some(prefix.clone()),
+        }
+    }
+
+    /// marks how the body was recorded for scribes
+    pub fn mark(&self, extensions: &mut Extensions) {
+        match self {
+            Payload::Full(_) => (),
+            Payload::Partial(_, _) => {
+                extensions.insert(Truncated);
+            }
+            Payload::Live(_, _) => {
+                extensions.insert(Live);
+            }
+        }
+    }
+
+    /// the body to send on, `filtered` is used unless the body is streamed
+    pub fn into_body(self, filtered: Vec<u8>) -> ProxyBody {
+        match self {
+            Payload::Full(_) => full(filtered),
+            Payload::Partial(prefix, rest) | Payload::Live(prefix, rest) => {
+                discard(filtered);
+                chained(prefix, rest)
+            }
+        }
+    }
+
+    /// the body to send on while reporting live chunks to the scribe
+    pub fn into_reported_body<S>(
+        self,
+        filtered: Vec<u8>,
+        scribe: Arc<S>,
+        ticket: S::Ticket,
+    ) -> ProxyBody
+    where
+        S: Scribe + Send + Sync + 'static,
+    {
+        let Payload::Live(prefix, rest) = self else {
+            return self.into_body(filtered);
+        };
+
+        discard(filtered);
+
+        // reports the end once the body is dropped, sent in full or not
+        let ending = Ending {
+            scribe,
+            ticket: Some(ticket),
+        };
+
+        let rest = BodyStream::new(rest).then(move |frame| {
+            let scribe = ending.scribe.clone();
+            let ticket = ending.ticket.clone();
+            let chunk = frame
+                .as_ref()
+                .ok()
+                .and_then(|frame| frame.data_ref())
+                .cloned();
+
+            async move {
+                if let (Some(chunk), Some(ticket)) = (chunk, ticket) {
+                    scribe.report_chunk(ticket, &chunk).await;
+                }
+
+                frame
+            }
+        });
+
+        let prefix = futures::stream::iter([Ok(Frame::data(Bytes::from(prefix)))]);
+
+        StreamBody::new(prefix.chain(rest)).boxed_unsync()
+    }
+}
+
+struct Ending<S: Scribe + Send + Sync + 'static> {
+    scribe: Arc<S>,
+    ticket: Option<S::Ticket>,
+}
+
+impl<S: Scribe + Send + Sync + 'static> Drop for Ending<S> {
+    fn drop(&mut self) {
+        let (Some(ticket), Ok(runtime)) =
+            (self.ticket.take(), tokio::runtime::Handle::try_current())
+        else {
+            return;
+        };
+
+        let scribe = self.scribe.clone();
+        runtime.spawn(async move { scribe.report_end(ticket).await });
+    }
+}
+
+/// a streamed body starts out empty for filters, anything they put there can not be sent
+fn discard(filtered: Vec<u8>) {
+    if !filtered.is_empty() {
+        tracing::warn!(
+            "dropping {} bytes a filter set as the body of a streamed message",
+            filtered.len()
+        );
+    }
+}
+
+pub fn full(buf: impl Into<Bytes>) -> ProxyBody {
+    Full::new(buf.into())
+        .map_err(|never| match never {})
+        .boxed_unsync()
+}
+
+fn chained(prefix: Vec<u8>, rest: Incoming) -> ProxyBody {
+    let prefix = futures::stream::iter([Ok(Frame::data(Bytes::from(prefix)))]);
+    let stream = prefix.chain(BodyStream::new(rest));
+
+    StreamBody::new(stream).boxed_unsync()
+}
+
+/// passes `body` on at no more than `rate` bytes a second, if there is a rate
+pub fn throttle(body: ProxyBody, rate: Option<u64>) -> ProxyBody {
+    let Some(rate) = rate.filter(|rate| *rate > 0) else {
+        return body;
+    };
+
+    // a tenth of a second's worth at a time keeps the rate smooth
+    let chunk = (rate / 10).max(1) as usize;
+
+    let frames = BodyStream::new(body).flat_map(move |frame| {
+        let frames: Vec<_> = match frame.map(Frame::into_data) {
+            Ok(Ok(data)) => data
+                .chunks(chunk)
+                .map(|part| Ok(Frame::data(data.slice_ref(part))))
+                .collect(),
+            Ok(Err(frame)) => vec![Ok(frame)],
+            Err(e) => vec![Err(e)],
+        };
+
+        futures::stream::iter(frames)
+    });
+
+    let paced = frames.then(move |frame| async move {
+        if let Some(data) = frame.as_ref().ok().and_then(Frame::data_ref) {
+            let pause = Duration::from_secs_f64(data.len() as f64 / rate as f64);
+            tokio::time::sleep(pause).await;
+        }
+
+        frame
+    });
+
+    StreamBody::new(paced).boxed_unsync()
+}
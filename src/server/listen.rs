@@ -0,0 +1,93 @@
+use std::net::SocketAddr;
+
+use super::Server;
+use crate::{Filter, Scribe, Upstream};
+use hyper::server::conn::http1;
+use tokio::{
+    io,
+    net::{TcpListener, TcpSocket},
+};
+
+use hyper_util::rt::TokioIo;
+
+/// A server with its address bound, ready to accept connections
+pub struct Bound<F, S> {
+    server: Server<F, S>,
+    listener: TcpListener,
+}
+
+impl<F, S> Server<F, S>
+where
+    F: Filter + Upstream + Sync + Send + 'static,
+    S: Scribe + Sync + Send + 'static,
+{
+    /// binds the listening address without accepting connections yet
+    pub fn bind(&self) -> Result<Bound<F, S>, io::Error> {
+        let socket = if self.addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+
+        socket.bind(self.addr)?;
+        socket.set_reuseaddr(true)?;
+        socket.set_reuseport(true)?;
+
+        let listener = socket.listen(1024)?;
+
+        Ok(Bound {
+            server: self.clone(),
+            listener,
+        })
+    }
+
+    /// binds and serves connections until the token is cancelled
+    pub async fn listen(&self) -> Result<(), io::Error> {
+        self.bind()?.serve().await
+    }
+}
+
+impl<F, S> Bound<F, S>
+where
+    F: Filter + Upstream + Sync + Send + 'static,
+    S: Scribe + Sync + Send + 'static,
+{
+    /// the address actually bound, to find the port picked for port 0
+    pub fn local_addr(&self) -> Result<SocketAddr, io::Error> {
+        self.listener.local_addr()
+    }
+
+    /// accepts connections until the server's token is cancelled
+    pub async fn serve(self) -> Result<(), io::Error> {
+        let token = self.server.token.clone();
+
+        loop {
+            tokio::select! {
+                _ = token.cancelled() => {
+                    return Ok(())
+                }
+
+                res = self.listener.accept() => {
+                    let (stream, _) = res?;
+
+                    let io = TokioIo::new(stream);
+
+                    let token = token.clone();
+
+                    let srv = self.server.clone();
+
+                    tokio::task::spawn(async move {
+                        tokio::select! {
+                            _ = token.cancelled() => { }
+                            res = http1::Builder::new().serve_connection(io, srv).with_upgrades() => {
+                                if let Err(err) = res {
+                                    tracing::error!("Error service connection: {:?}", err);
+                                }
+                            }
+                        }
+                    });
+                }
+            };
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! The proxy itself, embeddable with any [`Filter`](crate::Filter) and [`Scribe`](crate::Scribe)
+//!
+//! ```no_run
+//! # async fn run() -> std::io::Result<()> {
+//! let server = prax::server::Server::builder()
+//!     .listen("127.0.0.1:8091".parse().unwrap())
+//!     .filter(())
+//!     .scribe(prax::hist::Hist::default())
+//!     .build();
+//!
+//! server.listen().await
+//! # }
+//! ```
+
+use std::{net::SocketAddr, sync::Arc};
+
+use crate::PeerCertificates;
+use hyper::client::conn::http1::SendRequest;
+use tokio::sync::{Mutex, RwLock};
+use tokio_util::sync::CancellationToken;
+
+mod body;
+mod counted;
+mod listen;
+mod policy;
+mod service;
+mod tls;
+
+#[cfg(test)]
+mod test;
+
+pub use self::body::Streaming;
+pub use self::listen::Bound;
+pub use self::policy::Policy;
+pub use self::tls::{LoadError, Tls, TlsLoadError, TlsOptions};
+
+use self::body::ProxyBody;
+
+/// A running proxy, passing exchanges through the filter `F` and recording them with the scribe `S`
+///
+/// Clones share the same filter, scribe and token.
+pub struct Server<F, S> {
+    addr: SocketAddr,
+    token: CancellationToken,
+    filter: Arc<RwLock<Arc<F>>>,
+    scribe: Arc<S>,
+    tls: Option<Tls>,
+    streaming: Streaming,
+    policy: Policy,
+}
+
+/// Sets up a [`Server`], anything not set falls back to the proxy's defaults
+pub struct Builder<F, S> {
+    addr: SocketAddr,
+    token: CancellationToken,
+    filter: F,
+    scribe: S,
+    tls: Option<Tls>,
+    streaming: Streaming,
+    policy: Policy,
+}
+
+struct Tunnel<F, S> {
+    sender: Arc<Mutex<SendRequest<ProxyBody>>>,
+    host: String,
+    certificates: PeerCertificates,
+    server: Server<F, S>,
+}
+
+impl<F, S> Clone for Server<F, S> {
+    fn clone(&self) -> Self {
+        Server {
+            token: self.token.clone(),
+            addr: self.addr,
+            filter: self.filter.clone(),
+            scribe: self.scribe.clone(),
+            tls: self.tls.clone(),
+            streaming: self.streaming.clone(),
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+impl Server<(), ()> {
+    /// a server on 127.0.0.1:8091 that neither filters nor records
+    pub fn builder() -> Builder<(), ()> {
+        Builder {
+            addr: SocketAddr::from(([127, 0, 0, 1], 8091)),
+            token: CancellationToken::new(),
+            filter: (),
+            scribe: (),
+            tls: None,
+            streaming: Streaming::default(),
+            policy: Policy::default(),
+        }
+    }
+}
+
+impl<F, S> Server<F, S> {
+    /// swaps the filter used for exchanges from now on
+    pub async fn replace(&self, filter: F) {
+        let mut f = self.filter.write().await;
+
+        *f = Arc::new(filter);
+    }
+
+    /// cancelling it stops the server and closes its connections
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl<F, S> Builder<F, S> {
+    /// address to accept proxy connections on, a port of 0 picks a free one
+    pub fn listen(mut self, addr: SocketAddr) -> Self {
+        self.addr = addr;
+        self
+    }
+
+    /// stops the server once cancelled
+    pub fn token(mut self, token: CancellationToken) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// intercepts CONNECT tunnels, without it they are passed through untouched
+    pub fn tls(mut self, tls: impl Into<Option<Tls>>) -> Self {
+        self.tls = tls.into();
+        self
+    }
+
+    /// when bodies are streamed through instead of buffered for filters
+    pub fn streaming(mut self, streaming: Streaming) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// timeouts and retries for upstreams, targets can override them per host
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// what to do with exchanges, `()` passes them on untouched
+    pub fn filter<G>(self, filter: G) -> Builder<G, S> {
+        Builder {
+            addr: self.addr,
+            token: self.token,
+            filter,
+            scribe: self.scribe,
+            tls: self.tls,
+            streaming: self.streaming,
+            policy: self.policy,
+        }
+    }
+
+    /// where exchanges are recorded, `()` records nothing
+    pub fn scribe<T>(self, scribe: T) -> Builder<F, T> {
+        Builder {
+            addr: self.addr,
+            token: self.token,
+            filter: self.filter,
+            scribe,
+            tls: self.tls,
+            streaming: self.streaming,
+            policy: self.policy,
+        }
+    }
+
+    /// the server, not listening until [`Server::listen`] is awaited
+    pub fn build(self) -> Server<F, S> {
+        Server {
+            addr: self.addr,
+            token: self.token,
+            filter: Arc::new(RwLock::new(Arc::new(self.filter))),
+            scribe: Arc::new(self.scribe),
+            tls: self.tls,
+            streaming: self.streaming,
+            policy: self.policy,
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::future::Future;
+use std::io::ErrorKind;
+use std::time::Duration;
+
+use hyper::{header, StatusCode};
+use tokio::net::TcpStream;
+
+use crate::{Error, Res, Timeouts};
+
+/// How long to wait on an upstream and how often to retry connecting
+#[derive(Clone, Debug)]
+pub struct Policy {
+    pub connect: Duration,
+    pub read: Option<Duration>,
+    pub total: Option<Duration>,
+    pub retries: usize,
+    pub backoff: Duration,
+}
+
+/// Why an exchange with an upstream could not be completed
+#[derive(Debug, thiserror::Error)]
+pub enum Failure {
+    #[error("could not connect to {0}: {1}")]
+    Unreachable(String, std::io::Error),
+
+    #[error("timed out connecting to {0} after {1:?}")]
+    ConnectTimeout(String, Duration),
+
+    #[error("tls handshake with {0} failed: {1}")]
+    Handshake(String, String),
+
+    #[error("timed out waiting on the upstream after {0:?}")]
+    ReadTimeout(Duration),
+
+    #[error("exchange with the upstream took longer than {0:?}")]
+    TotalTimeout(Duration),
+
+    #[error("upstream failed: {0}")]
+    Upstream(#[from] Error),
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            connect: Duration::from_secs(10),
+            read: Some(Duration::from_secs(30)),
+            total: None,
+            retries: 5,
+            backoff: Duration::from_millis(250),
+        }
+    }
+}
+
+impl Policy {
+    /// this policy with a target's overrides applied
+    pub fn with(&self, timeouts: &Timeouts) -> Policy {
+        Policy {
+            connect: timeouts.connect.unwrap_or(self.connect),
+            read: timeouts.read.or(self.read),
+            total: timeouts.total.or(self.total),
+            retries: timeouts.retries.unwrap_or(self.retries),
+            backoff: timeouts.backoff.unwrap_or(self.backoff),
+        }
+    }
+
+    /// connects to `lookup`, retrying with a doubling backoff
+    pub async fn connect(&self, lookup: &str) -> Result<TcpStream, Failure> {
+        let mut wait = self.backoff;
+        let mut attempt = 0;
+
+        loop {
+            let failure = match tokio::time::timeout(self.connect, TcpStream::connect(lookup)).await
+            {
+                Ok(Ok(stream)) => return Ok(stream),
+                Ok(Err(e)) => Failure::Unreachable(lookup.to_string(), e),
+                Err(_) => Failure::ConnectTimeout(lookup.to_string(), self.connect),
+            };
+
+            if attempt >= self.retries {
+                return Err(failure);
+            }
+
+            tracing::debug!("retrying in {wait:?}: {failure}");
+            tokio::time::sleep(wait).await;
+
+            attempt += 1;
+            wait *= 2;
+        }
+    }
+
+    /// waits on the upstream within the read timeout
+    pub async fn read<T, E>(&self, fut: impl Future<Output = Result<T, E>>) -> Result<T, Failure>
+    where
+        Error: From<E>,
+    {
+        let Some(read) = self.read else {
+            return Ok(fut.await.map_err(Error::from)?);
+        };
+
+        match tokio::time::timeout(read, fut).await {
+            Ok(res) => Ok(res.map_err(Error::from)?),
+            Err(_) => Err(Failure::ReadTimeout(read)),
+        }
+    }
+
+    /// runs a whole exchange with the upstream within the total timeout
+    pub async fn total<T>(
+        &self,
+        fut: impl Future<Output = Result<T, Failure>>,
+    ) -> Result<T, Failure> {
+        let Some(total) = self.total else {
+            return fut.await;
+        };
+
+        tokio::time::timeout(total, fut)
+            .await
+            .unwrap_or(Err(Failure::TotalTimeout(total)))
+    }
+}
+
+impl Failure {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Failure::ConnectTimeout(_, _) | Failure::ReadTimeout(_) | Failure::TotalTimeout(_) => {
+                StatusCode::GATEWAY_TIMEOUT
+            }
+
+            Failure::Upstream(Error::IO(e)) if e.kind() == ErrorKind::TimedOut => {
+                StatusCode::GATEWAY_TIMEOUT
+            }
+
+            _ => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    /// a response describing the failure to the client
+    pub fn response(&self) -> Res<Vec<u8>> {
+        let mut res = Res::new(format!("prax: {self}\n").into_bytes());
+        *res.status_mut() = self.status();
+        res.headers_mut().insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("text/plain"),
+        );
+
+        res
+    }
+}
+
+#[test]
+fn test_with() {
+    let policy = Policy::default().with(&Timeouts {
+        connect: Some(Duration::from_secs(1)),
+        total: Some(Duration::from_secs(60)),
+        retries: Some(0),
+        ..Timeouts::default()
+    });
+
+    assert_eq!(policy.connect, Duration::from_secs(1));
+    assert_eq!(policy.read, Some(Duration::from_secs(30)));
+    assert_eq!(policy.total, Some(Duration::from_secs(60)));
+    assert_eq!(policy.retries, 0);
+    assert_eq!(policy.backoff, Duration::from_millis(250));
+}
+
+#[tokio::test]
+async fn test_connect() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let lookup = listener.local_addr().unwrap().to_string();
+
+    let policy = Policy {
+        retries: 0,
+        ..Policy::default()
+    };
+
+    assert!(policy.connect(&lookup).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_connect_retries() {
+    // nothing listens on a port that was just freed
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let lookup = listener.local_addr().unwrap().to_string();
+    drop(listener);
+
+    let policy = Policy {
+        retries: 2,
+        backoff: Duration::from_millis(20),
+        ..Policy::default()
+    };
+
+    let start = std::time::Instant::now();
+    let failure = policy.connect(&lookup).await.unwrap_err();
+
+    // waits 20ms then 40ms before giving up
+    assert!(start.elapsed() >= Duration::from_millis(60));
+    assert!(matches!(failure, Failure::Unreachable(ref host, _) if *host == lookup));
+    assert_eq!(failure.status(), StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn test_read() {
+    let policy = Policy {
+        read: Some(Duration::from_millis(10)),
+        ..Policy::default()
+    };
+
+    let answered = policy.read(async { Ok::<_, std::io::Error>(7) }).await;
+    assert!(matches!(answered, Ok(7)));
+
+    let stalled = policy
+        .read(std::future::pending::<Result<(), std::io::Error>>())
+        .await;
+    assert!(matches!(stalled, Err(Failure::ReadTimeout(_))));
+
+    let failed = policy
+        .read(async { Err::<(), _>(std::io::Error::from(ErrorKind::ConnectionReset)) })
+        .await;
+    assert!(matches!(failed, Err(Failure::Upstream(Error::IO(_)))));
+
+    // without a read timeout anything slow is waited on
+    let patient = Policy {
+        read: None,
+        ..Policy::default()
+    };
+
+    let slow = async {
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        Ok::<_, std::io::Error>(())
+    };
+    assert!(patient.read(slow).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_total() {
+    let policy = Policy {
+        total: Some(Duration::from_millis(10)),
+        ..Policy::default()
+    };
+
+    let slow = async {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        Ok(())
+    };
+    let failure = policy.total(slow).await.unwrap_err();
+    assert!(matches!(failure, Failure::TotalTimeout(_)));
+
+    // failures inside are passed on as they are
+    let unreachable = async {
+        Err::<(), _>(Failure::Unreachable(
+            "example.com:443".to_string(),
+            std::io::Error::from(ErrorKind::ConnectionRefused),
+        ))
+    };
+    let failure = policy.total(unreachable).await.unwrap_err();
+    assert!(matches!(failure, Failure::Unreachable(_, _)));
+
+    let unlimited = Policy::default();
+    assert!(unlimited.total(async { Ok(()) }).await.is_ok());
+}
+
+#[test]
+fn test_status() {
+    let timed_out = std::io::Error::from(ErrorKind::TimedOut);
+    let reset = std::io::Error::from(ErrorKind::ConnectionReset);
+    let second = Duration::from_secs(1);
+
+    let gateway_timeouts = [
+        Failure::ConnectTimeout("example.com:443".to_string(), second),
+        Failure::ReadTimeout(second),
+        Failure::TotalTimeout(second),
+        Failure::Upstream(Error::IO(timed_out)),
+    ];
+
+    for failure in gateway_timeouts {
+        assert_eq!(failure.status(), StatusCode::GATEWAY_TIMEOUT, "{failure}");
+    }
+
+    let bad_gateways = [
+        Failure::Unreachable(
+            "example.com:443".to_string(),
+            std::io::Error::from(ErrorKind::ConnectionRefused),
+        ),
+        Failure::Handshake("example.com:443".to_string(), "bad certificate".to_string()),
+        Failure::Upstream(Error::IO(reset)),
+        Failure::Upstream(Error::NoHost),
+    ];
+
+    for failure in bad_gateways {
+        assert_eq!(failure.status(), StatusCode::BAD_GATEWAY, "{failure}");
+    }
+
+    let res = Failure::ReadTimeout(second).response();
+    assert_eq!(res.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(
+        res.body(),
+        b"prax: timed out waiting on the upstream after 1s\n"
+    );
+}
@@ -0,0 +1,523 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+
+use hyper::client::conn::http1::SendRequest;
+use hyper::Uri;
+use hyper::{client::conn::http1::Builder, Method};
+use hyper_util::rt::TokioIo;
+
+use hyper::{body::Incoming, service::Service, Response};
+use rustls::pki_types::ServerName;
+use tokio::sync::{Mutex, RwLock};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use tokio_util::sync::CancellationToken;
+
+use super::Tunnel;
+
+use super::body::{self, Payload, ProxyBody};
+use super::counted::Counted;
+use super::policy::Failure;
+use super::{Policy, Server, Streaming, Tls};
+use crate::{
+    Error, Fault, Faults, Filter, Origin, Outcome, PeerCertificates, Req, Res, Result, Scribe,
+    Tunneled, Upstream,
+};
+
+impl<F, S> Service<Req<Incoming>> for Server<F, S>
+where
+    F: Filter + Upstream + Send + Sync + 'static,
+    S: Scribe + Send + Sync + 'static,
+{
+    type Response = Res<ProxyBody>;
+    type Error = Error;
+
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response>> + Send>>;
+
+    fn call(&self, req: Req<Incoming>) -> Self::Future {
+        tracing::trace!("starting to service request");
+        let Some(host) = req.uri().host() else {
+            return Box::pin(async { Err(Error::NoHost) });
+        };
+
+        let host = host.to_string();
+        let port = req.uri().port_u16().unwrap_or(80);
+        let lookup = format!("{host}:{port}");
+
+        tracing::trace!("request host detected: {lookup:?}");
+
+        let filter = self.filter.clone();
+        let scribe = self.scribe.clone();
+        let streaming = self.streaming.clone();
+        let policy = self.policy.clone();
+
+        if req.method() == Method::CONNECT {
+            let tls = self.tls.clone();
+            let srv = self.clone();
+            let token = self.token.clone();
+            let host = host.clone();
+
+            return Box::pin(async move { connect(req, tls, srv, host, lookup, token).await });
+        }
+
+        Box::pin(async move {
+            handle(
+                filter,
+                scribe,
+                &streaming,
+                &policy,
+                req,
+                lookup.clone(),
+                Connection::Lookup(lookup),
+            )
+            .await
+        })
+    }
+}
+
+impl<F, S> Service<Req<Incoming>> for Tunnel<F, S>
+where
+    F: Filter + Upstream + Send + Sync + 'static,
+    S: Scribe + Send + Sync + 'static,
+{
+    type Response = Res<ProxyBody>;
+    type Error = Error;
+
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response>> + Send>>;
+
+    fn call(&self, mut req: Req<Incoming>) -> Self::Future {
+        tracing::trace!("starting to service request");
+
+        tracing::trace!("servicing tunneled request: {req:?}");
+        req.extensions_mut().insert(self.certificates.clone());
+
+        let host = req.uri().host().unwrap_or_else(|| &self.host);
+
+        let host = host.to_string();
+        let port = req.uri().port_u16().unwrap_or(80);
+        let lookup = format!("{host}:{port}");
+
+        tracing::trace!("request host detected: {lookup:?}");
+
+        let filter = self.server.filter.clone();
+        let scribe = self.server.scribe.clone();
+        let streaming = self.server.streaming.clone();
+        let policy = self.server.policy.clone();
+
+        if req.method() == Method::CONNECT {
+            let tls = self.server.tls.clone();
+            let srv = self.server.clone();
+            let token = self.server.token.clone();
+            let host = host.clone();
+
+            return Box::pin(async move { connect(req, tls, srv, host, lookup, token).await });
+        }
+
+        let sender = self.sender.clone();
+        let conn = Connection::Tunnel(sender);
+        Box::pin(
+            async move { handle(filter, scribe, &streaming, &policy, req, lookup, conn).await },
+        )
+    }
+}
+
+async fn connect<F, S>(
+    req: Req<Incoming>,
+    tls: Option<Tls>,
+    srv: Server<F, S>,
+    host: String,
+    lookup: String,
+    token: CancellationToken,
+) -> Result<Res<ProxyBody>>
+where
+    F: Filter + Upstream + Send + Sync + 'static,
+    S: Scribe + Send + Sync + 'static,
+{
+    let (in_scope, identity, policy) = {
+        let filter = srv.filter.read().await;
+        let policy = srv.policy.with(&filter.timeouts(&lookup));
+
+        (filter.in_scope(&lookup), filter.identity(&lookup), policy)
+    };
+
+    let Some(tls) = tls.filter(|_| in_scope) else {
+        return passthrough(req, srv.scribe.clone(), &policy, lookup, token).await;
+    };
+
+    tracing::trace!("connecting to target");
+    let upstream = policy
+        .total(async {
+            let stream = policy.connect(&lookup).await?;
+
+            let handshake =
+                |e: &dyn std::fmt::Display| Failure::Handshake(lookup.clone(), e.to_string());
+            let client_tls = tls
+                .client(&lookup, &host, identity)
+                .map_err(|e| handshake(&e))?;
+            let servername = ServerName::try_from(host.clone()).map_err(|e| handshake(&e))?;
+
+            let connector = TlsConnector::from(client_tls);
+            match tokio::time::timeout(policy.connect, connector.connect(servername, stream)).await
+            {
+                Ok(connect) => connect.map_err(|e| handshake(&e)),
+                Err(_) => Err(Failure::ConnectTimeout(lookup.clone(), policy.connect)),
+            }
+        })
+        .await;
+
+    let connect = match upstream {
+        Ok(connect) => connect,
+        Err(failure) => return Ok(failed(&*srv.scribe, &req, failure).await),
+    };
+
+    let certificates = connect
+        .get_ref()
+        .1
+        .peer_certificates()
+        .map(|chain| chain.iter().map(|cert| cert.to_vec()).collect())
+        .unwrap_or_default();
+    let certificates = PeerCertificates(Arc::new(certificates));
+
+    tokio::spawn(async move {
+        tracing::trace!("upgrading connection");
+        let upgrade = match hyper::upgrade::on(req).await {
+            Ok(u) => u,
+            Err(e) => {
+                tracing::error!("failed to upgrade connection {e}");
+                return;
+            }
+        };
+
+        tracing::trace!("upgraded connection");
+
+        let io = TokioIo::new(upgrade);
+
+        tracing::trace!("creating acceptor connection");
+        let acceptor = TlsAcceptor::from(tls.server.clone());
+        let incoming = match acceptor.accept(io).await {
+            Ok(i) => i,
+            Err(e) => {
+                tracing::error!("failed to accept {e}");
+                return;
+            }
+        };
+        let tunnel = TokioIo::new(incoming);
+
+        tracing::trace!("creating sender");
+        let (sender, conn) =
+            match hyper::client::conn::http1::handshake(TokioIo::new(connect)).await {
+                Ok(o) => o,
+                Err(e) => {
+                    tracing::error!("failed to handshake {e}");
+                    return;
+                }
+            };
+
+        tracing::trace!("spawning sender poller");
+        tokio::spawn(async move {
+            if let Err(e) = conn.await {
+                tracing::error!("error in upstream connection {e}");
+            }
+        });
+
+        let sender = Arc::new(Mutex::new(sender));
+
+        tracing::trace!("spawning tunneled server");
+        tokio::spawn(async move {
+            tokio::select! {
+                () = token.cancelled() => { }
+
+                res = hyper::server::conn::http1::Builder::new().serve_connection(tunnel, Tunnel { sender, host, certificates, server: srv } ).with_upgrades() => {
+                    if let Err(err) = res {
+                        tracing::error!("Error service connection: {:?}", err);
+                    }
+                }
+            }
+        });
+    });
+
+    let builder = Response::builder()
+        .status(200)
+        .body(body::full(Vec::new()))
+        .unwrap();
+    Ok(builder)
+}
+
+/// blindly tunnels a CONNECT to the upstream, recording only its metadata
+async fn passthrough<S>(
+    req: Req<Incoming>,
+    scribe: Arc<S>,
+    policy: &Policy,
+    lookup: String,
+    token: CancellationToken,
+) -> Result<Res<ProxyBody>>
+where
+    S: Scribe + Send + Sync + 'static,
+{
+    tracing::debug!("tunneling through to {lookup}");
+
+    let mut upstream = match policy.total(policy.connect(&lookup)).await {
+        Ok(stream) => Counted::new(stream),
+        Err(failure) => return Ok(failed(&*scribe, &req, failure).await),
+    };
+
+    let ticket = scribe.report_request(&metadata(&req)).await;
+
+    tokio::spawn(async move {
+        let start = Instant::now();
+
+        let closed = match hyper::upgrade::on(req).await {
+            Ok(upgrade) => {
+                let mut client = TokioIo::new(upgrade);
+
+                tokio::select! {
+                    () = token.cancelled() => Err("proxy shut down".to_string()),
+
+                    res = tokio::io::copy_bidirectional(&mut client, &mut upstream) => {
+                        res.map(|_| ()).map_err(|e| e.to_string())
+                    }
+                }
+            }
+            Err(e) => Err(format!("failed to upgrade connection {e}")),
+        };
+
+        // every tunnel gets a response, broken ones with why and how far they got
+        let error = closed.err();
+        if let Some(error) = &error {
+            tracing::error!("tunnel to {lookup} failed {error}");
+        }
+
+        let mut res = Res::new(Vec::new());
+        res.extensions_mut().insert(Tunneled {
+            sent: upstream.written,
+            received: upstream.read,
+            duration: start.elapsed(),
+            error,
+        });
+
+        scribe.report_response(ticket, &res).await;
+    });
+
+    Ok(Response::builder()
+        .status(200)
+        .body(body::full(Vec::new()))
+        .unwrap())
+}
+
+/// records a CONNECT that could not reach its upstream and responds with why
+async fn failed<S>(scribe: &S, req: &Req<Incoming>, failure: Failure) -> Res<ProxyBody>
+where
+    S: Scribe + Send + Sync + 'static,
+{
+    tracing::error!("{failure}");
+
+    let ticket = scribe.report_request(&metadata(req)).await;
+    let res = failure.response();
+    scribe.report_response(ticket, &res).await;
+
+    res.map(body::full)
+}
+
+/// the head of a request without its body
+fn metadata<T>(req: &Req<T>) -> Req<Vec<u8>> {
+    let mut record = Req::new(Vec::new());
+    *record.method_mut() = req.method().clone();
+    *record.uri_mut() = req.uri().clone();
+    *record.version_mut() = req.version();
+    *record.headers_mut() = req.headers().clone();
+    *record.extensions_mut() = req.extensions().clone();
+
+    record
+}
+
+pub enum Connection {
+    Tunnel(Arc<Mutex<SendRequest<ProxyBody>>>),
+    Lookup(String),
+}
+
+impl Connection {
+    async fn send(
+        &self,
+        req: Req<ProxyBody>,
+        policy: &Policy,
+    ) -> std::result::Result<Response<Incoming>, Failure> {
+        match self {
+            Connection::Tunnel(sender) => {
+                let mut sender = sender.lock().await;
+                policy.read(sender.send_request(req)).await
+            }
+
+            Connection::Lookup(lookup) => {
+                let stream = policy.connect(lookup).await?;
+                let io = TokioIo::new(stream);
+
+                tracing::trace!("starting connection to requested host");
+                let (mut sender, conn) = Builder::new()
+                    .handshake::<_, ProxyBody>(io)
+                    .await
+                    .map_err(Error::from)?;
+                tokio::task::spawn(async move {
+                    if let Err(err) = conn.await {
+                        tracing::error!("Connection failed: {:?}", err);
+                    }
+                });
+
+                tracing::trace!("established connection to requested host");
+
+                policy.read(sender.send_request(req)).await
+            }
+        }
+    }
+
+    fn inject(&mut self, lookup: &str) {
+        match self {
+            Connection::Tunnel(_) => (),
+            Connection::Lookup(internal) => {
+                internal.clear();
+                internal.push_str(lookup);
+            }
+        }
+    }
+}
+
+async fn handle<F, S>(
+    filter: Arc<RwLock<Arc<F>>>,
+    scribe: Arc<S>,
+    streaming: &Streaming,
+    policy: &Policy,
+    req: Req<Incoming>,
+    mut lookup: String,
+    mut conn: Connection,
+) -> Result<Res<ProxyBody>>
+where
+    F: Filter + Upstream + Send + Sync + 'static,
+    S: Scribe + Send + Sync + 'static,
+{
+    let filter = filter.read().await.clone();
+
+    let (mut parts, body) = req.into_parts();
+    let mut payload = Payload::read(body, &parts.headers, streaming, None).await?;
+    let body = payload.take_filterable(&mut parts.extensions);
+    let mut req = Req::from_parts(parts, body);
+
+    let outcome = filter.modify_request(&mut lookup, &mut req).await?;
+    conn.inject(&lookup);
+
+    let policy = policy.with(&filter.timeouts(&lookup));
+
+    tracing::trace!("sending modified request to scribe");
+    let ticket = match payload.recorded() {
+        Some(prefix) => {
+            let filtered = std::mem::replace(req.body_mut(), prefix);
+            payload.mark(req.extensions_mut());
+
+            let ticket = scribe.report_request(&req).await;
+            *req.body_mut() = filtered;
+            ticket
+        }
+        None => scribe.report_request(&req).await,
+    };
+    tracing::trace!("done sending modified request to scribe");
+
+    match outcome {
+        Outcome::Continue => (),
+        Outcome::Respond(res) => {
+            tracing::debug!("request answered by filter");
+            scribe.report_response(ticket, &res).await;
+
+            return Ok(res.map(body::full));
+        }
+        Outcome::Drop => {
+            tracing::debug!("request dropped by filter");
+
+            // nothing came back, the record says why instead of staying pending
+            let mut res = Res::new(Vec::new());
+            *res.status_mut() = hyper::StatusCode::BAD_GATEWAY;
+
+            let mut faults = req
+                .extensions()
+                .get::<Faults>()
+                .cloned()
+                .unwrap_or_default();
+            if !faults.0.contains(&Fault::Dropped) {
+                faults.0.push(Fault::Dropped);
+            }
+            res.extensions_mut().insert(faults);
+
+            scribe.report_response(ticket, &res).await;
+            return Err(Error::Dropped);
+        }
+    }
+
+    let mut origin = metadata(&req);
+    *origin.body_mut() = req.body().clone();
+    let origin = Origin(Arc::new(origin));
+
+    let mut builder = Uri::builder();
+    if let Some(pq) = req.uri().path_and_query() {
+        builder = builder.path_and_query(pq.clone());
+    }
+
+    *req.uri_mut() = builder.build().unwrap();
+
+    let upload = Faults::throttle(req.extensions());
+
+    let upstream = policy
+        .total(async {
+            let req = req.map(|b| body::throttle(payload.into_body(b), upload));
+            let res = conn.send(req, &policy).await?;
+
+            let (parts, body) = res.into_parts();
+            let payload = Payload::read(body, &parts.headers, streaming, policy.read).await?;
+
+            Ok((parts, payload))
+        })
+        .await;
+
+    let (mut parts, mut payload) = match upstream {
+        Ok(upstream) => upstream,
+        Err(failure) => {
+            tracing::error!("{failure}");
+
+            let (parts, body) = failure.response().into_parts();
+            (parts, Payload::Full(body))
+        }
+    };
+
+    let body = payload.take_filterable(&mut parts.extensions);
+    let mut res = Res::from_parts(parts, body);
+    res.extensions_mut().insert(origin);
+
+    match filter.modify_response(&mut lookup, &mut res).await? {
+        Outcome::Continue => (),
+        Outcome::Respond(replaced) => {
+            tracing::debug!("response replaced by filter");
+            res = replaced;
+            payload = Payload::Full(Vec::new());
+        }
+        Outcome::Drop => {
+            tracing::debug!("response dropped by filter");
+            scribe.report_response(ticket, &res).await;
+
+            return Err(Error::Dropped);
+        }
+    }
+
+    tracing::trace!("sending modified response to scribe");
+    match payload.recorded() {
+        Some(prefix) => {
+            let filtered = std::mem::replace(res.body_mut(), prefix);
+            payload.mark(res.extensions_mut());
+
+            scribe.report_response(ticket.clone(), &res).await;
+            *res.body_mut() = filtered;
+        }
+        None => scribe.report_response(ticket.clone(), &res).await,
+    }
+    tracing::trace!("done sending modified response to scribe");
+
+    tracing::trace!("finished to service request");
+    let download = Faults::throttle(res.extensions());
+    Ok(res.map(|b| body::throttle(payload.into_reported_body(b, scribe, ticket), download)))
+}
@@ -0,0 +1,199 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use super::Server;
+use crate::{hist::Hist, DynFilter, Fault, Filter, Outcome, Req, Res, Upstream};
+
+/// answers anything sent to port 1 itself, drops port 2, stamps everything else
+#[derive(Clone)]
+struct Mock;
+
+impl Filter for Mock {
+    async fn modify_request(
+        &self,
+        hostname: &mut String,
+        req: &mut Req<Vec<u8>>,
+    ) -> crate::Result<Outcome> {
+        if hostname.ends_with(":1") {
+            let mut res = Res::new(b"mocked".to_vec());
+            *res.status_mut() = hyper::StatusCode::IM_A_TEAPOT;
+
+            return Ok(Outcome::Respond(res));
+        }
+
+        if hostname.ends_with(":2") {
+            return Ok(Outcome::Drop);
+        }
+
+        req.headers_mut().insert("x-mock", "1".parse().unwrap());
+        Ok(Outcome::Continue)
+    }
+
+    async fn modify_response(
+        &self,
+        _: &mut String,
+        _: &mut Res<Vec<u8>>,
+    ) -> crate::Result<Outcome> {
+        Ok(Outcome::Continue)
+    }
+}
+
+impl Upstream for Mock {}
+
+/// sends a raw request through the proxy, returning the raw response
+async fn exchange(proxy: SocketAddr, request: String) -> String {
+    let mut stream = TcpStream::connect(proxy).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    response
+}
+
+async fn start(hist: Arc<Hist>) -> (Server<Mock, Arc<Hist>>, SocketAddr) {
+    let server = Server::builder()
+        .listen("127.0.0.1:0".parse().unwrap())
+        .filter(Mock)
+        .scribe(hist)
+        .build();
+
+    let bound = server.bind().unwrap();
+    let addr = bound.local_addr().unwrap();
+    tokio::spawn(bound.serve());
+
+    (server, addr)
+}
+
+#[tokio::test]
+async fn answered() {
+    let hist = Arc::new(Hist::default());
+    let (server, proxy) = start(hist.clone()).await;
+
+    let response = exchange(
+        proxy,
+        "GET http://127.0.0.1:1/ HTTP/1.1\r\nhost: 127.0.0.1:1\r\nconnection: close\r\n\r\n"
+            .to_string(),
+    )
+    .await;
+
+    assert!(response.starts_with("HTTP/1.1 418"), "{response}");
+    assert!(response.ends_with("mocked"), "{response}");
+
+    let entry = hist.entry(0).unwrap();
+    assert_eq!(entry.response.map(|res| res.status), Some(418));
+
+    server.token().cancel();
+}
+
+#[tokio::test]
+async fn boxed() {
+    let filter: Box<dyn DynFilter> = Box::new(Mock);
+    let hist = Arc::new(Hist::default());
+
+    let server = Server::builder()
+        .listen("127.0.0.1:0".parse().unwrap())
+        .filter(filter)
+        .scribe(hist.clone())
+        .build();
+
+    let bound = server.bind().unwrap();
+    let proxy = bound.local_addr().unwrap();
+    tokio::spawn(bound.serve());
+
+    let response = exchange(
+        proxy,
+        "GET http://127.0.0.1:1/ HTTP/1.1\r\nhost: 127.0.0.1:1\r\nconnection: close\r\n\r\n"
+            .to_string(),
+    )
+    .await;
+
+    assert!(response.starts_with("HTTP/1.1 418"), "{response}");
+    assert_eq!(
+        hist.entry(0).unwrap().response.map(|res| res.status),
+        Some(418)
+    );
+
+    server.token().cancel();
+}
+
+#[tokio::test]
+async fn dropped() {
+    let hist = Arc::new(Hist::default());
+    let (server, proxy) = start(hist.clone()).await;
+
+    let mut stream = TcpStream::connect(proxy).await.unwrap();
+    stream
+        .write_all(b"GET http://127.0.0.1:2/ HTTP/1.1\r\nhost: 127.0.0.1:2\r\n\r\n")
+        .await
+        .unwrap();
+
+    // closed without an answer
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response).await;
+
+    let response = hist.entry(0).unwrap().response.unwrap();
+    assert_eq!(response.faults, vec![Fault::Dropped]);
+
+    server.token().cancel();
+}
+
+#[tokio::test]
+async fn forwarded() {
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let target = upstream.local_addr().unwrap();
+
+    let seen = tokio::spawn(async move {
+        let (mut stream, _) = upstream.accept().await.unwrap();
+
+        let mut buf = vec![0; 4096];
+        let len = stream.read(&mut buf).await.unwrap();
+
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 8\r\n\r\nupstream")
+            .await
+            .unwrap();
+
+        String::from_utf8_lossy(&buf[..len]).to_lowercase()
+    });
+
+    let hist = Arc::new(Hist::default());
+    let (server, proxy) = start(hist.clone()).await;
+
+    let response = exchange(
+        proxy,
+        format!("GET http://{target}/path HTTP/1.1\r\nhost: {target}\r\nconnection: close\r\n\r\n"),
+    )
+    .await;
+
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    assert!(response.ends_with("upstream"), "{response}");
+    assert!(seen.await.unwrap().contains("x-mock: 1"));
+
+    let entry = hist.entry(0).unwrap();
+    assert_eq!(entry.request.path, "/path");
+
+    server.token().cancel();
+}
+
+#[tokio::test]
+async fn counted() {
+    let (near, mut far) = tokio::io::duplex(64);
+    let mut stream = super::counted::Counted::new(near);
+
+    stream.write_all(b"ping").await.unwrap();
+    far.write_all(b"pong").await.unwrap();
+
+    let mut buf = [0; 4];
+    stream.read_exact(&mut buf).await.unwrap();
+
+    // a broken stream keeps what it got through
+    drop(far);
+    assert!(stream.write_all(b"lost").await.is_err());
+
+    assert_eq!((stream.written, stream.read), (4, 4));
+}
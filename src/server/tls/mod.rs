@@ -0,0 +1,250 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use rustls::{
+    client::{VerifierBuilderError, WebPkiServerVerifier},
+    pki_types::{CertificateDer, PrivateKeyDer},
+    ClientConfig, RootCertStore, ServerConfig,
+};
+use rustls_pemfile::Item;
+
+use crate::Identity;
+
+use self::verify::Verifier;
+
+mod verify;
+
+#[derive(Clone)]
+pub struct Tls {
+    pub client: Arc<ClientConfig>,
+    pub server: Arc<ServerConfig>,
+
+    verifier: Arc<Verifier>,
+    identities: Arc<HashMap<String, Identity>>,
+    clients: Arc<Mutex<HashMap<Identity, Arc<ClientConfig>>>>,
+}
+
+/// Where to find what the proxy needs to intercept tls
+#[derive(Clone, Debug)]
+pub struct TlsOptions {
+    /// private key (pem) for the certificates the proxy presents
+    pub key: PathBuf,
+
+    /// certificate (pem) the proxy presents, or signs with
+    pub cert: PathBuf,
+
+    /// extra certificate authorities (pem) to trust for upstream servers
+    pub cas: Vec<PathBuf>,
+
+    /// upstream hosts to skip certificate verification for
+    pub insecure: Vec<String>,
+
+    /// upstream hosts pinned to a certificate's sha256 fingerprint
+    pub pins: Vec<(String, [u8; 32])>,
+
+    /// client certificates to present to upstream hosts ("host" or "host:port"),
+    /// loaded and checked by [`Tls::load`]
+    pub identities: Vec<(String, Identity)>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TlsLoadError {
+    #[error("failed to load key: {0}")]
+    Key(LoadError),
+    #[error("failed to load cert: {0}")]
+    Cert(LoadError),
+
+    #[error("failed to load client identity {0:?}: {1}")]
+    Identity(Identity, LoadError),
+
+    #[error("failed to load certificate authority: {0}")]
+    Authority(LoadError),
+
+    #[error("failed to build verifier: {0}")]
+    Verifier(#[from] VerifierBuilderError),
+
+    #[error("failed to construct context: {0}")]
+    Tls(#[from] rustls::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    #[error("no cryptographical content")]
+    NoContent,
+
+    #[error("content mismatch")]
+    ContentMismatch,
+
+    #[error("io error {0}")]
+    IO(#[from] std::io::Error),
+}
+
+impl Tls {
+    pub fn load(opts: TlsOptions) -> Result<Self, TlsLoadError> {
+        let mut root_store = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+
+        for ca in &opts.cas {
+            let certs = load_certs(ca).map_err(TlsLoadError::Authority)?;
+            if certs.is_empty() {
+                return Err(TlsLoadError::Authority(LoadError::NoContent));
+            }
+
+            for cert in certs {
+                root_store.add(cert)?;
+            }
+        }
+
+        let key = load_key(&opts.key).map_err(TlsLoadError::Key)?;
+        let certs = load_certs(&opts.cert).map_err(TlsLoadError::Cert)?;
+
+        let mut pins = HashMap::<String, Vec<[u8; 32]>>::new();
+        for (host, fingerprint) in opts.pins {
+            pins.entry(host).or_default().push(fingerprint);
+        }
+
+        let verifier = Arc::new(Verifier::new(
+            WebPkiServerVerifier::builder(Arc::new(root_store)).build()?,
+            opts.insecure.into_iter().collect(),
+            pins,
+        ));
+
+        let client = ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(verifier.clone())
+            .with_no_client_auth();
+
+        // identities from the command line are checked up front,
+        // only those that rules hand out are loaded when first used
+        let mut clients = HashMap::new();
+        for (_, identity) in &opts.identities {
+            if !clients.contains_key(identity) {
+                let client = authenticated(&verifier, identity)?;
+                clients.insert(identity.clone(), Arc::new(client));
+            }
+        }
+
+        let identities = opts.identities.into_iter().collect();
+
+        let server = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)?;
+
+        let client = Arc::new(client);
+        let server = Arc::new(server);
+
+        Ok(Tls {
+            client,
+            server,
+            verifier,
+            identities: Arc::new(identities),
+            clients: Arc::new(Mutex::new(clients)),
+        })
+    }
+
+    /// the client config to connect upstream with
+    ///
+    /// an identity given in the options for `lookup` ("host:port")
+    /// or `host` takes precedence over `identity`
+    pub fn client(
+        &self,
+        lookup: &str,
+        host: &str,
+        identity: Option<Identity>,
+    ) -> Result<Arc<ClientConfig>, TlsLoadError> {
+        let identity = self
+            .identities
+            .get(lookup)
+            .or_else(|| self.identities.get(host))
+            .cloned()
+            .or(identity);
+
+        let Some(identity) = identity else {
+            return Ok(self.client.clone());
+        };
+
+        let cached = self
+            .clients
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&identity)
+            .cloned();
+
+        if let Some(client) = cached {
+            return Ok(client);
+        }
+
+        // racing loads of the same identity are harmless, the last one is kept
+        let client = Arc::new(authenticated(&self.verifier, &identity)?);
+
+        self.clients
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(identity, client.clone());
+
+        Ok(client)
+    }
+}
+
+/// a client config presenting `identity`
+fn authenticated(
+    verifier: &Arc<Verifier>,
+    identity: &Identity,
+) -> Result<ClientConfig, TlsLoadError> {
+    let failed = |e| TlsLoadError::Identity(identity.clone(), e);
+
+    let key = load_key(&identity.key).map_err(failed)?;
+    let certs = load_certs(&identity.cert).map_err(failed)?;
+
+    if certs.is_empty() {
+        return Err(failed(LoadError::NoContent));
+    }
+
+    let client = ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(verifier.clone())
+        .with_client_auth_cert(certs, key)?;
+
+    Ok(client)
+}
+
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, LoadError> {
+    let file = File::open(path)?;
+
+    let mut buf = BufReader::new(file);
+
+    let Some(item) = rustls_pemfile::read_one(&mut buf)? else {
+        return Err(LoadError::NoContent);
+    };
+
+    match item {
+        Item::Pkcs1Key(key) => Ok(PrivateKeyDer::Pkcs1(key)),
+        Item::Pkcs8Key(key) => Ok(PrivateKeyDer::Pkcs8(key)),
+        Item::Sec1Key(key) => Ok(PrivateKeyDer::Sec1(key)),
+        _ => Err(LoadError::ContentMismatch),
+    }
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, LoadError> {
+    let file = File::open(path)?;
+    let mut certs = Vec::new();
+
+    let mut buf = BufReader::new(file);
+
+    for item in rustls_pemfile::read_all(&mut buf) {
+        let item = item?;
+
+        match item {
+            Item::X509Certificate(cert) => certs.push(cert),
+            _ => continue,
+        }
+    }
+
+    Ok(certs)
+}
@@ -0,0 +1,172 @@
+use std::{collections::HashMap, collections::HashSet, net::IpAddr, sync::Arc};
+
+use rustls::{
+    client::{
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        WebPkiServerVerifier,
+    },
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    DigitallySignedStruct, SignatureScheme,
+};
+
+/// Verifies upstream certificates against the root store
+/// unless a host is pinned or exempted
+///
+/// A pin replaces validation entirely, only the fingerprint of the end entity is compared.
+#[derive(Debug)]
+pub struct Verifier {
+    inner: Arc<WebPkiServerVerifier>,
+    insecure: HashSet<String>,
+    pins: HashMap<String, Vec<[u8; 32]>>,
+}
+
+impl Verifier {
+    pub fn new(
+        inner: Arc<WebPkiServerVerifier>,
+        insecure: HashSet<String>,
+        pins: HashMap<String, Vec<[u8; 32]>>,
+    ) -> Self {
+        Verifier {
+            inner,
+            insecure,
+            pins,
+        }
+    }
+}
+
+impl ServerCertVerifier for Verifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let host = match server_name {
+            ServerName::DnsName(name) => name.as_ref().to_string(),
+            ServerName::IpAddress(ip) => IpAddr::from(*ip).to_string(),
+            _ => String::new(),
+        };
+
+        if let Some(pins) = self.pins.get(&host) {
+            let digest = ring::digest::digest(&ring::digest::SHA256, end_entity.as_ref());
+
+            return if pins.iter().any(|pin| pin == digest.as_ref()) {
+                Ok(ServerCertVerified::assertion())
+            } else {
+                tracing::error!("certificate for {host} does not match any pin");
+                Err(rustls::Error::General(format!(
+                    "certificate for {host} does not match any pin"
+                )))
+            };
+        }
+
+        if self.insecure.contains(&host) {
+            tracing::debug!("skipping certificate verification for {host}");
+            return Ok(ServerCertVerified::assertion());
+        }
+
+        self.inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+fn verifier(insecure: &[&str], pins: &[(&str, &[u8])]) -> Verifier {
+    let roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+
+    let pins = pins
+        .iter()
+        .map(|(host, cert)| {
+            let digest = ring::digest::digest(&ring::digest::SHA256, cert);
+            let mut fingerprint = [0u8; 32];
+            fingerprint.copy_from_slice(digest.as_ref());
+
+            (host.to_string(), vec![fingerprint])
+        })
+        .collect();
+
+    Verifier::new(
+        WebPkiServerVerifier::builder(Arc::new(roots))
+            .build()
+            .unwrap(),
+        insecure.iter().map(ToString::to_string).collect(),
+        pins,
+    )
+}
+
+#[cfg(test)]
+fn verify(
+    verifier: &Verifier,
+    host: &str,
+    cert: &[u8],
+) -> Result<ServerCertVerified, rustls::Error> {
+    let server_name = ServerName::try_from(host.to_string()).unwrap();
+
+    verifier.verify_server_cert(
+        &CertificateDer::from(cert.to_vec()),
+        &[],
+        &server_name,
+        &[],
+        UnixTime::now(),
+    )
+}
+
+#[test]
+fn test_insecure() {
+    let verifier = verifier(&["insecure.test"], &[]);
+
+    // not even a certificate, but nothing is checked
+    assert!(verify(&verifier, "insecure.test", b"garbage").is_ok());
+    assert!(verify(&verifier, "other.test", b"garbage").is_err());
+}
+
+#[test]
+fn test_pinned() {
+    let verifier = verifier(&["pinned.test"], &[("pinned.test", b"pinned certificate")]);
+
+    assert!(verify(&verifier, "pinned.test", b"pinned certificate").is_ok());
+
+    // a pin wins over insecure, anything else is rejected
+    let Err(rustls::Error::General(reason)) = verify(&verifier, "pinned.test", b"other") else {
+        panic!("mismatched pin should be rejected");
+    };
+    assert_eq!(reason, "certificate for pinned.test does not match any pin");
+
+    // hosts without a pin are not affected by it
+    assert!(verify(&verifier, "other.test", b"pinned certificate").is_err());
+}
+
+#[test]
+fn test_webpki() {
+    let verifier = verifier(&[], &[("pinned.test", b"pinned certificate")]);
+
+    // what webpki makes of a certificate it can not parse
+    let err = verify(&verifier, "example.com", b"garbage").unwrap_err();
+    assert!(!matches!(err, rustls::Error::General(_)), "{err}");
+}
@@ -233,6 +233,15 @@ impl<T> Store<T, Append> {
 
         slot
     }
+
+    /// slots handed out so far, the latest may not be filled in yet
+    pub fn len(&self) -> usize {
+        self.inserter.0.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 impl<T, I> std::fmt::Debug for Store<T, I>